@@ -0,0 +1,138 @@
+//! Compare the speed of the [`Parser`]s, and check that they produce the same pages.
+//!
+//! With no arguments a synthetic dump is generated in memory. Otherwise the argument should be
+//! the path to a decompressed (`.xml`) dump file, which is read from disk by each parser in turn.
+//!
+//! ```text
+//! cargo run --release --example compare_parsers [dump.xml]
+//! ```
+
+use wiki_dump_analyzer::{PageIterator, Parser, ScanOptions};
+
+/// Totals used to compare the output of the parsers.
+#[derive(Debug, PartialEq, Eq, Default)]
+struct Summary {
+    pages: usize,
+    revisions: usize,
+    body_bytes: usize,
+    id_sum: i64,
+}
+
+fn summarize(pages: impl Iterator<Item = wiki_dump_analyzer::Page>) -> Summary {
+    let mut summary = Summary::default();
+    for page in pages {
+        summary.pages += 1;
+        summary.id_sum += page.id();
+        for rev in page.revisions() {
+            summary.revisions += 1;
+            summary.id_sum += rev.id() + rev.contributor_id().unwrap_or(0);
+            summary.body_bytes += rev.body().len() + rev.comment().map_or(0, String::len);
+        }
+    }
+    summary
+}
+
+/// Generate a dump with `page_count` pages, each with a varying number of revisions.
+fn synthetic_dump(page_count: i64) -> Vec<u8> {
+    let mut xml = String::from(
+        "<mediawiki xmlns=\"http://www.mediawiki.org/xml/export-0.10/\" version=\"0.10\">\n\
+         <siteinfo><sitename>Synthetic</sitename><namespaces>\
+         <namespace key=\"0\" case=\"first-letter\" /></namespaces></siteinfo>\n",
+    );
+    let mut rev_id = 1;
+    for page_id in 1..=page_count {
+        xml += &format!(
+            "<page>\n<title>Page &amp; {}</title>\n<ns>{}</ns>\n<id>{}</id>\n",
+            page_id,
+            page_id % 4,
+            page_id
+        );
+        let mut body = String::new();
+        for i in 0..(page_id % 50 + 1) {
+            body += &format!(
+                "Line {} of [[Page {}]] about the caf\u{e9}, with a citation&lt;ref&gt;{{{{cite web|title=Source}}}}&lt;/ref&gt; and &quot;quoted&quot; text.\n",
+                i, page_id
+            );
+            let contributor = if i % 3 == 0 {
+                "<contributor><ip>127.0.0.1</ip></contributor>".to_string()
+            } else if i % 7 == 0 {
+                "<contributor deleted=\"deleted\" />".to_string()
+            } else {
+                format!(
+                    "<contributor><username>User {}</username><id>{}</id></contributor>",
+                    i, i
+                )
+            };
+            xml += &format!(
+                "<revision>\n<id>{}</id>\n<parentid>{}</parentid>\n\
+                 <timestamp>2020-01-{:02}T00:00:00Z</timestamp>\n{}\n<minor />\n\
+                 <comment>Edit {}</comment>\n<model>wikitext</model>\n<format>text/x-wiki</format>\n\
+                 <text bytes=\"{}\" xml:space=\"preserve\">{}</text>\n<sha1>abc</sha1>\n</revision>\n",
+                rev_id,
+                rev_id - 1,
+                i % 28 + 1,
+                contributor,
+                i,
+                body.len(),
+                body
+            );
+            rev_id += 1;
+        }
+        xml += "</page>\n";
+    }
+    xml += "</mediawiki>\n";
+    xml.into_bytes()
+}
+
+fn time<T>(name: &str, bytes: u64, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    println!(
+        "{:>5}: {:>8.3}s ({:.1} MB/s)",
+        name,
+        elapsed.as_secs_f64(),
+        bytes as f64 / elapsed.as_secs_f64() / 1e6
+    );
+    result
+}
+
+fn main() {
+    let parsers = [Parser::Xml, Parser::Fast];
+    let summaries: Vec<Summary> = match std::env::args_os().nth(1) {
+        Some(path) => {
+            let bytes = std::fs::metadata(&path).expect("Cannot read dump").len();
+            parsers
+                .iter()
+                .map(|&parser| {
+                    time(&format!("{:?}", parser), bytes, || {
+                        let file = std::fs::File::open(&path).expect("Cannot open dump");
+                        let options = ScanOptions::new().with_parser(parser);
+                        summarize(PageIterator::from_reader_with(
+                            std::io::BufReader::new(file),
+                            options,
+                        ))
+                    })
+                })
+                .collect()
+        }
+        None => {
+            let xml = synthetic_dump(20_000);
+            parsers
+                .iter()
+                .map(|&parser| {
+                    time(&format!("{:?}", parser), xml.len() as u64, || {
+                        let options = ScanOptions::new().with_parser(parser);
+                        summarize(PageIterator::from_reader_with(&xml[..], options))
+                    })
+                })
+                .collect()
+        }
+    };
+
+    println!("{:?}", summaries[0]);
+    assert_eq!(
+        summaries[0], summaries[1],
+        "The parsers produced different pages"
+    );
+}
@@ -0,0 +1,456 @@
+mod ui;
+
+use super::StoreArgs;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::text::Line;
+use wiki_dump_analyzer::{CancellationToken, DumpStore, Page};
+
+#[derive(Debug, clap::Args)]
+pub struct BrowseArgs {
+    /// Open the page with this ID.
+    #[arg(long, conflicts_with = "title")]
+    id: Option<i64>,
+
+    /// Open the page with exactly this title, including any namespace prefix.
+    #[arg(long)]
+    title: Option<String>,
+}
+
+/// What is shown of the open page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Revisions,
+    Body,
+    /// The differences from the selected revision's parent.
+    Diff,
+}
+
+/// The state of the browser.
+struct App {
+    store: DumpStore,
+    page: Option<Page>,
+    /// The index of the parent of each revision of the page (see [`parent_indices`]).
+    parents: Vec<Option<usize>>,
+    /// The selected revision of the page.
+    revisions: ratatui::widgets::TableState,
+    view: View,
+    /// The lines of the body or diff being shown, and the index of the first line on screen.
+    lines: Vec<Line<'static>>,
+    scroll: usize,
+    /// The text typed at the search prompt, while it is open.
+    prompt: Option<String>,
+    /// The search running in the background (if any).
+    search: Option<Search>,
+    status: String,
+    quit: bool,
+}
+
+/// A search for a page, which runs on a background thread so that the screen stays responsive
+/// and the search can be cancelled.
+struct Search {
+    query: String,
+    token: CancellationToken,
+    result: std::sync::mpsc::Receiver<std::io::Result<Option<Page>>>,
+}
+
+/// Number of rows moved by Page Up and Page Down.
+const PAGE_ROWS: usize = 20;
+
+/// How often a running search is checked for a result.
+const SEARCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+const HELP: &str = "/ search  ↑↓ select  enter body  d diff  q quit";
+
+/// The search prompt. Titles must match exactly, including any namespace prefix.
+const PROMPT: &str = "Exact title or #ID: ";
+
+impl App {
+    fn new(store: DumpStore) -> App {
+        App {
+            store,
+            page: None,
+            parents: Vec::new(),
+            revisions: Default::default(),
+            view: View::Revisions,
+            lines: Vec::new(),
+            scroll: 0,
+            prompt: None,
+            search: None,
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    /// Start searching for a page by its exact title, or by ID if `query` is like `#123`,
+    /// cancelling any search already running.
+    fn start_search(self: &mut App, query: String) {
+        let id = match query.strip_prefix('#').map(str::parse::<i64>) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => {
+                self.status = format!("Invalid page ID {:?}", &query[1..]);
+                return;
+            }
+            None => None,
+        };
+        self.cancel_search();
+
+        let token = CancellationToken::new();
+        let options = self
+            .store
+            .options()
+            .clone()
+            .with_cancellation(token.clone());
+        let store = self.store.clone().with_options(options);
+        let (sender, result) = std::sync::mpsc::channel();
+        let title = query.clone();
+        std::thread::spawn(move || {
+            let page = match id {
+                Some(id) => store.page_by_id(id),
+                None => store.page_by_title(&title),
+            };
+            // Nothing is waiting for the result if the search was cancelled
+            let _ = sender.send(page);
+        });
+
+        self.status = if id.is_none() && self.store.index().is_none() {
+            format!(
+                "Searching every dump for {:?} (use --index-file to make title searches fast)... Esc to cancel",
+                query
+            )
+        } else {
+            format!("Searching for {:?}... Esc to cancel", query)
+        };
+        self.search = Some(Search {
+            query,
+            token,
+            result,
+        });
+    }
+
+    /// Stop the running search (if any).
+    fn cancel_search(self: &mut App) {
+        if let Some(search) = self.search.take() {
+            search.token.cancel();
+            self.status = format!("Cancelled the search for {:?}", search.query);
+        }
+    }
+
+    /// Open the page found by the running search, if it has finished.
+    fn poll_search(self: &mut App) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let page = match search.result.try_recv() {
+            Ok(page) => page,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.status = format!("The search for {:?} failed", search.query);
+                self.search = None;
+                return;
+            }
+        };
+        let query = self.search.take().map(|s| s.query).unwrap_or_default();
+        match page {
+            Ok(Some(page)) => {
+                self.status = HELP.to_string();
+                // Select the latest revision
+                self.revisions.select(page.revisions().len().checked_sub(1));
+                self.parents = parent_indices(&page);
+                self.page = Some(page);
+                self.view = View::Revisions;
+            }
+            Ok(None) => self.status = format!("No page {:?}", query),
+            Err(e) => self.status = format!("The search for {:?} failed: {}", query, e),
+        }
+    }
+
+    fn selected(self: &App) -> Option<(&Page, usize)> {
+        let page = self.page.as_ref()?;
+        let index = self.revisions.selected()?;
+        (index < page.revisions().len()).then_some((page, index))
+    }
+
+    /// Show the body of the selected revision, or its differences from its parent.
+    fn show(self: &mut App, view: View) {
+        let Some((page, index)) = self.selected() else {
+            return;
+        };
+        // Bodies spilled to disk by a memory budget are read back
+        let body = |i: usize| page.revisions()[i].read_body();
+        let lines = match view {
+            View::Body => body(index).map(|body| ui::body_lines(&body)),
+            View::Diff => self.parents[index]
+                .map(body)
+                .transpose()
+                .and_then(|parent| {
+                    Ok(ui::diff_lines(
+                        parent.as_deref().unwrap_or(""),
+                        &body(index)?,
+                    ))
+                }),
+            View::Revisions => Ok(Vec::new()),
+        };
+        self.lines = match lines {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.status = format!("The body could not be read: {}", e);
+                return;
+            }
+        };
+        self.scroll = 0;
+        self.view = view;
+    }
+
+    /// Move the selection by `rows` (up if negative), keeping the current view.
+    fn move_selection(self: &mut App, rows: isize) {
+        let Some(page) = &self.page else {
+            return;
+        };
+        let last = page.revisions().len().saturating_sub(1);
+        let index = self.revisions.selected().unwrap_or(0);
+        let index = index.saturating_add_signed(rows).min(last);
+        self.revisions.select(Some(index));
+        if self.view != View::Revisions {
+            self.show(self.view);
+        }
+    }
+
+    /// Return the change in body size of the revision at `index` from its parent.
+    fn size_delta(self: &App, page: &Page, index: usize) -> i64 {
+        let size = |i: usize| page.revisions()[i].body_len() as i64;
+        size(index) - self.parents[index].map_or(0, size)
+    }
+
+    fn scroll_by(self: &mut App, rows: isize) {
+        let last = self.lines.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(rows).min(last);
+    }
+
+    fn handle_key(self: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+        if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+            match self.search {
+                Some(_) => self.cancel_search(),
+                None => self.quit = true,
+            }
+            return;
+        }
+        if let Some(prompt) = &mut self.prompt {
+            match code {
+                KeyCode::Enter => {
+                    let query = prompt.trim().to_string();
+                    self.prompt = None;
+                    if !query.is_empty() {
+                        self.start_search(query);
+                    }
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Backspace => {
+                    prompt.pop();
+                }
+                KeyCode::Char(c) => prompt.push(c),
+                _ => {}
+            }
+            return;
+        }
+        if code == KeyCode::Esc && self.search.is_some() {
+            self.cancel_search();
+            return;
+        }
+
+        match (self.view, code) {
+            (_, KeyCode::Char('/')) => self.prompt = Some(String::new()),
+            (View::Revisions, KeyCode::Char('q') | KeyCode::Esc) => self.quit = true,
+            (_, KeyCode::Char('q') | KeyCode::Esc) => self.view = View::Revisions,
+            (View::Revisions, KeyCode::Enter) | (View::Diff, KeyCode::Char('b')) => {
+                self.show(View::Body)
+            }
+            (View::Revisions | View::Body, KeyCode::Char('d')) => self.show(View::Diff),
+            (View::Diff, KeyCode::Char('d')) => self.show(View::Body),
+            (View::Revisions, KeyCode::Up | KeyCode::Char('k')) => self.move_selection(-1),
+            (View::Revisions, KeyCode::Down | KeyCode::Char('j')) => self.move_selection(1),
+            (View::Revisions, KeyCode::PageUp) => self.move_selection(-(PAGE_ROWS as isize)),
+            (View::Revisions, KeyCode::PageDown) => self.move_selection(PAGE_ROWS as isize),
+            (View::Revisions, KeyCode::Home | KeyCode::Char('g')) => {
+                self.move_selection(isize::MIN)
+            }
+            (View::Revisions, KeyCode::End | KeyCode::Char('G')) => self.move_selection(isize::MAX),
+            (_, KeyCode::Left | KeyCode::Char('p')) => self.move_selection(-1),
+            (_, KeyCode::Right | KeyCode::Char('n')) => self.move_selection(1),
+            (_, KeyCode::Up | KeyCode::Char('k')) => self.scroll_by(-1),
+            (_, KeyCode::Down | KeyCode::Char('j')) => self.scroll_by(1),
+            (_, KeyCode::PageUp) => self.scroll_by(-(PAGE_ROWS as isize)),
+            (_, KeyCode::PageDown | KeyCode::Char(' ')) => self.scroll_by(PAGE_ROWS as isize),
+            (_, KeyCode::Home | KeyCode::Char('g')) => self.scroll = 0,
+            (_, KeyCode::End | KeyCode::Char('G')) => self.scroll_by(isize::MAX),
+            _ => {}
+        }
+    }
+}
+
+/// Return the index of the parent of each revision of `page`: the revision with its parent ID,
+/// or otherwise the previous revision.
+fn parent_indices(page: &Page) -> Vec<Option<usize>> {
+    let positions: std::collections::HashMap<i64, usize> = page
+        .revisions()
+        .iter()
+        .enumerate()
+        .map(|(index, rev)| (rev.id(), index))
+        .collect();
+    page.revisions()
+        .iter()
+        .enumerate()
+        .map(|(index, rev)| {
+            rev.parent_id()
+                .and_then(|id| positions.get(&id).copied())
+                .or(index.checked_sub(1))
+        })
+        .collect()
+}
+
+pub fn run(store_args: &StoreArgs, args: &BrowseArgs) -> std::io::Result<()> {
+    let store = store_args.open_pages()?;
+    // Progress written to stderr would garble the screen
+    let options = store.options().clone().without_progress();
+    let mut app = App::new(store.with_options(options));
+    app.status = HELP.to_string();
+    match (&args.id, &args.title) {
+        (Some(id), _) => app.start_search(format!("#{}", id)),
+        (None, Some(title)) => app.start_search(title.clone()),
+        (None, None) => {}
+    }
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut ratatui::DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    while !app.quit {
+        app.poll_search();
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        // While a search is running, keys are still handled (so it can be cancelled) but the
+        // result is checked regularly
+        if app.search.is_some() && !event::poll(SEARCH_POLL_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key.code, key.modifiers);
+            }
+        }
+    }
+    // Stop any search still running, so that its `7z` processes are killed
+    app.cancel_search();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A page with revisions given as `(revision ID, parent ID, body)`, in order.
+    fn page(revisions: &[(i64, Option<i64>, &str)]) -> Page {
+        let mut xml = "<mediawiki><page><title>A</title><ns>0</ns><id>1</id>".to_string();
+        for (id, parent_id, body) in revisions {
+            let parent_id =
+                parent_id.map_or(String::new(), |id| format!("<parentid>{}</parentid>", id));
+            xml += &format!(
+                "<revision><id>{}</id>{}<timestamp>2020-01-01T00:00:00Z</timestamp>\
+                 <contributor><username>User</username><id>1</id></contributor>\
+                 <model>wikitext</model><format>text/x-wiki</format><text>{}</text></revision>",
+                id, parent_id, body
+            );
+        }
+        xml += "</page></mediawiki>";
+        wiki_dump_analyzer::PageIterator::from_reader(xml.as_bytes())
+            .next()
+            .unwrap()
+    }
+
+    fn app(page: Page) -> App {
+        let mut app = App::new(DumpStore::from_dumps(std::path::PathBuf::new(), Vec::new()));
+        app.parents = parent_indices(&page);
+        app.revisions.select(page.revisions().len().checked_sub(1));
+        app.page = Some(page);
+        app
+    }
+
+    #[test]
+    fn parents_are_found_by_id_or_position() {
+        let page = page(&[
+            (10, None, "a"),
+            (11, Some(10), "ab"),
+            // A revert to the first revision
+            (12, Some(10), "abc"),
+            // A parent missing from the dump
+            (13, Some(5), "abcd"),
+            (14, None, "abcde"),
+        ]);
+        assert_eq!(
+            parent_indices(&page),
+            [None, Some(0), Some(0), Some(2), Some(3)]
+        );
+        assert_eq!(parent_indices(&self::page(&[])), []);
+    }
+
+    #[test]
+    fn size_deltas_are_from_the_parent() {
+        let app = app(page(&[
+            (10, None, "abc"),
+            (11, Some(10), "abcdef"),
+            (12, Some(10), "a"),
+        ]));
+        let page = app.page.as_ref().unwrap();
+        let deltas: Vec<i64> = (0..3).map(|i| app.size_delta(page, i)).collect();
+        assert_eq!(deltas, [3, 3, -2]);
+    }
+
+    #[test]
+    fn only_rows_on_screen_are_drawn() {
+        let revisions: Vec<(i64, Option<i64>, &str)> =
+            (100..200).map(|id| (id, None, "text")).collect();
+        let mut app = app(page(&revisions));
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 10)).unwrap();
+        let screen = |terminal: &ratatui::Terminal<ratatui::backend::TestBackend>| {
+            let buffer = terminal.backend().buffer();
+            (0..buffer.area.height)
+                .map(|y| {
+                    (0..buffer.area.width)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect::<String>()
+                })
+                .collect::<Vec<String>>()
+        };
+        // The index and revision ID in the row at `y`
+        let row = |terminal: &ratatui::Terminal<ratatui::backend::TestBackend>, y: usize| {
+            let line = &screen(terminal)[y];
+            let cells: Vec<&str> = line.split_whitespace().take(2).collect();
+            cells.join(" ")
+        };
+
+        // The latest revision is selected, so the last rows are shown
+        terminal.draw(|frame| ui::draw(frame, &mut app)).unwrap();
+        assert_eq!(app.revisions.offset(), 95);
+        assert_eq!(row(&terminal, 3), "95 195");
+        assert_eq!(row(&terminal, 7), "99 199");
+        let highlighted = |terminal: &ratatui::Terminal<ratatui::backend::TestBackend>, y| {
+            terminal.backend().buffer()[(0, y)]
+                .modifier
+                .contains(ratatui::style::Modifier::REVERSED)
+        };
+        assert!(highlighted(&terminal, 7));
+
+        // The rows shown stay put until the selection moves off screen
+        app.move_selection(-3);
+        terminal.draw(|frame| ui::draw(frame, &mut app)).unwrap();
+        assert_eq!(app.revisions.offset(), 95);
+        assert!(highlighted(&terminal, 4) && !highlighted(&terminal, 7));
+        app.move_selection(-10);
+        terminal.draw(|frame| ui::draw(frame, &mut app)).unwrap();
+        assert_eq!(app.revisions.offset(), 86);
+        assert_eq!(row(&terminal, 3), "86 186");
+        assert!(highlighted(&terminal, 3));
+    }
+}
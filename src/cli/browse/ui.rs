@@ -0,0 +1,178 @@
+use super::{App, View, PROMPT};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap};
+use ratatui::Frame;
+
+/// The longest time spent working out a diff, after which the diff may not be minimal.
+const DIFF_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, main, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let title = match &app.page {
+        Some(page) => Line::from(vec![
+            Span::from(page.title().clone()).bold(),
+            Span::from(format!(
+                "  ID {}  namespace {}  {} revisions",
+                page.id(),
+                page.namespace(),
+                page.revisions().len()
+            )),
+        ]),
+        None => Line::from(
+            "No page open: press / to search by exact title (including any namespace prefix), \
+             or by ID like #123",
+        ),
+    };
+    frame.render_widget(title.reversed(), header);
+
+    match app.view {
+        View::Revisions => draw_revisions(frame, app, main),
+        View::Body | View::Diff => draw_text(frame, app, main),
+    }
+
+    let status = match &app.prompt {
+        Some(prompt) => Line::from(format!("{}{}", PROMPT, prompt)),
+        None => Line::from(app.status.as_str()).dim(),
+    };
+    frame.render_widget(status, footer);
+    if let Some(prompt) = &app.prompt {
+        let x = footer.x + PROMPT.len() as u16 + prompt.chars().count() as u16;
+        frame.set_cursor_position((x.min(footer.right().saturating_sub(1)), footer.y));
+    }
+}
+
+fn draw_revisions(frame: &mut Frame, app: &mut App, area: Rect) {
+    let Some(page) = &app.page else {
+        return;
+    };
+    // Only the rows on screen are built, as a page may have many thousands of revisions: the
+    // first row shown is kept unless the selection has moved off screen
+    let height = area.height.saturating_sub(3) as usize;
+    let selected = app.revisions.selected();
+    let mut offset = app.revisions.offset();
+    if let Some(selected) = selected {
+        offset = offset.clamp(selected.saturating_sub(height.saturating_sub(1)), selected);
+    }
+    *app.revisions.offset_mut() = offset;
+    let revisions = page
+        .revisions()
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height);
+    let rows = revisions.map(|(index, rev)| {
+        let delta = app.size_delta(page, index);
+        let delta_style = match delta {
+            d if d > 0 => Style::new().fg(Color::Green),
+            d if d < 0 => Style::new().fg(Color::Red),
+            _ => Style::new(),
+        };
+        Row::new(vec![
+            Cell::from(index.to_string()),
+            Cell::from(rev.id().to_string()),
+            Cell::from(rev.timestamp().format("%Y-%m-%d %H:%M").to_string()),
+            Cell::from(
+                rev.contributor_username()
+                    .or(rev.contributor_ip())
+                    .map_or("(deleted)", String::as_str)
+                    .to_string(),
+            ),
+            Cell::from(rev.body_len().to_string()),
+            Cell::from(format!("{:+}", delta)).style(delta_style),
+            Cell::from(rev.comment().map_or("", String::as_str).to_string()),
+        ])
+    });
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(12),
+        Constraint::Length(16),
+        Constraint::Length(24),
+        Constraint::Length(9),
+        Constraint::Length(8),
+        Constraint::Min(10),
+    ];
+    let header = Row::new([
+        "INDEX",
+        "REVISION",
+        "TIMESTAMP",
+        "CONTRIBUTOR",
+        "SIZE",
+        "DELTA",
+        "COMMENT",
+    ])
+    .bold();
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::new().borders(Borders::TOP | Borders::BOTTOM))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state =
+        ratatui::widgets::TableState::default().with_selected(selected.map(|s| s - offset));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_text(frame: &mut Frame, app: &App, area: Rect) {
+    let Some((page, index)) = app.selected() else {
+        return;
+    };
+    let rev = &page.revisions()[index];
+    let mut title = format!(
+        " Revision {} (index {}) at {} by {} ",
+        rev.id(),
+        index,
+        rev.timestamp().format("%Y-%m-%dT%H:%M:%SZ"),
+        rev.contributor_username()
+            .or(rev.contributor_ip())
+            .map_or("(deleted)", String::as_str)
+    );
+    if app.view == View::Diff {
+        title += "- changes from its parent ";
+    }
+    // Each line takes at least one row, so only the lines which can be on screen are wrapped
+    let end = (app.scroll + area.height as usize).min(app.lines.len());
+    let lines = app.lines[app.scroll.min(end)..end].to_vec();
+    let text = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::new()
+            .borders(Borders::TOP | Borders::BOTTOM)
+            .title(title),
+    );
+    frame.render_widget(text, area);
+}
+
+/// Split a body into lines for display.
+pub fn body_lines(body: &str) -> Vec<Line<'static>> {
+    body.lines()
+        .map(|line| Line::from(line.to_string()))
+        .collect()
+}
+
+/// Describe the changes from `old` to `new` as a unified diff, colouring added and removed lines.
+pub fn diff_lines(old: &str, new: &str) -> Vec<Line<'static>> {
+    let diff = similar::TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new);
+    let mut lines = Vec::new();
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        lines.push(Line::from(hunk.header().to_string()).fg(Color::Cyan));
+        for change in hunk.iter_changes() {
+            let (sign, style) = match change.tag() {
+                similar::ChangeTag::Delete => ('-', Style::new().fg(Color::Red)),
+                similar::ChangeTag::Insert => ('+', Style::new().fg(Color::Green)),
+                similar::ChangeTag::Equal => (' ', Style::new()),
+            };
+            let text = change.value().trim_end_matches(['\n', '\r']);
+            lines.push(Line::styled(format!("{}{}", sign, text), style));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::from("(no changes)").dim());
+    }
+    lines
+}
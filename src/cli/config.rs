@@ -0,0 +1,79 @@
+/// Settings read from the configuration file, which are used when they are not given as arguments
+/// or environment variables.
+///
+/// ```toml
+/// dump_dir = "/data/wikipedia/rawDumps"
+/// index_file = "/data/wikipedia/index.tsv"
+/// ```
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct Config {
+    /// Directory containing the dump files.
+    pub dump_dir: Option<std::path::PathBuf>,
+    /// Page index file (see [`wiki_dump_analyzer::DumpStore::with_index_file`]).
+    pub index_file: Option<std::path::PathBuf>,
+}
+
+impl Config {
+    /// Read the configuration file at `path`, or at the default location if `path` is [`None`].
+    /// A missing file at the default location is treated as an empty configuration.
+    pub fn load(path: Option<&std::path::Path>) -> std::io::Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(e),
+        };
+        toml::from_str(&text).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid config file {}: {}", path.display(), e),
+            )
+        })
+    }
+}
+
+/// The default location of the configuration file:
+/// `$XDG_CONFIG_HOME/wiki-dump-analyzer/config.toml` (or `~/.config/...`).
+fn default_path() -> Option<std::path::PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => std::path::PathBuf::from(dir),
+        _ => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("wiki-dump-analyzer").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_files_are_loaded() {
+        let dir =
+            std::env::temp_dir().join(format!("wiki-dump-analyzer-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "dump_dir = \"/data/dumps\"\n").unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.dump_dir, Some("/data/dumps".into()));
+        assert_eq!(config.index_file, None);
+
+        std::fs::write(&path, "dump_dir = 3\n").unwrap();
+        let error = Config::load(Some(&path)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("Invalid config file"));
+
+        // A file given explicitly must exist
+        let error = Config::load(Some(&dir.join("missing.toml"))).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+#[cfg(feature = "parquet")]
+mod parquet_writer;
+mod record;
+
+use super::{cancellable, parse_start_time, parse_time, write_streamed, StoreArgs};
+use rayon::prelude::*;
+use record::{PageRecord, Record, RevisionRecord};
+use wiki_dump_analyzer::{CancellationToken, Page, Revision};
+
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Write one record per page (summarizing its matching revisions) or one per revision.
+    #[arg(long, value_enum, default_value_t = Level::Revisions)]
+    level: Level,
+
+    /// Output format [default: from the extension of --output, otherwise jsonl].
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Include revision bodies (for --level pages, the body of the last matching revision).
+    #[arg(long)]
+    bodies: bool,
+
+    /// Write to this file rather than stdout.
+    #[arg(short, long)]
+    output: Option<std::path::PathBuf>,
+}
+
+/// Which pages and revisions to export. Pages are selected by namespace, ID and title, and
+/// revisions by time and contributor; a page is only exported if it has a selected revision.
+#[derive(Debug, clap::Args)]
+struct FilterArgs {
+    /// Only pages in this namespace (may be repeated).
+    #[arg(long)]
+    namespace: Vec<i64>,
+
+    /// Only pages with this ID or higher.
+    #[arg(long)]
+    min_id: Option<i64>,
+
+    /// Only pages with this ID or lower.
+    #[arg(long)]
+    max_id: Option<i64>,
+
+    /// Only pages whose title (including any namespace prefix) matches this regular expression.
+    #[arg(long)]
+    title: Option<regex::Regex>,
+
+    /// Only revisions made at or after this time, given as an RFC 3339 timestamp or a date (which
+    /// means the start of that day, UTC).
+    #[arg(long, value_parser = parse_start_time)]
+    since: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Only revisions made at or before this time, given as an RFC 3339 timestamp or a date (which
+    /// means the end of that day, UTC).
+    #[arg(long, value_parser = parse_time)]
+    until: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Only revisions by this user name or IP address (may be repeated).
+    #[arg(long)]
+    contributor: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Level {
+    Pages,
+    Revisions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// One JSON object per line.
+    Jsonl,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Apache Parquet.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl FilterArgs {
+    /// Return whether `page` is selected, regardless of its revisions.
+    fn selects_page(self: &FilterArgs, page: &Page) -> bool {
+        (self.namespace.is_empty() || self.namespace.contains(&page.namespace()))
+            && self.min_id.is_none_or(|min| page.id() >= min)
+            && self.max_id.is_none_or(|max| page.id() <= max)
+            && self.title.as_ref().is_none_or(|r| r.is_match(page.title()))
+    }
+
+    /// Return whether `rev` is selected.
+    fn selects_revision(self: &FilterArgs, rev: &Revision) -> bool {
+        self.since.is_none_or(|since| *rev.timestamp() >= since)
+            && self.until.is_none_or(|until| *rev.timestamp() <= until)
+            && (self.contributor.is_empty()
+                || self.contributor.iter().any(|c| {
+                    rev.contributor_username() == Some(c) || rev.contributor_ip() == Some(c)
+                }))
+    }
+
+    /// Return the selected revisions of `page`, or [`None`] if the page is not selected.
+    fn select<'a>(self: &FilterArgs, page: &'a Page) -> Option<Vec<&'a Revision>> {
+        if !self.selects_page(page) {
+            return None;
+        }
+        let revisions: Vec<&Revision> = page
+            .revisions()
+            .iter()
+            .filter(|rev| self.selects_revision(rev))
+            .collect();
+        (!revisions.is_empty()).then_some(revisions)
+    }
+}
+
+impl Format {
+    /// Choose the format from the extension of the output file.
+    fn from_path(path: &std::path::Path) -> std::io::Result<Format> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            #[cfg(feature = "parquet")]
+            Some("parquet") => Ok(Format::Parquet),
+            #[cfg(not(feature = "parquet"))]
+            Some("parquet") => Err(super::invalid_input(
+                "Parquet output is not available: build with the `parquet` feature".to_string(),
+            )),
+            _ => Ok(Format::Jsonl),
+        }
+    }
+}
+
+/// Writes records in one of the output formats.
+trait RecordWriter<R> {
+    fn write(&mut self, record: &R) -> std::io::Result<()>;
+
+    /// Write anything buffered, and any footer, to the output.
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+struct JsonlWriter<W: std::io::Write> {
+    out: W,
+}
+
+impl<W: std::io::Write, R: serde::Serialize> RecordWriter<R> for JsonlWriter<W> {
+    fn write(&mut self, record: &R) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+struct CsvWriter<W: std::io::Write> {
+    out: csv::Writer<W>,
+}
+
+impl<W: std::io::Write, R: serde::Serialize> RecordWriter<R> for CsvWriter<W> {
+    fn write(&mut self, record: &R) -> std::io::Result<()> {
+        Ok(self.out.serialize(record)?)
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Create a writer of records in `format`.
+#[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+fn record_writer<R: Record + 'static>(
+    format: Format,
+    out: Box<dyn std::io::Write + Send>,
+    bodies: bool,
+) -> std::io::Result<Box<dyn RecordWriter<R>>> {
+    Ok(match format {
+        Format::Jsonl => Box::new(JsonlWriter { out }),
+        Format::Csv => Box::new(CsvWriter {
+            out: csv::Writer::from_writer(out),
+        }),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => Box::new(parquet_writer::ParquetWriter::new(out, bodies)?),
+    })
+}
+
+pub fn run(store_args: &StoreArgs, args: &ExportArgs) -> std::io::Result<()> {
+    let filter = &args.filter;
+    let mut store = store_args.open_pages()?;
+    if filter.min_id.is_some() || filter.max_id.is_some() {
+        store = store.in_page_id_range(
+            filter.min_id.unwrap_or(i64::MIN),
+            filter.max_id.unwrap_or(i64::MAX),
+        );
+    }
+    let (store, token) = cancellable(store);
+
+    let format = match (args.format, &args.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => Format::from_path(path)?,
+        (None, None) => Format::Jsonl,
+    };
+    let out: Box<dyn std::io::Write + Send> = match &args.output {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+    };
+
+    let bodies = args.bodies;
+    match args.level {
+        Level::Pages => export(
+            &store,
+            &token,
+            |page| {
+                filter
+                    .select(&page)
+                    .map(|revisions| PageRecord::new(&page, &revisions, bodies))
+                    .into_iter()
+                    .collect::<std::io::Result<_>>()
+            },
+            record_writer(format, out, bodies)?,
+        ),
+        Level::Revisions => export(
+            &store,
+            &token,
+            |page| {
+                filter
+                    .select(&page)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|rev| RevisionRecord::new(&page, rev, bodies))
+                    .collect::<std::io::Result<_>>()
+            },
+            record_writer(format, out, bodies)?,
+        ),
+    }
+}
+
+/// Read the pages of `store` in parallel, turning each into records, and write them as they are
+/// produced (so not in any particular order). Reading stops at the first error.
+fn export<R: Record>(
+    store: &wiki_dump_analyzer::DumpStore,
+    token: &CancellationToken,
+    records: impl Fn(Page) -> std::io::Result<Vec<R>> + Sync + Send,
+    mut writer: Box<dyn RecordWriter<R>>,
+) -> std::io::Result<()> {
+    let records = store
+        .par_pages()
+        .map(records)
+        .filter(|records| records.as_ref().map_or(true, |records| !records.is_empty()));
+    write_streamed(records, token, |records| {
+        records?.iter().try_for_each(|record| writer.write(record))
+    })?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_page;
+
+    fn filter() -> FilterArgs {
+        FilterArgs {
+            namespace: Vec::new(),
+            min_id: None,
+            max_id: None,
+            title: None,
+            since: None,
+            until: None,
+            contributor: Vec::new(),
+        }
+    }
+
+    /// The IDs of the revisions of `page` selected by `filter`.
+    fn selected(filter: &FilterArgs, page: &Page) -> Option<Vec<i64>> {
+        filter
+            .select(page)
+            .map(|revisions| revisions.iter().map(|rev| rev.id()).collect())
+    }
+
+    #[test]
+    fn pages_are_selected_by_namespace_id_and_title() {
+        let page = test_page(5, 1, "Talk:A", &[(50, "2020-01-01T00:00:00Z", "U", "a")]);
+        assert_eq!(selected(&filter(), &page), Some(vec![50]));
+
+        let namespace = |namespace: Vec<i64>| FilterArgs {
+            namespace,
+            ..filter()
+        };
+        assert_eq!(selected(&namespace(vec![0, 1]), &page), Some(vec![50]));
+        assert_eq!(selected(&namespace(vec![0]), &page), None);
+
+        let ids = |min_id, max_id| FilterArgs {
+            min_id,
+            max_id,
+            ..filter()
+        };
+        assert_eq!(selected(&ids(Some(5), Some(5)), &page), Some(vec![50]));
+        assert_eq!(selected(&ids(Some(6), None), &page), None);
+        assert_eq!(selected(&ids(None, Some(4)), &page), None);
+
+        let title = |title: &str| FilterArgs {
+            title: Some(regex::Regex::new(title).unwrap()),
+            ..filter()
+        };
+        assert_eq!(selected(&title("^Talk:"), &page), Some(vec![50]));
+        assert_eq!(selected(&title("^A$"), &page), None);
+    }
+
+    #[test]
+    fn revisions_are_selected_by_time_and_contributor() {
+        let page = test_page(
+            1,
+            0,
+            "A",
+            &[
+                (10, "2020-01-01T00:00:00Z", "Alice", "a"),
+                (11, "2020-01-02T12:00:00Z", "127.0.0.1", "b"),
+                (12, "2020-01-03T00:00:00Z", "Bob", "c"),
+            ],
+        );
+        // Dates mean the start of the day for --since and the end of the day for --until
+        let window = |since: &str, until: &str| FilterArgs {
+            since: Some(parse_start_time(since).unwrap()),
+            until: Some(parse_time(until).unwrap()),
+            ..filter()
+        };
+        assert_eq!(
+            selected(&window("2020-01-02", "2020-01-02"), &page),
+            Some(vec![11])
+        );
+        assert_eq!(
+            selected(&window("2020-01-01T00:00:01Z", "2020-01-03"), &page),
+            Some(vec![11, 12])
+        );
+        // A page without selected revisions is not selected
+        assert_eq!(selected(&window("2021-01-01", "2021-12-31"), &page), None);
+
+        let contributors = |contributor: &[&str]| FilterArgs {
+            contributor: contributor.iter().map(|c| c.to_string()).collect(),
+            ..filter()
+        };
+        assert_eq!(
+            selected(&contributors(&["Bob", "127.0.0.1"]), &page),
+            Some(vec![11, 12])
+        );
+        assert_eq!(selected(&contributors(&["Carol"]), &page), None);
+    }
+
+    #[test]
+    fn formats_are_inferred_from_extensions() {
+        let format = |path: &str| Format::from_path(std::path::Path::new(path)).unwrap();
+        assert_eq!(format("out.csv"), Format::Csv);
+        assert_eq!(format("out.jsonl"), Format::Jsonl);
+        assert_eq!(format("out.json"), Format::Jsonl);
+        assert_eq!(format("out"), Format::Jsonl);
+        #[cfg(feature = "parquet")]
+        assert_eq!(format("out.parquet"), Format::Parquet);
+        #[cfg(not(feature = "parquet"))]
+        assert_eq!(
+            Format::from_path(std::path::Path::new("out.parquet"))
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use super::record::{Column, ColumnKind, Record, Value};
+use super::RecordWriter;
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use parquet::file::writer::SerializedFileWriter;
+
+/// Rows are written in a row group once this many are buffered...
+const ROW_GROUP_ROWS: usize = 100_000;
+/// ...or once their text (mostly bodies) reaches this many bytes.
+const ROW_GROUP_BYTES: usize = 128 * 1024 * 1024;
+
+/// Writes records to a Parquet file, buffering the values of each column until a row group is
+/// complete.
+pub struct ParquetWriter<R: Record> {
+    writer: SerializedFileWriter<Box<dyn std::io::Write + Send>>,
+    columns: Vec<Column>,
+    buffers: Vec<ColumnBuffer>,
+    rows: usize,
+    bytes: usize,
+    record: std::marker::PhantomData<R>,
+}
+
+/// The buffered values of a column, with their definition levels (1 for a value, 0 for a null)
+/// if the column is optional.
+enum ColumnBuffer {
+    Integer(Vec<i64>, Vec<i16>),
+    Text(Vec<ByteArray>, Vec<i16>),
+}
+
+impl<R: Record> ParquetWriter<R> {
+    pub fn new(
+        out: Box<dyn std::io::Write + Send>,
+        bodies: bool,
+    ) -> std::io::Result<ParquetWriter<R>> {
+        let columns = R::columns(bodies);
+        let schema = parquet::schema::parser::parse_message_type(&message_type(&columns))?;
+        let properties = parquet::file::properties::WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .build();
+        let writer =
+            SerializedFileWriter::new(out, std::sync::Arc::new(schema), properties.into())?;
+        let buffers = columns
+            .iter()
+            .map(|c| match c.kind {
+                ColumnKind::Integer | ColumnKind::Timestamp => {
+                    ColumnBuffer::Integer(Vec::new(), Vec::new())
+                }
+                ColumnKind::Text => ColumnBuffer::Text(Vec::new(), Vec::new()),
+            })
+            .collect();
+        Ok(ParquetWriter {
+            writer,
+            columns,
+            buffers,
+            rows: 0,
+            bytes: 0,
+            record: std::marker::PhantomData,
+        })
+    }
+
+    /// Write the buffered rows as a row group.
+    fn flush_row_group(&mut self) -> std::io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        for (column, buffer) in self.columns.iter().zip(&mut self.buffers) {
+            let mut writer = row_group.next_column()?.expect("Missing column");
+            match buffer {
+                ColumnBuffer::Integer(values, levels) => {
+                    writer.typed::<Int64Type>().write_batch(
+                        values,
+                        column.optional.then_some(levels.as_slice()),
+                        None,
+                    )?;
+                    values.clear();
+                    levels.clear();
+                }
+                ColumnBuffer::Text(values, levels) => {
+                    writer.typed::<ByteArrayType>().write_batch(
+                        values,
+                        column.optional.then_some(levels.as_slice()),
+                        None,
+                    )?;
+                    values.clear();
+                    levels.clear();
+                }
+            }
+            writer.close()?;
+        }
+        row_group.close()?;
+        self.rows = 0;
+        self.bytes = 0;
+        Ok(())
+    }
+}
+
+impl<R: Record> RecordWriter<R> for ParquetWriter<R> {
+    fn write(&mut self, record: &R) -> std::io::Result<()> {
+        for (value, buffer) in record.values().into_iter().zip(&mut self.buffers) {
+            match (value, buffer) {
+                (Value::Integer(value), ColumnBuffer::Integer(values, levels)) => {
+                    levels.push(value.is_some() as i16);
+                    values.extend(value);
+                }
+                (Value::Text(value), ColumnBuffer::Text(values, levels)) => {
+                    levels.push(value.is_some() as i16);
+                    if let Some(text) = value {
+                        self.bytes += text.len();
+                        values.push(ByteArray::from(text));
+                    }
+                }
+                _ => panic!("Value does not match the column type"),
+            }
+        }
+        self.rows += 1;
+        if self.rows >= ROW_GROUP_ROWS || self.bytes >= ROW_GROUP_BYTES {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> std::io::Result<()> {
+        self.flush_row_group()?;
+        let mut out = self.writer.into_inner()?;
+        out.flush()
+    }
+}
+
+/// Describe the columns in Parquet's schema language.
+fn message_type(columns: &[Column]) -> String {
+    let mut message = "message record {\n".to_string();
+    for column in columns {
+        let repetition = if column.optional {
+            "OPTIONAL"
+        } else {
+            "REQUIRED"
+        };
+        let (physical, logical) = match column.kind {
+            ColumnKind::Integer => ("INT64", ""),
+            ColumnKind::Timestamp => ("INT64", " (TIMESTAMP(MILLIS,true))"),
+            ColumnKind::Text => ("BINARY", " (STRING)"),
+        };
+        message += &format!(
+            "  {} {} {}{};\n",
+            repetition, physical, column.name, logical
+        );
+    }
+    message + "}"
+}
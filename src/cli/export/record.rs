@@ -0,0 +1,247 @@
+use wiki_dump_analyzer::{Page, Revision};
+
+/// A row of the export.
+pub trait Record: serde::Serialize + Send {
+    /// Describe the columns of the records, which only include the body if `bodies` is true.
+    #[cfg(feature = "parquet")]
+    fn columns(bodies: bool) -> Vec<Column>;
+
+    /// Return the values of the columns of this record, in the order given by
+    /// [`Record::columns`].
+    #[cfg(feature = "parquet")]
+    fn values(&self) -> Vec<Value<'_>>;
+}
+
+/// The name and type of a column.
+#[cfg(feature = "parquet")]
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+    pub optional: bool,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Integer,
+    /// A UTC time, stored as milliseconds since the epoch.
+    Timestamp,
+    Text,
+}
+
+/// The value of a column in a record.
+#[cfg(feature = "parquet")]
+#[derive(Debug, Clone, Copy)]
+pub enum Value<'a> {
+    Integer(Option<i64>),
+    Text(Option<&'a str>),
+}
+
+#[cfg(feature = "parquet")]
+impl Column {
+    fn required(name: &'static str, kind: ColumnKind) -> Column {
+        Column {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    fn optional(name: &'static str, kind: ColumnKind) -> Column {
+        Column {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+}
+
+/// A page, summarizing its selected revisions.
+#[derive(Debug, serde::Serialize)]
+pub struct PageRecord {
+    page_id: i64,
+    namespace: i64,
+    title: String,
+    /// Number of selected revisions.
+    revisions: usize,
+    first_revision_id: i64,
+    first_timestamp: chrono::DateTime<chrono::Utc>,
+    last_revision_id: i64,
+    last_timestamp: chrono::DateTime<chrono::Utc>,
+    /// User name or IP address of the contributor of the last selected revision.
+    last_contributor: Option<String>,
+    /// Size in bytes of the body of the last selected revision.
+    size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+impl PageRecord {
+    /// Describe `page`, of which `revisions` (which must not be empty) are selected. A body
+    /// spilled to disk by a memory budget is read back.
+    pub fn new(page: &Page, revisions: &[&Revision], bodies: bool) -> std::io::Result<PageRecord> {
+        let first = revisions.first().expect("No revisions");
+        let last = revisions.last().expect("No revisions");
+        Ok(PageRecord {
+            page_id: page.id(),
+            namespace: page.namespace(),
+            title: page.title().clone(),
+            revisions: revisions.len(),
+            first_revision_id: first.id(),
+            first_timestamp: *first.timestamp(),
+            last_revision_id: last.id(),
+            last_timestamp: *last.timestamp(),
+            last_contributor: last
+                .contributor_username()
+                .or(last.contributor_ip())
+                .cloned(),
+            size: last.body_len(),
+            body: match bodies {
+                true => Some(last.read_body()?.into_owned()),
+                false => None,
+            },
+        })
+    }
+}
+
+/// A revision of a page.
+#[derive(Debug, serde::Serialize)]
+pub struct RevisionRecord {
+    page_id: i64,
+    namespace: i64,
+    title: String,
+    revision_id: i64,
+    parent_id: Option<i64>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    contributor_id: Option<i64>,
+    contributor_username: Option<String>,
+    contributor_ip: Option<String>,
+    comment: Option<String>,
+    model: String,
+    format: String,
+    /// Size in bytes of the body.
+    size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+impl RevisionRecord {
+    pub fn new(page: &Page, rev: &Revision, bodies: bool) -> std::io::Result<RevisionRecord> {
+        Ok(RevisionRecord {
+            page_id: page.id(),
+            namespace: page.namespace(),
+            title: page.title().clone(),
+            revision_id: rev.id(),
+            parent_id: rev.parent_id(),
+            timestamp: *rev.timestamp(),
+            contributor_id: rev.contributor_id(),
+            contributor_username: rev.contributor_username().cloned(),
+            contributor_ip: rev.contributor_ip().cloned(),
+            comment: rev.comment().cloned(),
+            model: rev.model().clone(),
+            format: rev.format().clone(),
+            size: rev.body_len(),
+            body: match bodies {
+                true => Some(rev.read_body()?.into_owned()),
+                false => None,
+            },
+        })
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl Record for PageRecord {
+    fn columns(bodies: bool) -> Vec<Column> {
+        use ColumnKind::*;
+        let mut columns = vec![
+            Column::required("page_id", Integer),
+            Column::required("namespace", Integer),
+            Column::required("title", Text),
+            Column::required("revisions", Integer),
+            Column::required("first_revision_id", Integer),
+            Column::required("first_timestamp", Timestamp),
+            Column::required("last_revision_id", Integer),
+            Column::required("last_timestamp", Timestamp),
+            Column::optional("last_contributor", Text),
+            Column::required("size", Integer),
+        ];
+        if bodies {
+            columns.push(Column::required("body", Text));
+        }
+        columns
+    }
+
+    fn values(&self) -> Vec<Value<'_>> {
+        let mut values = vec![
+            Value::Integer(Some(self.page_id)),
+            Value::Integer(Some(self.namespace)),
+            Value::Text(Some(&self.title)),
+            Value::Integer(Some(self.revisions as i64)),
+            Value::Integer(Some(self.first_revision_id)),
+            Value::Integer(Some(self.first_timestamp.timestamp_millis())),
+            Value::Integer(Some(self.last_revision_id)),
+            Value::Integer(Some(self.last_timestamp.timestamp_millis())),
+            Value::Text(self.last_contributor.as_deref()),
+            Value::Integer(Some(self.size as i64)),
+        ];
+        if let Some(body) = &self.body {
+            values.push(Value::Text(Some(body)));
+        }
+        values
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+impl Record for PageRecord {}
+
+#[cfg(feature = "parquet")]
+impl Record for RevisionRecord {
+    fn columns(bodies: bool) -> Vec<Column> {
+        use ColumnKind::*;
+        let mut columns = vec![
+            Column::required("page_id", Integer),
+            Column::required("namespace", Integer),
+            Column::required("title", Text),
+            Column::required("revision_id", Integer),
+            Column::optional("parent_id", Integer),
+            Column::required("timestamp", Timestamp),
+            Column::optional("contributor_id", Integer),
+            Column::optional("contributor_username", Text),
+            Column::optional("contributor_ip", Text),
+            Column::optional("comment", Text),
+            Column::required("model", Text),
+            Column::required("format", Text),
+            Column::required("size", Integer),
+        ];
+        if bodies {
+            columns.push(Column::required("body", Text));
+        }
+        columns
+    }
+
+    fn values(&self) -> Vec<Value<'_>> {
+        let mut values = vec![
+            Value::Integer(Some(self.page_id)),
+            Value::Integer(Some(self.namespace)),
+            Value::Text(Some(&self.title)),
+            Value::Integer(Some(self.revision_id)),
+            Value::Integer(self.parent_id),
+            Value::Integer(Some(self.timestamp.timestamp_millis())),
+            Value::Integer(self.contributor_id),
+            Value::Text(self.contributor_username.as_deref()),
+            Value::Text(self.contributor_ip.as_deref()),
+            Value::Text(self.comment.as_deref()),
+            Value::Text(Some(&self.model)),
+            Value::Text(Some(&self.format)),
+            Value::Integer(Some(self.size as i64)),
+        ];
+        if let Some(body) = &self.body {
+            values.push(Value::Text(Some(body)));
+        }
+        values
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+impl Record for RevisionRecord {}
@@ -0,0 +1,101 @@
+use super::{cancellable, write_streamed, StoreArgs};
+use std::io::Write;
+use wiki_dump_analyzer::{Grep, GrepRevision, PageMatches};
+
+#[derive(Debug, clap::Args)]
+pub struct GrepArgs {
+    /// Regular expression to search for in the body of every revision.
+    pattern: String,
+
+    /// Match case-insensitively.
+    #[arg(short, long)]
+    ignore_case: bool,
+
+    /// Only search pages in this namespace (may be repeated).
+    #[arg(long)]
+    namespace: Vec<i64>,
+
+    /// List every matching revision, not just the first and last.
+    #[arg(long)]
+    all_revisions: bool,
+
+    /// Write one JSON object per page rather than text.
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(store_args: &StoreArgs, args: &GrepArgs) -> std::io::Result<()> {
+    let regex = regex::RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|e| super::invalid_input(e.to_string()))?;
+    let grep = Grep::new(regex)
+        .with_all_revisions(args.all_revisions)
+        .with_namespaces(args.namespace.iter().copied());
+    let (store, token) = cancellable(store_args.open_pages()?);
+
+    let pages = store.grep(&grep);
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut count = 0;
+    let mut earliest: Option<PageMatches> = None;
+    write_streamed(pages, &token, |matches| {
+        let matches = matches?;
+        if args.json {
+            serde_json::to_writer(&mut out, &matches)?;
+            writeln!(out)?;
+        } else {
+            write_matches(&mut out, &matches)?;
+        }
+        count += 1;
+        if earliest
+            .as_ref()
+            .is_none_or(|e| matches.first.timestamp < e.first.timestamp)
+        {
+            earliest = Some(matches);
+        }
+        Ok(())
+    })?;
+
+    if !args.json {
+        match &earliest {
+            Some(e) => writeln!(
+                out,
+                "Matches in {} {}; the earliest is in {} ({})",
+                count,
+                if count == 1 { "page" } else { "pages" },
+                e.title,
+                describe(&e.first)
+            )?,
+            None => writeln!(out, "No pages match")?,
+        }
+    }
+    out.flush()
+}
+
+/// Write the matches in a page as text.
+fn write_matches(out: &mut impl Write, matches: &PageMatches) -> std::io::Result<()> {
+    writeln!(out, "{} (ID {})", matches.title, matches.page_id)?;
+    writeln!(out, "  first:    {}", describe(&matches.first))?;
+    writeln!(out, "  last:     {}", describe(&matches.last))?;
+    for span in &matches.spans {
+        writeln!(out, "  added:    {}", describe(&span.added))?;
+        match &span.removed {
+            Some(removed) => writeln!(out, "  removed:  {}", describe(removed))?,
+            None => writeln!(out, "  present in the latest revision")?,
+        }
+    }
+    for rev in &matches.revisions {
+        writeln!(out, "  matches:  {}", describe(rev))?;
+    }
+    writeln!(out)
+}
+
+/// Describe a revision like `revision 1234 at 2020-01-31T12:00:00Z by Example`.
+fn describe(rev: &GrepRevision) -> String {
+    format!(
+        "revision {} at {} by {}",
+        rev.revision_id,
+        rev.timestamp.format("%Y-%m-%dT%H:%M:%SZ"),
+        rev.contributor.as_deref().unwrap_or("(deleted)")
+    )
+}
@@ -0,0 +1,101 @@
+use super::list::format_size;
+use super::StoreArgs;
+use rayon::prelude::*;
+use std::io::Write;
+
+#[derive(Debug, clap::Args)]
+pub struct InfoArgs {
+    /// Also count the pages and revisions, which reads every dump.
+    #[arg(long)]
+    count: bool,
+
+    /// Count in this many worker processes rather than threads.
+    #[arg(long, requires = "count")]
+    processes: Option<usize>,
+}
+
+pub fn run(store_args: &StoreArgs, args: &InfoArgs) -> std::io::Result<()> {
+    let store = store_args.open()?;
+    let mut out = std::io::stdout().lock();
+
+    // The siteinfo is the same in every dump of a wiki, so it is read from the smallest
+    let site_info = store
+        .dumps()
+        .iter()
+        .filter(|d| d.kind().contains_pages())
+        .min_by_key(|d| d.file_size().unwrap_or(u64::MAX))
+        .map(|d| d.site_info())
+        .transpose()?;
+    if let Some(info) = &site_info {
+        writeln!(out, "Site:        {} ({})", info.sitename, info.dbname)?;
+        writeln!(out, "Base:        {}", info.base)?;
+        writeln!(out, "Generator:   {}", info.generator)?;
+        writeln!(out, "Case:        {}", info.case)?;
+        let namespaces: Vec<String> = info
+            .namespaces
+            .iter()
+            .map(|(key, name)| match name.as_str() {
+                "" => format!("{} (main)", key),
+                name => format!("{} {}", key, name),
+            })
+            .collect();
+        writeln!(out, "Namespaces:  {}", namespaces.join(", "))?;
+    }
+
+    let mut dates: Vec<String> = store.dumps().iter().map(|d| d.date().to_string()).collect();
+    dates.sort();
+    dates.dedup();
+    let kinds: Vec<&str> = store.kinds().iter().map(|k| k.as_str()).collect();
+    let total_size = store
+        .dumps()
+        .iter()
+        .map(|d| d.file_size())
+        .sum::<std::io::Result<u64>>()?;
+    writeln!(out, "Dump dates:  {}", dates.join(", "))?;
+    writeln!(
+        out,
+        "Dumps:       {} ({})",
+        store.dumps().len(),
+        kinds.join(", ")
+    )?;
+    writeln!(out, "Total size:  {}", format_size(total_size))?;
+    let ranges: Vec<(i64, i64)> = store
+        .dumps()
+        .iter()
+        .flat_map(|d| d.page_id_range())
+        .collect();
+    if let (Some(first), Some(last)) = (
+        ranges.iter().map(|r| r.0).min(),
+        ranges.iter().map(|r| r.1).max(),
+    ) {
+        writeln!(out, "Page IDs:    {} to {}", first, last)?;
+    }
+
+    if args.count {
+        let store = store_args.open_pages()?;
+        let (pages, revisions) = match args.processes {
+            Some(processes) => {
+                let (mut pages, mut revisions) = (0, 0);
+                let report = wiki_dump_analyzer::WorkerPool::new(processes)?.run(
+                    &store,
+                    |_, count: usize| {
+                        pages += 1;
+                        revisions += count;
+                    },
+                )?;
+                for line in &report.stray_output {
+                    eprintln!("Warning: unexpected worker output: {}", line);
+                }
+                (pages, revisions)
+            }
+            None => store
+                .par_pages()
+                .map(|p| (1, p.revisions().len()))
+                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1)),
+        };
+        super::clear_progress();
+        writeln!(out, "Pages:       {}", pages)?;
+        writeln!(out, "Revisions:   {}", revisions)?;
+    }
+    Ok(())
+}
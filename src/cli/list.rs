@@ -0,0 +1,53 @@
+use super::StoreArgs;
+use std::io::Write;
+
+pub fn run(store_args: &StoreArgs) -> std::io::Result<()> {
+    let store = store_args.open()?;
+    let mut dumps = store.dumps().clone();
+    dumps.sort_by_key(|d| (d.kind(), d.page_id_range(), d.part()));
+
+    let mut out = std::io::stdout().lock();
+    writeln!(
+        out,
+        "{:<24} {:>5} {:>24} {:>10}  FILE",
+        "KIND", "PART", "PAGE IDS", "SIZE"
+    )?;
+    for dump in &dumps {
+        let part = dump.part().map(|p| p.to_string()).unwrap_or_default();
+        let range = match dump.page_id_range() {
+            Some((first, last)) => format!("{}-{}", first, last),
+            None => String::new(),
+        };
+        let file_name = dump
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        writeln!(
+            out,
+            "{:<24} {:>5} {:>24} {:>10}  {}",
+            dump.kind(),
+            part,
+            range,
+            format_size(dump.file_size()?),
+            file_name
+        )?;
+    }
+    Ok(())
+}
+
+/// Format a size in bytes with a binary unit, like `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
@@ -0,0 +1,472 @@
+//! The `wiki-dump-analyzer` command line interface.
+
+mod browse;
+mod config;
+mod export;
+mod grep;
+mod info;
+mod list;
+mod page;
+mod revisions;
+mod stats;
+
+use std::io::Write;
+use wiki_dump_analyzer::{
+    CancellationToken, DumpKind, DumpStore, Page, Parser, ScanOptions, Shard, Verification,
+    VerificationPolicy,
+};
+
+/// Read and analyze wiki(pedia) dump files.
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    store: StoreArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// List the dump files with their kinds, page ID ranges and sizes.
+    List,
+    /// Show information about the wiki and the dumps.
+    Info(info::InfoArgs),
+    /// Show a page, looked up by ID or title.
+    Page(page::PageArgs),
+    /// List the revisions of a page.
+    Revisions(revisions::RevisionsArgs),
+    /// Export selected pages or revisions as JSON lines, CSV or Parquet.
+    Export(export::ExportArgs),
+    /// Search the body of every revision for a regular expression, reporting when each page first
+    /// and last matched and who added and removed the match.
+    Grep(grep::GrepArgs),
+    /// Summarize the pages and revisions of the dumps, reading every dump once.
+    Stats(stats::StatsArgs),
+    /// Browse page histories interactively: open pages by exact title or ID, list their revisions,
+    /// and view the bodies of revisions and their differences from their parents.
+    Browse(browse::BrowseArgs),
+}
+
+/// Arguments selecting the dumps to read and how to read them.
+#[derive(Debug, clap::Args)]
+pub struct StoreArgs {
+    /// Directory containing the dump files (otherwise `dump_dir` from the config file).
+    #[arg(short, long, global = true, env = "WIKI_DUMP_DIR")]
+    dump_dir: Option<std::path::PathBuf>,
+
+    /// Configuration file [default: ~/.config/wiki-dump-analyzer/config.toml].
+    #[arg(short, long, global = true, env = "WIKI_DUMP_CONFIG")]
+    config: Option<std::path::PathBuf>,
+
+    /// Only use dumps of this kind, like `pages-meta-history`.
+    #[arg(short, long, global = true, value_parser = str::parse::<DumpKind>)]
+    kind: Option<DumpKind>,
+
+    /// Page index file, which is built if it does not exist (otherwise `index_file` from the config
+    /// file).
+    #[arg(long, global = true)]
+    index_file: Option<std::path::PathBuf>,
+
+    /// Parser used to read pages.
+    #[arg(long, global = true, value_enum, default_value_t = ParserArg::Xml)]
+    parser: ParserArg,
+
+    /// Only read part of the dumps, given as `k/N` (the k-th of N shards, from 0).
+    #[arg(long, global = true, value_parser = str::parse::<Shard>)]
+    shard: Option<Shard>,
+
+    /// Check the dumps against the published `dumpstatus.json` and checksum files before reading
+    /// them, and warn about or refuse to read dumps which are missing, extra or the wrong size.
+    #[arg(long, global = true, value_enum, default_value_t = VerifyArg::Off)]
+    verify: VerifyArg,
+
+    /// With --verify, also compare the MD5 and SHA-1 checksums of the dumps with the published
+    /// ones, which reads every dump in full.
+    #[arg(long, global = true)]
+    verify_checksums: bool,
+
+    /// When the command succeeds, write a manifest of the dumps read (with their checksums), the
+    /// options and counts of the pages and revisions read to this JSON file.
+    #[arg(long, global = true)]
+    manifest: Option<std::path::PathBuf>,
+
+    /// The store most recently opened by the command, for the manifest.
+    #[arg(skip)]
+    opened: std::sync::Mutex<Option<DumpStore>>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ParserArg {
+    Xml,
+    Fast,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum VerifyArg {
+    Off,
+    Warn,
+    Refuse,
+}
+
+/// How to find a page.
+#[derive(Debug, clap::Args)]
+#[group(required = true, multiple = false)]
+pub struct PageSelector {
+    /// Page ID.
+    #[arg(long)]
+    id: Option<i64>,
+
+    /// Page title, including any namespace prefix.
+    #[arg(long)]
+    title: Option<String>,
+}
+
+impl StoreArgs {
+    /// Open the [`DumpStore`] described by the arguments, environment and config file, with
+    /// progress reported to stderr.
+    pub fn open(self: &StoreArgs) -> std::io::Result<DumpStore> {
+        let config = config::Config::load(self.config.as_deref())?;
+        let dump_dir = self.dump_dir.clone().or(config.dump_dir).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "No dump directory: use --dump-dir, set WIKI_DUMP_DIR or set dump_dir in the config file",
+            )
+        })?;
+
+        let mut options = ScanOptions::new().with_parser(match self.parser {
+            ParserArg::Xml => Parser::Xml,
+            ParserArg::Fast => Parser::Fast,
+        });
+        if std::io::IsTerminal::is_terminal(&std::io::stderr()) {
+            options = options.with_progress(wiki_dump_analyzer::Progress::with_callback(
+                std::time::Duration::from_secs(1),
+                print_progress,
+            ));
+        }
+
+        let mut store = DumpStore::new(dump_dir)?;
+        for path in store.unrecognized_files()? {
+            eprintln!(
+                "Warning: skipping {}, whose name does not follow the Wikimedia naming scheme",
+                path.display()
+            );
+        }
+        if let Some(kind) = self.kind {
+            store = store.of_kind(kind);
+        }
+        // The whole store is verified, since the dumps of other shards are not missing
+        let verification = Verification::new(match self.verify {
+            VerifyArg::Off => VerificationPolicy::Off,
+            VerifyArg::Warn => VerificationPolicy::Warn,
+            VerifyArg::Refuse => VerificationPolicy::Refuse,
+        })
+        .with_checksums(self.verify_checksums);
+        let (mut store, report) = store.verified(&verification)?;
+        if let Some(report) = report.filter(|r| !r.is_ok()) {
+            eprint!("Warning: dumps failed verification:\n{}", report);
+        }
+        if let Some(shard) = self.shard {
+            store = store.shard(shard, wiki_dump_analyzer::ShardMode::Dumps);
+        }
+        let store = store.with_options(options);
+        *self.opened.lock().unwrap() = Some(store.clone());
+        Ok(store)
+    }
+
+    /// Write a manifest of the store opened by the command (if any) to the file given with
+    /// --manifest (if any).
+    pub fn write_manifest(self: &StoreArgs) -> std::io::Result<()> {
+        if let (Some(path), Some(store)) = (&self.manifest, &*self.opened.lock().unwrap()) {
+            clear_progress();
+            store.manifest()?.save(path)?;
+        }
+        Ok(())
+    }
+
+    /// Open the [`DumpStore`] for a command which reads pages, which requires that the dumps are
+    /// of a single kind which contains pages. The page index is used (and built) if one is
+    /// configured.
+    pub fn open_pages(self: &StoreArgs) -> std::io::Result<DumpStore> {
+        let store = self.open()?;
+        match store.kinds().as_slice() {
+            [kind] if kind.contains_pages() => {
+                let config = config::Config::load(self.config.as_deref())?;
+                match self.index_file.clone().or(config.index_file) {
+                    Some(index) => store.with_index_file(&index),
+                    None => Ok(store),
+                }
+            }
+            [kind] => Err(invalid_input(format!(
+                "Dumps of kind {} contain no pages",
+                kind
+            ))),
+            [] => Err(invalid_input("No dumps found".to_string())),
+            kinds => Err(invalid_input(format!(
+                "The dump directory contains several kinds of dump ({}): choose one with --kind",
+                kinds
+                    .iter()
+                    .map(DumpKind::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+impl PageSelector {
+    /// Find the selected page in `store`.
+    pub fn find(self: &PageSelector, store: &DumpStore) -> std::io::Result<Page> {
+        let page = match (&self.id, &self.title) {
+            (Some(id), _) => store.page_by_id(*id),
+            (None, Some(title)) => store.page_by_title(title),
+            (None, None) => Ok(None),
+        };
+        clear_progress();
+        page?.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Page not found"))
+    }
+}
+
+/// Parse an RFC 3339 timestamp, or a date meaning the end of that day (UTC).
+pub fn parse_time(s: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    parse_time_or_date(
+        s,
+        chrono::NaiveTime::from_hms_opt(23, 59, 59).expect("Valid time"),
+    )
+}
+
+/// Parse an RFC 3339 timestamp, or a date meaning the start of that day (UTC).
+pub fn parse_start_time(s: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    parse_time_or_date(s, chrono::NaiveTime::MIN)
+}
+
+fn parse_time_or_date(
+    s: &str,
+    time_of_day: chrono::NaiveTime,
+) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    match chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_time(time_of_day).and_utc()),
+        Err(_) => Err(format!(
+            "expected an RFC 3339 timestamp or a date (YYYY-MM-DD), not {:?}",
+            s
+        )),
+    }
+}
+
+/// Attach a [`CancellationToken`] to `store`, so that reading can be stopped when the output of
+/// [`write_streamed`] fails. The other options (including any incremental dumps) are kept.
+pub fn cancellable(store: DumpStore) -> (DumpStore, CancellationToken) {
+    let token = CancellationToken::new();
+    let options = store.options().clone().with_cancellation(token.clone());
+    (store.with_options(options), token)
+}
+
+/// Produce `items` on background threads, passing each to `write` on this thread as it is
+/// produced (so not in any particular order). If writing fails, `token` is cancelled.
+pub fn write_streamed<T: Send>(
+    items: impl rayon::iter::ParallelIterator<Item = T>,
+    token: &CancellationToken,
+    mut write: impl FnMut(T) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel::<T>(1024);
+    let result = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            items.for_each_with(sender, |sender, item| {
+                // The receiver is only dropped after an error, when reading is cancelled
+                let _ = sender.send(item);
+            })
+        });
+        let result = receiver.iter().try_for_each(&mut write);
+        if result.is_err() {
+            token.cancel();
+        }
+        drop(receiver);
+        result
+    });
+    clear_progress();
+    result
+}
+
+pub fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// Display a progress report on a single, continually updated line of the terminal.
+fn print_progress(report: &wiki_dump_analyzer::ProgressReport) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(stderr, "\r\x1b[2K{}", report);
+    let _ = stderr.flush();
+}
+
+/// Remove the progress line (if any) from the terminal.
+pub fn clear_progress() {
+    if std::io::IsTerminal::is_terminal(&std::io::stderr()) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K");
+        let _ = stderr.flush();
+    }
+}
+
+pub fn main() {
+    // When started by a `WorkerPool` (see `info --count --processes`), count the revisions of
+    // each page
+    wiki_dump_analyzer::run_worker_if_requested(|p| Some(p.revisions().len()));
+
+    let cli: Cli = clap::Parser::parse();
+    let result = match &cli.command {
+        Command::List => list::run(&cli.store),
+        Command::Info(args) => info::run(&cli.store, args),
+        Command::Page(args) => page::run(&cli.store, args),
+        Command::Revisions(args) => revisions::run(&cli.store, args),
+        Command::Export(args) => export::run(&cli.store, args),
+        Command::Grep(args) => grep::run(&cli.store, args),
+        Command::Stats(args) => stats::run(&cli.store, args),
+        Command::Browse(args) => browse::run(&cli.store, args),
+    }
+    .and_then(|()| cli.store.write_manifest());
+    if let Err(e) = result {
+        // Output piped into a command like `head` may be closed early
+        if e.kind() == std::io::ErrorKind::BrokenPipe {
+            return;
+        }
+        clear_progress();
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Parse a page for tests from `(revision ID, timestamp, contributor, body)` tuples, in order. A
+/// contributor which is an IP address is anonymous.
+#[cfg(test)]
+pub fn test_page(
+    id: i64,
+    namespace: i64,
+    title: &str,
+    revisions: &[(i64, &str, &str, &str)],
+) -> Page {
+    let mut xml = format!(
+        "<mediawiki><page><title>{}</title><ns>{}</ns><id>{}</id>",
+        title, namespace, id
+    );
+    for (rev_id, timestamp, contributor, body) in revisions {
+        let contributor = match contributor.parse::<std::net::IpAddr>() {
+            Ok(_) => format!("<ip>{}</ip>", contributor),
+            Err(_) => format!("<username>{}</username><id>1</id>", contributor),
+        };
+        xml += &format!(
+            "<revision><id>{}</id><timestamp>{}</timestamp><contributor>{}</contributor>\
+             <model>wikitext</model><format>text/x-wiki</format><text>{}</text></revision>",
+            rev_id, timestamp, contributor, body
+        );
+    }
+    xml += "</page></mediawiki>";
+    let mut pages = wiki_dump_analyzer::PageIterator::from_reader(xml.as_bytes());
+    pages.next().expect("Invalid test page")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharded_stores_are_verified_in_full() {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-cli-verify-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for part in 1..=4 {
+            let name = format!("enwiki-20230101-pages-articles{}.xml.bz2", part);
+            std::fs::write(dir.join(name), b"abc").unwrap();
+        }
+        std::fs::write(
+            dir.join("dumpstatus.json"),
+            r#"{"jobs": {"articlesdump": {"status": "done", "files": {
+                "enwiki-20230101-pages-articles1.xml.bz2": {"size": 3},
+                "enwiki-20230101-pages-articles2.xml.bz2": {"size": 3},
+                "enwiki-20230101-pages-articles3.xml.bz2": {"size": 3},
+                "enwiki-20230101-pages-articles4.xml.bz2": {"size": 3}
+            }}}}"#,
+        )
+        .unwrap();
+        let config = dir.join("config.toml");
+        std::fs::write(&config, "").unwrap();
+
+        let args = StoreArgs {
+            dump_dir: Some(dir.clone()),
+            config: Some(config),
+            kind: None,
+            index_file: None,
+            parser: ParserArg::Xml,
+            shard: Some(Shard::new(0, 2).unwrap()),
+            verify: VerifyArg::Refuse,
+            verify_checksums: false,
+            manifest: None,
+            opened: Default::default(),
+        };
+        let store = args.open().unwrap();
+        assert_eq!(store.dumps().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn manifests_are_written_of_opened_stores() {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-cli-manifest-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("enwiki-20230101-pages-articles1.xml.bz2"), b"abc").unwrap();
+        let config = dir.join("config.toml");
+        std::fs::write(&config, "").unwrap();
+        let manifest = dir.join("manifest.json");
+
+        let args = StoreArgs {
+            dump_dir: Some(dir.clone()),
+            config: Some(config),
+            kind: None,
+            index_file: None,
+            parser: ParserArg::Fast,
+            shard: None,
+            verify: VerifyArg::Off,
+            verify_checksums: false,
+            manifest: Some(manifest.clone()),
+            opened: Default::default(),
+        };
+        // Nothing is written unless a store was opened
+        args.write_manifest().unwrap();
+        assert!(!manifest.exists());
+
+        args.open().unwrap();
+        args.write_manifest().unwrap();
+        let manifest = wiki_dump_analyzer::Manifest::load(&manifest).unwrap();
+        assert_eq!(manifest.dumps.len(), 1);
+        assert_eq!(manifest.options.parser, "fast");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn times_are_parsed_as_timestamps_or_dates() {
+        let time = |s: &str| s.parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+        assert_eq!(
+            parse_time("2023-01-01T12:00:00+02:00"),
+            Ok(time("2023-01-01T10:00:00Z"))
+        );
+        assert_eq!(parse_time("2023-01-01"), Ok(time("2023-01-01T23:59:59Z")));
+        assert_eq!(
+            parse_start_time("2023-01-01"),
+            Ok(time("2023-01-01T00:00:00Z"))
+        );
+        assert_eq!(
+            parse_time("2023-01-32"),
+            Err(
+                "expected an RFC 3339 timestamp or a date (YYYY-MM-DD), not \"2023-01-32\""
+                    .to_string()
+            )
+        );
+    }
+}
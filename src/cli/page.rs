@@ -0,0 +1,165 @@
+use super::revisions::{contributor, write_revisions};
+use super::{parse_time, PageSelector, StoreArgs};
+use std::io::Write;
+use wiki_dump_analyzer::{Page, Revision};
+
+#[derive(Debug, clap::Args)]
+pub struct PageArgs {
+    #[command(flatten)]
+    page: PageSelector,
+
+    #[command(flatten)]
+    body: BodySelector,
+
+    /// Write the body to this file rather than stdout.
+    #[arg(short, long, requires = "body")]
+    output: Option<std::path::PathBuf>,
+}
+
+/// Which revision's body to print. Without any of these, the page and its revisions are listed.
+#[derive(Debug, clap::Args)]
+#[group(id = "body", multiple = false)]
+struct BodySelector {
+    /// Print the body of the revision with this revision ID.
+    #[arg(long)]
+    revision: Option<i64>,
+
+    /// Print the body of the revision at this position in the list of revisions, counting from
+    /// 0 (negative positions count back from the latest revision, which is -1).
+    #[arg(long, allow_negative_numbers = true)]
+    index: Option<i64>,
+
+    /// Print the body of the revision which was current at this time, given as an RFC 3339
+    /// timestamp (like 2020-01-31T12:00:00Z) or a date (which means the end of that day, UTC).
+    #[arg(long, value_parser = parse_time)]
+    as_of: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl BodySelector {
+    /// Find the selected revision of `page`, or return [`None`] if no revision was selected.
+    fn find<'a>(self: &BodySelector, page: &'a Page) -> Option<std::io::Result<&'a Revision>> {
+        let not_found =
+            |message: String| std::io::Error::new(std::io::ErrorKind::NotFound, message);
+        let revisions = page.revisions();
+        let rev = if let Some(id) = self.revision {
+            page.revision_by_id(id)
+                .ok_or_else(|| not_found(format!("Page has no revision {}", id)))
+        } else if let Some(index) = self.index {
+            let position = if index < 0 {
+                revisions.len() as i64 + index
+            } else {
+                index
+            };
+            usize::try_from(position)
+                .ok()
+                .and_then(|i| revisions.get(i))
+                .ok_or_else(|| {
+                    not_found(format!(
+                        "Page has {} revisions, so there is no index {}",
+                        revisions.len(),
+                        index
+                    ))
+                })
+        } else if let Some(time) = &self.as_of {
+            page.revision_at(time)
+                .ok_or_else(|| not_found(format!("Page has no revisions as of {}", time)))
+        } else {
+            return None;
+        };
+        Some(rev)
+    }
+}
+
+pub fn run(store_args: &StoreArgs, args: &PageArgs) -> std::io::Result<()> {
+    let store = store_args.open_pages()?;
+    let page = args.page.find(&store)?;
+
+    if let Some(rev) = args.body.find(&page) {
+        let body = rev?.read_body()?;
+        return match &args.output {
+            Some(path) => std::fs::write(path, body.as_bytes()),
+            None => std::io::stdout().lock().write_all(body.as_bytes()),
+        };
+    }
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "ID:          {}", page.id())?;
+    writeln!(out, "Title:       {}", page.title())?;
+    writeln!(out, "Namespace:   {}", page.namespace())?;
+    writeln!(out, "Revisions:   {}", page.revisions().len())?;
+    if let (Some(first), Some(last)) = (page.revisions().first(), page.revisions().last()) {
+        writeln!(
+            out,
+            "First edit:  {} by {}",
+            first.timestamp().format("%Y-%m-%dT%H:%M:%SZ"),
+            contributor(first)
+        )?;
+        writeln!(
+            out,
+            "Last edit:   {} by {}",
+            last.timestamp().format("%Y-%m-%dT%H:%M:%SZ"),
+            contributor(last)
+        )?;
+        writeln!(out, "Size:        {} bytes", last.body_len())?;
+    }
+    writeln!(out)?;
+    write_revisions(&mut out, &page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_page;
+
+    fn selector(revision: Option<i64>, index: Option<i64>, as_of: Option<&str>) -> BodySelector {
+        BodySelector {
+            revision,
+            index,
+            as_of: as_of.map(|time| parse_time(time).unwrap()),
+        }
+    }
+
+    fn found(selector: &BodySelector, page: &Page) -> std::io::Result<i64> {
+        Ok(selector.find(page).expect("No revision selected")?.id())
+    }
+
+    #[test]
+    fn revisions_are_found_by_id_index_or_time() {
+        let page = test_page(
+            1,
+            0,
+            "A",
+            &[
+                (10, "2020-01-01T00:00:00Z", "User", "a"),
+                (11, "2020-01-02T12:00:00Z", "User", "b"),
+                (12, "2020-01-03T00:00:00Z", "User", "c"),
+            ],
+        );
+        assert!(selector(None, None, None).find(&page).is_none());
+        assert_eq!(found(&selector(Some(11), None, None), &page).unwrap(), 11);
+        let error = found(&selector(Some(13), None, None), &page).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+
+        assert_eq!(found(&selector(None, Some(0), None), &page).unwrap(), 10);
+        assert_eq!(found(&selector(None, Some(2), None), &page).unwrap(), 12);
+        // Negative indices count back from the latest revision
+        assert_eq!(found(&selector(None, Some(-1), None), &page).unwrap(), 12);
+        assert_eq!(found(&selector(None, Some(-3), None), &page).unwrap(), 10);
+        for index in [3, -4, i64::MIN] {
+            let error = found(&selector(None, Some(index), None), &page).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+            assert_eq!(
+                error.to_string(),
+                format!("Page has 3 revisions, so there is no index {}", index)
+            );
+        }
+
+        // A date means the end of that day
+        let as_of = |time| found(&selector(None, None, Some(time)), &page);
+        assert_eq!(as_of("2020-01-02").unwrap(), 11);
+        assert_eq!(as_of("2020-01-02T11:59:59Z").unwrap(), 10);
+        assert_eq!(as_of("2020-01-03T00:00:00Z").unwrap(), 12);
+        let error = as_of("2019-12-31").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    }
+}
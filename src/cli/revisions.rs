@@ -0,0 +1,48 @@
+use super::{PageSelector, StoreArgs};
+use std::io::Write;
+use wiki_dump_analyzer::{Page, Revision};
+
+#[derive(Debug, clap::Args)]
+pub struct RevisionsArgs {
+    #[command(flatten)]
+    page: PageSelector,
+}
+
+pub fn run(store_args: &StoreArgs, args: &RevisionsArgs) -> std::io::Result<()> {
+    let store = store_args.open_pages()?;
+    let page = args.page.find(&store)?;
+
+    write_revisions(&mut std::io::stdout().lock(), &page)
+}
+
+/// Write a table of the revisions of a page.
+pub fn write_revisions(out: &mut impl Write, page: &Page) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "{:>6} {:>12} {:>12} {:<20} {:<24} {:>9}  COMMENT",
+        "INDEX", "REVISION", "PARENT", "TIMESTAMP", "CONTRIBUTOR", "SIZE"
+    )?;
+    for (index, rev) in page.revisions().iter().enumerate() {
+        writeln!(
+            out,
+            "{:>6} {:>12} {:>12} {:<20} {:<24} {:>9}  {}",
+            index,
+            rev.id(),
+            rev.parent_id().map(|id| id.to_string()).unwrap_or_default(),
+            rev.timestamp().format("%Y-%m-%dT%H:%M:%SZ"),
+            contributor(rev),
+            rev.body_len(),
+            rev.comment().map(String::as_str).unwrap_or_default()
+        )?;
+    }
+    Ok(())
+}
+
+/// Describe the contributor of a revision by username, or IP address if they were not logged in.
+pub fn contributor(rev: &Revision) -> String {
+    match (rev.contributor_username(), rev.contributor_ip()) {
+        (Some(username), _) => username.clone(),
+        (None, Some(ip)) => ip.clone(),
+        (None, None) => "(deleted)".to_string(),
+    }
+}
@@ -0,0 +1,495 @@
+use super::list::format_size;
+use super::StoreArgs;
+use chrono::Datelike;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Write;
+use wiki_dump_analyzer::Page;
+
+#[derive(Debug, clap::Args)]
+pub struct StatsArgs {
+    /// Number of largest pages to list.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Show revisions per month rather than per year (JSON output always has both).
+    #[arg(long)]
+    monthly: bool,
+
+    /// Write the statistics as JSON rather than tables.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Statistics gathered from the pages of a store, which are combined across threads with
+/// [`Stats::merge`].
+#[derive(Debug, Default)]
+struct Stats {
+    pages: u64,
+    revisions: u64,
+    /// Pages and revisions by namespace.
+    namespaces: BTreeMap<i64, (u64, u64)>,
+    /// Revisions by year and month.
+    months: BTreeMap<(i32, u32), u64>,
+    registered_edits: u64,
+    anonymous_edits: u64,
+    /// Edits whose contributor has been deleted.
+    hidden_edits: u64,
+    models: BTreeMap<String, u64>,
+    formats: BTreeMap<String, u64>,
+    revision_sizes: SizeHistogram,
+    /// Sizes of the latest revision of each page.
+    page_sizes: SizeHistogram,
+    /// The largest pages (by the size of their latest revision) as `(size, page ID, title)`,
+    /// smallest first.
+    largest: std::collections::BinaryHeap<std::cmp::Reverse<(u64, i64, String)>>,
+}
+
+/// A histogram of sizes, from which percentiles can be estimated without keeping every size.
+/// Sizes below 16 bytes are counted exactly; larger ones are grouped into 16 buckets between
+/// each power of two, so the bucket of a size is known to within 1/16 of it.
+#[derive(Debug, Default, Clone)]
+struct SizeHistogram {
+    counts: Vec<u64>,
+    total: u64,
+    sum: u64,
+    max: u64,
+}
+
+/// Percentiles of sizes, in bytes.
+#[derive(Debug, serde::Serialize)]
+struct SizeSummary {
+    mean: u64,
+    p50: u64,
+    p90: u64,
+    p99: u64,
+    max: u64,
+}
+
+/// The statistics as they are output.
+#[derive(Debug, serde::Serialize)]
+struct StatsReport {
+    pages: u64,
+    revisions: u64,
+    namespaces: Vec<NamespaceCount>,
+    revisions_per_year: BTreeMap<i32, u64>,
+    revisions_per_month: BTreeMap<String, u64>,
+    registered_edits: u64,
+    anonymous_edits: u64,
+    hidden_edits: u64,
+    models: BTreeMap<String, u64>,
+    formats: BTreeMap<String, u64>,
+    revision_sizes: Option<SizeSummary>,
+    page_sizes: Option<SizeSummary>,
+    largest_pages: Vec<LargePage>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct NamespaceCount {
+    namespace: i64,
+    /// Name of the namespace, if it is given by the siteinfo.
+    name: Option<String>,
+    pages: u64,
+    revisions: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct LargePage {
+    page_id: i64,
+    title: String,
+    size: u64,
+}
+
+impl SizeHistogram {
+    fn bucket(size: u64) -> usize {
+        if size < 16 {
+            return size as usize;
+        }
+        let exponent = 63 - size.leading_zeros() as usize;
+        let mantissa = (size >> (exponent - 4)) as usize & 15;
+        16 + (exponent - 4) * 16 + mantissa
+    }
+
+    /// The smallest size in a bucket.
+    fn bucket_start(bucket: usize) -> u64 {
+        if bucket < 16 {
+            return bucket as u64;
+        }
+        let exponent = (bucket - 16) / 16 + 4;
+        let mantissa = ((bucket - 16) % 16) as u64;
+        (16 + mantissa) << (exponent - 4)
+    }
+
+    fn add(self: &mut SizeHistogram, size: u64) {
+        let bucket = SizeHistogram::bucket(size);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.total += 1;
+        self.sum += size;
+        self.max = self.max.max(size);
+    }
+
+    fn merge(self: &mut SizeHistogram, other: SizeHistogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Estimate the size below which `percent` of sizes fall, as the start of its bucket.
+    fn percentile(self: &SizeHistogram, percent: u64) -> u64 {
+        let rank = (self.total * percent).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return SizeHistogram::bucket_start(bucket);
+            }
+        }
+        self.max
+    }
+
+    fn summary(self: &SizeHistogram) -> Option<SizeSummary> {
+        (self.total > 0).then(|| SizeSummary {
+            mean: self.sum / self.total,
+            p50: self.percentile(50),
+            p90: self.percentile(90),
+            p99: self.percentile(99),
+            max: self.max,
+        })
+    }
+}
+
+impl Stats {
+    fn add(mut self: Stats, page: &Page, top: usize) -> Stats {
+        let revisions = page.revisions();
+        self.pages += 1;
+        self.revisions += revisions.len() as u64;
+        let namespace = self.namespaces.entry(page.namespace()).or_default();
+        namespace.0 += 1;
+        namespace.1 += revisions.len() as u64;
+
+        for rev in revisions {
+            let timestamp = rev.timestamp();
+            *self
+                .months
+                .entry((timestamp.year(), timestamp.month()))
+                .or_default() += 1;
+            match (rev.contributor_username(), rev.contributor_ip()) {
+                (Some(_), _) => self.registered_edits += 1,
+                (None, Some(_)) => self.anonymous_edits += 1,
+                (None, None) => self.hidden_edits += 1,
+            }
+            count(&mut self.models, rev.model());
+            count(&mut self.formats, rev.format());
+            self.revision_sizes.add(rev.body_len() as u64);
+        }
+
+        if let Some(latest) = revisions.last() {
+            let size = latest.body_len() as u64;
+            self.page_sizes.add(size);
+            self.consider_largest(size, page.id(), page.title(), top);
+        }
+        self
+    }
+
+    /// Keep the page among the largest if it is one of the `top` largest seen.
+    fn consider_largest(self: &mut Stats, size: u64, page_id: i64, title: &str, top: usize) {
+        if top == 0 {
+            return;
+        }
+        if self.largest.len() < top {
+            self.largest
+                .push(std::cmp::Reverse((size, page_id, title.to_string())));
+        } else if self
+            .largest
+            .peek()
+            .is_some_and(|smallest| size > smallest.0 .0)
+        {
+            self.largest.pop();
+            self.largest
+                .push(std::cmp::Reverse((size, page_id, title.to_string())));
+        }
+    }
+
+    fn merge(mut self: Stats, other: Stats, top: usize) -> Stats {
+        self.pages += other.pages;
+        self.revisions += other.revisions;
+        for (key, (pages, revisions)) in other.namespaces {
+            let namespace = self.namespaces.entry(key).or_default();
+            namespace.0 += pages;
+            namespace.1 += revisions;
+        }
+        for (month, revisions) in other.months {
+            *self.months.entry(month).or_default() += revisions;
+        }
+        self.registered_edits += other.registered_edits;
+        self.anonymous_edits += other.anonymous_edits;
+        self.hidden_edits += other.hidden_edits;
+        for (model, revisions) in other.models {
+            *self.models.entry(model).or_default() += revisions;
+        }
+        for (format, revisions) in other.formats {
+            *self.formats.entry(format).or_default() += revisions;
+        }
+        self.revision_sizes.merge(other.revision_sizes);
+        self.page_sizes.merge(other.page_sizes);
+        for std::cmp::Reverse((size, page_id, title)) in other.largest {
+            self.consider_largest(size, page_id, &title, top);
+        }
+        self
+    }
+
+    fn report(self: Stats, site_info: Option<&wiki_dump_analyzer::SiteInfo>) -> StatsReport {
+        let mut revisions_per_year: BTreeMap<i32, u64> = BTreeMap::new();
+        for ((year, _), revisions) in &self.months {
+            *revisions_per_year.entry(*year).or_default() += revisions;
+        }
+        let mut largest_pages: Vec<LargePage> = self
+            .largest
+            .into_iter()
+            .map(|std::cmp::Reverse((size, page_id, title))| LargePage {
+                page_id,
+                title,
+                size,
+            })
+            .collect();
+        largest_pages.sort_by_key(|p| (std::cmp::Reverse(p.size), p.page_id));
+        StatsReport {
+            pages: self.pages,
+            revisions: self.revisions,
+            namespaces: self
+                .namespaces
+                .into_iter()
+                .map(|(namespace, (pages, revisions))| NamespaceCount {
+                    namespace,
+                    name: site_info
+                        .and_then(|info| info.namespace_name(namespace))
+                        .map(str::to_string),
+                    pages,
+                    revisions,
+                })
+                .collect(),
+            revisions_per_year,
+            revisions_per_month: self
+                .months
+                .into_iter()
+                .map(|((year, month), revisions)| (format!("{:04}-{:02}", year, month), revisions))
+                .collect(),
+            registered_edits: self.registered_edits,
+            anonymous_edits: self.anonymous_edits,
+            hidden_edits: self.hidden_edits,
+            models: self.models,
+            formats: self.formats,
+            revision_sizes: self.revision_sizes.summary(),
+            page_sizes: self.page_sizes.summary(),
+            largest_pages,
+        }
+    }
+}
+
+/// Increment the count of `key`, only allocating a new key the first time it is seen.
+fn count(counts: &mut BTreeMap<String, u64>, key: &str) {
+    match counts.get_mut(key) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(key.to_string(), 1);
+        }
+    }
+}
+
+pub fn run(store_args: &StoreArgs, args: &StatsArgs) -> std::io::Result<()> {
+    let store = store_args.open_pages()?;
+    let site_info = match store.page_dumps().first() {
+        Some(dump) => Some(dump.site_info()?),
+        None => None,
+    };
+    let top = args.top;
+    let stats = store
+        .par_pages()
+        .fold(Stats::default, |stats, page| stats.add(&page, top))
+        .reduce(Stats::default, |a, b| a.merge(b, top));
+    super::clear_progress();
+    let report = stats.report(site_info.as_ref());
+
+    let mut out = std::io::stdout().lock();
+    if args.json {
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        return writeln!(out);
+    }
+    write_report(&mut out, &report, args.monthly)
+}
+
+/// Write the statistics as tables.
+fn write_report(out: &mut impl Write, report: &StatsReport, monthly: bool) -> std::io::Result<()> {
+    let percent = |n: u64| 100.0 * n as f64 / report.revisions.max(1) as f64;
+    writeln!(out, "Pages:       {}", report.pages)?;
+    writeln!(out, "Revisions:   {}", report.revisions)?;
+    writeln!(
+        out,
+        "Edits:       {} registered ({:.1}%), {} anonymous ({:.1}%), {} hidden ({:.1}%)",
+        report.registered_edits,
+        percent(report.registered_edits),
+        report.anonymous_edits,
+        percent(report.anonymous_edits),
+        report.hidden_edits,
+        percent(report.hidden_edits)
+    )?;
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{:<24} {:>12} {:>14}",
+        "NAMESPACE", "PAGES", "REVISIONS"
+    )?;
+    for namespace in &report.namespaces {
+        let name = match namespace.name.as_deref() {
+            Some("") => format!("{} (main)", namespace.namespace),
+            Some(name) => format!("{} {}", namespace.namespace, name),
+            None => namespace.namespace.to_string(),
+        };
+        writeln!(
+            out,
+            "{:<24} {:>12} {:>14}",
+            name, namespace.pages, namespace.revisions
+        )?;
+    }
+
+    writeln!(out)?;
+    if monthly {
+        writeln!(out, "{:<24} {:>14}", "MONTH", "REVISIONS")?;
+        for (month, revisions) in &report.revisions_per_month {
+            writeln!(out, "{:<24} {:>14}", month, revisions)?;
+        }
+    } else {
+        writeln!(out, "{:<24} {:>14}", "YEAR", "REVISIONS")?;
+        for (year, revisions) in &report.revisions_per_year {
+            writeln!(out, "{:<24} {:>14}", year, revisions)?;
+        }
+    }
+
+    for (heading, counts) in [("MODEL", &report.models), ("FORMAT", &report.formats)] {
+        writeln!(out)?;
+        writeln!(out, "{:<24} {:>14}", heading, "REVISIONS")?;
+        for (key, revisions) in counts {
+            writeln!(
+                out,
+                "{:<24} {:>14} {:>6.1}%",
+                key,
+                revisions,
+                percent(*revisions)
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "SIZES (APPROXIMATE)", "MEAN", "MEDIAN", "90%", "99%", "MAX"
+    )?;
+    for (label, summary) in [
+        ("Revisions", &report.revision_sizes),
+        ("Latest revisions", &report.page_sizes),
+    ] {
+        if let Some(s) = summary {
+            writeln!(
+                out,
+                "{:<24} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                label,
+                format_size(s.mean),
+                format_size(s.p50),
+                format_size(s.p90),
+                format_size(s.p99),
+                format_size(s.max)
+            )?;
+        }
+    }
+
+    if !report.largest_pages.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{:>10} {:>12}  LARGEST PAGES", "SIZE", "ID")?;
+        for page in &report.largest_pages {
+            writeln!(
+                out,
+                "{:>10} {:>12}  {}",
+                format_size(page.size),
+                page.page_id,
+                page.title
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_contain_their_sizes() {
+        let sizes = (0..5000).chain([1 << 20, (1 << 20) + 12345, u32::MAX as u64]);
+        for size in sizes {
+            let bucket = SizeHistogram::bucket(size);
+            let start = SizeHistogram::bucket_start(bucket);
+            let next = SizeHistogram::bucket_start(bucket + 1);
+            assert!(
+                start <= size && size < next,
+                "{} in [{}, {})",
+                size,
+                start,
+                next
+            );
+            // Within 1/16 of the size
+            assert!(size - start <= size / 16, "{} starts at {}", size, start);
+        }
+        // Small sizes are exact
+        assert_eq!(SizeHistogram::bucket(15), 15);
+        assert_eq!(SizeHistogram::bucket_start(SizeHistogram::bucket(16)), 16);
+        assert_eq!(SizeHistogram::bucket_start(SizeHistogram::bucket(33)), 32);
+    }
+
+    #[test]
+    fn percentiles_are_estimated_from_buckets() {
+        let mut histogram = SizeHistogram::default();
+        assert!(histogram.summary().is_none());
+        for size in 1..=10 {
+            histogram.add(size);
+        }
+        histogram.add(1000);
+        let summary = histogram.summary().unwrap();
+        assert_eq!(summary.mean, (55 + 1000) / 11);
+        assert_eq!(summary.p50, 6);
+        assert_eq!(summary.p90, 10);
+        // 1000 falls in the bucket starting at 992
+        assert_eq!(summary.p99, 992);
+        assert_eq!(summary.max, 1000);
+        assert_eq!(histogram.percentile(0), 1);
+    }
+
+    #[test]
+    fn merged_histograms_match_a_single_histogram() {
+        let mut a = SizeHistogram::default();
+        let mut b = SizeHistogram::default();
+        let mut all = SizeHistogram::default();
+        for size in 0..300u64 {
+            let size = size * size;
+            if size % 3 == 0 {
+                a.add(size);
+            } else {
+                b.add(size);
+            }
+            all.add(size);
+        }
+        a.merge(b);
+        assert_eq!(a.counts, all.counts);
+        assert_eq!((a.total, a.sum, a.max), (all.total, all.sum, all.max));
+    }
+}
@@ -0,0 +1,28 @@
+/// A flag which stops scans of dumps once it is set.
+///
+/// A [`CancellationToken`] is given to a [`ScanOptions`](super::ScanOptions), and clones of it
+/// share the same flag. Once [`CancellationToken::cancel`] is called, page iterators using it stop
+/// (at the latest after the revision currently being parsed) and the `7z` processes they spawned
+/// are killed.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a [`CancellationToken`] which has not been cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel every scan using this token.
+    pub fn cancel(self: &CancellationToken) {
+        self.cancelled
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns [`true`] once [`CancellationToken::cancel`] has been called.
+    pub fn is_cancelled(self: &CancellationToken) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,300 @@
+use super::{Dump, DumpKind, DumpStore};
+
+/// The key of a set of dump files in a [`DumpCatalog`]: the wiki's database name, the date of the
+/// dump run and the kind of dump.
+pub type CatalogKey = (String, chrono::NaiveDate, DumpKind);
+
+/// A catalog of the dumps of several wikis and dump dates kept under one directory.
+///
+/// Dumps may be stored flat or nested (for example in `enwiki/20230101/`, mirroring
+/// [Wikimedia](https://dumps.wikimedia.org/)), since the database name, date and kind of each
+/// file are read from its name.
+#[derive(Debug, Clone)]
+pub struct DumpCatalog {
+    root: std::path::PathBuf,
+    dumps: std::collections::BTreeMap<CatalogKey, Vec<Dump>>,
+}
+
+impl DumpCatalog {
+    /// Create a [`DumpCatalog`] of all the dump files in `root` and its subdirectories.
+    pub fn new(root: std::path::PathBuf) -> std::io::Result<DumpCatalog> {
+        let mut dumps: std::collections::BTreeMap<CatalogKey, Vec<Dump>> =
+            std::collections::BTreeMap::new();
+        for dump in nested_dumps(&root)? {
+            let key = (dump.dbname().to_string(), dump.date(), dump.kind());
+            dumps.entry(key).or_default().push(dump);
+        }
+        for files in dumps.values_mut() {
+            files.sort_by_key(|d| (d.page_id_range(), d.part()));
+        }
+        Ok(DumpCatalog { root, dumps })
+    }
+
+    /// Return the path of the directory containing the catalog.
+    pub fn root(self: &DumpCatalog) -> &std::path::PathBuf {
+        &self.root
+    }
+
+    /// Return the keys of every set of dump files in the catalog, in order.
+    pub fn keys(self: &DumpCatalog) -> impl Iterator<Item = &CatalogKey> {
+        self.dumps.keys()
+    }
+
+    /// Return the database names of the wikis in the catalog, like `enwiki`.
+    pub fn wikis(self: &DumpCatalog) -> Vec<&str> {
+        let mut wikis: Vec<&str> = self.dumps.keys().map(|(db, _, _)| db.as_str()).collect();
+        wikis.dedup();
+        wikis
+    }
+
+    /// Return the dates of the dump runs of a wiki in the catalog, from oldest to newest.
+    pub fn dates(self: &DumpCatalog, dbname: &str) -> Vec<chrono::NaiveDate> {
+        let mut dates: Vec<chrono::NaiveDate> = self
+            .dumps
+            .keys()
+            .filter(|(db, _, _)| db == dbname)
+            .map(|(_, date, _)| *date)
+            .collect();
+        dates.dedup();
+        dates
+    }
+
+    /// Return the kinds of dump available for a wiki from a dump run.
+    pub fn kinds(self: &DumpCatalog, dbname: &str, date: chrono::NaiveDate) -> Vec<DumpKind> {
+        self.dumps
+            .keys()
+            .filter(|(db, d, _)| db == dbname && *d == date)
+            .map(|(_, _, kind)| *kind)
+            .collect()
+    }
+
+    /// Return the dump files of a kind for a wiki from a dump run, ordered by page ID range.
+    pub fn dumps(
+        self: &DumpCatalog,
+        dbname: &str,
+        date: chrono::NaiveDate,
+        kind: DumpKind,
+    ) -> Option<&Vec<Dump>> {
+        self.dumps.get(&(dbname.to_string(), date, kind))
+    }
+
+    /// Return a [`DumpStore`] of the dump files of a kind for a wiki from a dump run.
+    pub fn store(
+        self: &DumpCatalog,
+        dbname: &str,
+        date: chrono::NaiveDate,
+        kind: DumpKind,
+    ) -> Option<DumpStore> {
+        let dumps = self.dumps(dbname, date, kind)?;
+        Some(DumpStore::from_dumps(self.dump_dir(dumps), dumps.clone()))
+    }
+
+    /// Returns [`true`] if the dump files of a kind for a wiki from a dump run appear to be
+    /// complete. See [`is_complete`] for how this is decided.
+    pub fn is_complete(
+        self: &DumpCatalog,
+        dbname: &str,
+        date: chrono::NaiveDate,
+        kind: DumpKind,
+    ) -> bool {
+        self.dumps(dbname, date, kind)
+            .is_some_and(|d| is_complete(d))
+    }
+
+    /// Return the date of the most recent dump run of a wiki with a complete set of dump files
+    /// of a kind.
+    pub fn latest_complete_date(
+        self: &DumpCatalog,
+        dbname: &str,
+        kind: DumpKind,
+    ) -> Option<chrono::NaiveDate> {
+        self.dates(dbname)
+            .into_iter()
+            .rev()
+            .find(|&date| self.is_complete(dbname, date, kind))
+    }
+
+    /// Return a [`DumpStore`] of the most recent complete set of dump files of a kind for a wiki,
+    /// like "enwiki, latest complete history dump".
+    pub fn latest_complete(self: &DumpCatalog, dbname: &str, kind: DumpKind) -> Option<DumpStore> {
+        let date = self.latest_complete_date(dbname, kind)?;
+        self.store(dbname, date, kind)
+    }
+
+    /// Return the daily incremental dumps (with text) of a wiki from after `date`, in date order,
+    /// for use with [`DumpStore::with_increments`].
+    pub fn increments_after(
+        self: &DumpCatalog,
+        dbname: &str,
+        date: chrono::NaiveDate,
+    ) -> Vec<Dump> {
+        self.dumps
+            .iter()
+            .filter(|((db, d, kind), _)| {
+                db == dbname && *d > date && *kind == DumpKind::PagesMetaHistIncr
+            })
+            .flat_map(|(_, dumps)| dumps.iter().cloned())
+            .collect()
+    }
+
+    /// The directory to use for a [`DumpStore`] of `dumps`: the directory containing them if it is
+    /// the same for all of them, otherwise the root of the catalog.
+    fn dump_dir(self: &DumpCatalog, dumps: &[Dump]) -> std::path::PathBuf {
+        let mut parents = dumps.iter().map(|d| d.path().parent());
+        match parents.next().flatten() {
+            Some(first) if parents.all(|p| p == Some(first)) => first.to_path_buf(),
+            _ => self.root.clone(),
+        }
+    }
+}
+
+/// Returns [`true`] if a set of dump files, ordered by page ID range, appears to be complete.
+///
+/// When the files give page ID ranges, the ranges must start at page 1 and follow on from each
+/// other without gaps. Otherwise the part numbers (if any) must run from 1 without gaps. The end
+/// of the last range is not known from file names alone, so missing files at the end of a run
+/// cannot be detected.
+pub fn is_complete(dumps: &[Dump]) -> bool {
+    if dumps.is_empty() {
+        return false;
+    }
+    if dumps.iter().all(|d| d.page_id_range().is_some()) {
+        let mut next_id = 1;
+        for (first, last) in dumps.iter().flat_map(Dump::page_id_range) {
+            if first != next_id {
+                return false;
+            }
+            next_id = last + 1;
+        }
+        return true;
+    }
+    let mut parts: Vec<Option<u32>> = dumps.iter().map(Dump::part).collect();
+    parts.sort();
+    match parts.as_slice() {
+        [None] => true,
+        parts => parts
+            .iter()
+            .enumerate()
+            .all(|(i, part)| *part == Some(i as u32 + 1)),
+    }
+}
+
+/// Return the dumps in `dir` and all of its subdirectories.
+fn nested_dumps(dir: &std::path::Path) -> std::io::Result<Vec<Dump>> {
+    let mut dumps = Vec::new();
+    for entry in std::fs::read_dir(dir)?.filter_map(|res| res.ok()) {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            dumps.extend(nested_dumps(&path)?);
+        } else if let Some(dump) = Dump::new(path) {
+            dumps.push(dump);
+        }
+    }
+    Ok(dumps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dumps(file_names: &[&str]) -> Vec<Dump> {
+        file_names
+            .iter()
+            .map(|name| Dump::new(std::path::PathBuf::from(name)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn ranges_must_start_at_one_without_gaps() {
+        assert!(is_complete(&dumps(&[
+            "enwiki-20230101-pages-meta-history1.xml-p1p10.7z",
+            "enwiki-20230101-pages-meta-history1.xml-p11p25.7z",
+            "enwiki-20230101-pages-meta-history2.xml-p26p40.7z",
+        ])));
+        // Missing the first file
+        assert!(!is_complete(&dumps(&[
+            "enwiki-20230101-pages-meta-history1.xml-p11p25.7z",
+        ])));
+        // Missing a file in the middle
+        assert!(!is_complete(&dumps(&[
+            "enwiki-20230101-pages-meta-history1.xml-p1p10.7z",
+            "enwiki-20230101-pages-meta-history2.xml-p26p40.7z",
+        ])));
+        assert!(!is_complete(&[]));
+    }
+
+    #[test]
+    fn parts_must_run_from_one_without_gaps() {
+        assert!(is_complete(&dumps(&[
+            "enwiki-20230101-pages-articles2.xml.bz2",
+            "enwiki-20230101-pages-articles1.xml.bz2",
+        ])));
+        assert!(!is_complete(&dumps(&[
+            "enwiki-20230101-pages-articles1.xml.bz2",
+            "enwiki-20230101-pages-articles3.xml.bz2",
+        ])));
+        assert!(is_complete(&dumps(&[
+            "enwiki-20230101-pages-articles.xml.bz2"
+        ])));
+        // A single file and a part cannot both be the whole dump
+        assert!(!is_complete(&dumps(&[
+            "enwiki-20230101-pages-articles.xml.bz2",
+            "enwiki-20230101-pages-articles1.xml.bz2",
+        ])));
+    }
+
+    #[test]
+    fn catalog_finds_latest_complete_dump() {
+        let root =
+            std::env::temp_dir().join(format!("wiki-dump-analyzer-catalog-{}", std::process::id()));
+        for (dir, name) in [
+            (
+                "enwiki/20230101",
+                "enwiki-20230101-pages-meta-history1.xml-p1p10.7z",
+            ),
+            (
+                "enwiki/20230101",
+                "enwiki-20230101-pages-meta-history2.xml-p11p20.7z",
+            ),
+            // The later run is missing its first file
+            (
+                "enwiki/20230201",
+                "enwiki-20230201-pages-meta-history2.xml-p11p20.7z",
+            ),
+            (
+                "enwiki/20230115",
+                "enwiki-20230115-pages-meta-hist-incr.xml.bz2",
+            ),
+            ("", "dewiki-20230101-pages-articles.xml.bz2"),
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(name), b"").unwrap();
+        }
+
+        let catalog = DumpCatalog::new(root.clone()).unwrap();
+        let date = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y%m%d").unwrap();
+        assert_eq!(catalog.wikis(), ["dewiki", "enwiki"]);
+        assert_eq!(
+            catalog.dates("enwiki"),
+            [date("20230101"), date("20230115"), date("20230201")]
+        );
+        assert_eq!(
+            catalog.latest_complete_date("enwiki", DumpKind::PagesMetaHistory),
+            Some(date("20230101"))
+        );
+        let store = catalog
+            .latest_complete("enwiki", DumpKind::PagesMetaHistory)
+            .unwrap();
+        assert_eq!(store.dumps().len(), 2);
+        assert_eq!(store.dump_dir(), &root.join("enwiki/20230101"));
+        assert_eq!(
+            catalog.increments_after("enwiki", date("20230101")).len(),
+            1
+        );
+        assert!(catalog
+            .increments_after("enwiki", date("20230115"))
+            .is_empty());
+        assert!(catalog.is_complete("dewiki", date("20230101"), DumpKind::PagesArticles));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
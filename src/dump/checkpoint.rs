@@ -0,0 +1,326 @@
+use super::{Dump, DumpStore, Page};
+use rayon::prelude::*;
+
+/// The persisted state of a scan performed by [`DumpStore::checkpointed_fold`], from which the scan
+/// can be resumed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Checkpoint<S> {
+    /// Paths of the dumps which have been read completely.
+    completed_dumps: std::collections::BTreeSet<std::path::PathBuf>,
+    /// State accumulated from the completed dumps.
+    completed_state: S,
+    /// Progress through each dump which has been partially read.
+    partial_dumps: std::collections::BTreeMap<std::path::PathBuf, PartialDump<S>>,
+}
+
+/// Progress through a dump which has been partially read.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct PartialDump<S> {
+    /// ID of the last page read. Pages in a dump are stored in order of their IDs.
+    last_page_id: i64,
+    /// Offset into the decompressed dump from which the pages after the last one can be read.
+    /// This is not recorded when reading with incremental dumps, nor by earlier versions, in
+    /// which case the dump is read from the start.
+    #[serde(default)]
+    offset: Option<u64>,
+    /// State accumulated from the pages read so far.
+    state: S,
+}
+
+impl<S: Default> Default for Checkpoint<S> {
+    fn default() -> Checkpoint<S> {
+        Checkpoint {
+            completed_dumps: Default::default(),
+            completed_state: S::default(),
+            partial_dumps: Default::default(),
+        }
+    }
+}
+
+impl<S> Checkpoint<S> {
+    /// Paths of the dumps which have been read completely.
+    pub fn completed_dumps(
+        self: &Checkpoint<S>,
+    ) -> &std::collections::BTreeSet<std::path::PathBuf> {
+        &self.completed_dumps
+    }
+
+    /// State accumulated from the completed dumps.
+    pub fn completed_state(self: &Checkpoint<S>) -> &S {
+        &self.completed_state
+    }
+
+    /// The ID of the last page read from a partially read dump.
+    pub fn last_page_id(self: &Checkpoint<S>, dump: &Dump) -> Option<i64> {
+        self.partial_dumps
+            .get(dump.path())
+            .map(|partial| partial.last_page_id)
+    }
+}
+
+impl<S: serde::Serialize + serde::de::DeserializeOwned> Checkpoint<S> {
+    /// Read a checkpoint from a file, returning [`None`] if the file does not exist.
+    pub fn load(path: &std::path::Path) -> std::io::Result<Option<Checkpoint<S>>> {
+        match std::fs::File::open(path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(std::io::BufReader::new(
+                file,
+            ))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the checkpoint to a file. The file is replaced atomically, so an interrupted write
+    /// leaves the previous checkpoint intact.
+    pub fn save(self: &Checkpoint<S>, path: &std::path::Path) -> std::io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = std::path::PathBuf::from(tmp_path);
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+        serde_json::to_writer(&mut file, self)?;
+        file.into_inner()?.sync_all()?;
+        std::fs::rename(tmp_path, path)
+    }
+}
+
+impl DumpStore {
+    /// Fold over all the pages stored in all the dumps, persisting a [`Checkpoint`] to
+    /// `checkpoint_path` at least every `interval` (and whenever a dump is completed).
+    ///
+    /// If `checkpoint_path` already contains a checkpoint, the scan resumes from it: completed
+    /// dumps are skipped, and partially read dumps are read from the offset recorded after their
+    /// last page (see [`PageIterator::from_path_at`](super::PageIterator::from_path_at)). When the
+    /// store has incremental dumps (see [`DumpStore::with_increments`]) there is no such offset, so
+    /// the pages up to the last one recorded are parsed again but not passed to `fold`. Dumps are read in parallel, each accumulating into its own
+    /// state (starting from [`Default::default`]), which are combined with `merge`.
+    ///
+    /// If the scan is cancelled (see [`ScanOptions::with_cancellation`](super::ScanOptions::with_cancellation)),
+    /// the checkpoint is saved and an error of kind [`std::io::ErrorKind::Interrupted`] is returned.
+    /// If reading a dump fails (for example because `7z` reports that the archive is truncated), the
+    /// checkpoint is saved without marking the dump as completed, and the error is returned.
+    pub fn checkpointed_fold<S, F, M>(
+        self: &DumpStore,
+        checkpoint_path: &std::path::Path,
+        interval: std::time::Duration,
+        fold: F,
+        merge: M,
+    ) -> std::io::Result<S>
+    where
+        S: Default + Clone + Send + serde::Serialize + serde::de::DeserializeOwned,
+        F: Fn(&mut S, Page) + Sync,
+        M: Fn(&mut S, S) + Sync,
+    {
+        let checkpoint: Checkpoint<S> = Checkpoint::load(checkpoint_path)?.unwrap_or_default();
+        let mut remaining: Vec<Dump> = self
+            .page_dumps()
+            .into_iter()
+            .filter(|d| !checkpoint.completed_dumps.contains(d.path()))
+            .collect();
+        remaining.sort_by_cached_key(|d| std::cmp::Reverse(d.file_size().unwrap_or(0)));
+
+        let checkpoint = std::sync::Mutex::new(checkpoint);
+        remaining.par_iter().try_for_each(|dump| {
+            let (last_page_id, offset, mut state) =
+                match checkpoint.lock().unwrap().partial_dumps.get(dump.path()) {
+                    Some(partial) => (
+                        Some(partial.last_page_id),
+                        partial.offset,
+                        partial.state.clone(),
+                    ),
+                    None => (None, None, S::default()),
+                };
+            // Offsets into the dump are unreliable when pages from increments are merged in
+            let resumable = self.options().increments().is_none();
+            let mut offset = offset.filter(|_| resumable);
+
+            let mut last_save = std::time::Instant::now();
+            let mut last_page_id = last_page_id;
+            let mut error = None;
+            for page in dump.try_pages_at(self.options(), offset.unwrap_or(0)) {
+                let (page, page_offset) = match page {
+                    Ok(page) => page,
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                };
+                offset = Some(page_offset).filter(|_| resumable);
+                // Fast-forward past pages which were already read
+                if last_page_id.is_some_and(|id| page.id() <= id) {
+                    continue;
+                }
+                last_page_id = Some(page.id());
+                fold(&mut state, page);
+
+                if last_save.elapsed() >= interval {
+                    let mut checkpoint = checkpoint.lock().unwrap();
+                    checkpoint.partial_dumps.insert(
+                        dump.path().clone(),
+                        PartialDump {
+                            last_page_id: last_page_id.unwrap(),
+                            offset,
+                            state: state.clone(),
+                        },
+                    );
+                    checkpoint.save(checkpoint_path)?;
+                    last_save = std::time::Instant::now();
+                }
+            }
+
+            let mut checkpoint = checkpoint.lock().unwrap();
+            // A dump which was not read completely is resumed from its last page
+            if self.options().is_cancelled() || error.is_some() {
+                if let Some(last_page_id) = last_page_id {
+                    checkpoint.partial_dumps.insert(
+                        dump.path().clone(),
+                        PartialDump {
+                            last_page_id,
+                            offset,
+                            state,
+                        },
+                    );
+                }
+                checkpoint.save(checkpoint_path)?;
+                return Err(error.unwrap_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::Interrupted, "Scan was cancelled")
+                }));
+            }
+
+            checkpoint.partial_dumps.remove(dump.path());
+            checkpoint.completed_dumps.insert(dump.path().clone());
+            merge(&mut checkpoint.completed_state, state);
+            checkpoint.save(checkpoint_path)
+        })?;
+
+        let checkpoint = checkpoint.into_inner().unwrap();
+        Ok(checkpoint.completed_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+    use crate::dump::{CancellationToken, ScanOptions};
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-checkpoint-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn store(dir: &std::path::Path) -> DumpStore {
+        let pages: Vec<Page> = (1..=4)
+            .map(|id| {
+                test_page(
+                    id,
+                    &format!("Page {}", id),
+                    &[(id, "2020-01-01T00:00:00Z", "Body")],
+                )
+            })
+            .collect();
+        crate::dump::test_dump(
+            dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p4.7z",
+            &pages,
+        );
+        DumpStore::new(dir.to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn checkpoints_are_saved_and_loaded() {
+        let dir = test_dir("save");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+        assert!(Checkpoint::<Vec<i64>>::load(&path).unwrap().is_none());
+
+        let mut checkpoint: Checkpoint<Vec<i64>> = Checkpoint::default();
+        checkpoint.completed_dumps.insert("a.7z".into());
+        checkpoint.completed_state = vec![1, 2];
+        checkpoint.partial_dumps.insert(
+            "b.7z".into(),
+            PartialDump {
+                last_page_id: 4,
+                offset: Some(120),
+                state: vec![3, 4],
+            },
+        );
+        checkpoint.save(&path).unwrap();
+        assert!(!dir.join("checkpoint.json.tmp").exists());
+
+        let loaded: Checkpoint<Vec<i64>> = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.completed_dumps(), checkpoint.completed_dumps());
+        assert_eq!(loaded.completed_state(), &vec![1, 2]);
+        let partial = &loaded.partial_dumps[std::path::Path::new("b.7z")];
+        assert_eq!((partial.last_page_id, partial.offset), (4, Some(120)));
+        assert_eq!(partial.state, vec![3, 4]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_scans_are_resumed_from_the_last_page() {
+        let dir = test_dir("resume");
+        let store = store(&dir);
+        let dump = store.dumps()[0].clone();
+        let path = dir.join("checkpoint.json");
+        let fold = |ids: &mut Vec<i64>, page: Page| ids.push(page.id());
+        let merge = |ids: &mut Vec<i64>, other: Vec<i64>| ids.extend(other);
+
+        let token = CancellationToken::new();
+        let cancelled = store
+            .clone()
+            .with_options(ScanOptions::new().with_cancellation(token.clone()));
+        let error = cancelled
+            .checkpointed_fold(
+                &path,
+                std::time::Duration::MAX,
+                |ids: &mut Vec<i64>, page: Page| {
+                    // Cancel once two pages have been read
+                    if page.id() == 2 {
+                        token.cancel();
+                    }
+                    fold(ids, page)
+                },
+                merge,
+            )
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Interrupted);
+
+        let checkpoint: Checkpoint<Vec<i64>> = Checkpoint::load(&path).unwrap().unwrap();
+        assert!(checkpoint.completed_dumps().is_empty());
+        assert_eq!(checkpoint.last_page_id(&dump), Some(2));
+        let partial = &checkpoint.partial_dumps[dump.path()];
+        assert_eq!(partial.state, vec![1, 2]);
+        // The dump is resumed from the page after the last one read
+        let xml = std::fs::read_to_string(dump.path()).unwrap();
+        let rest = xml[partial.offset.unwrap() as usize..].trim_start();
+        assert!(
+            rest.starts_with("<page>\n    <title>Page 3</title>"),
+            "{}",
+            rest
+        );
+        // Pages before the offset are not read again, even if the file has changed
+        let offset = partial.offset.unwrap() as usize;
+        let replaced = "<mediawiki><page><title>Z</title><ns>0</ns><id>7</id></page>";
+        let xml = format!("{:<width$}{}", replaced, &xml[offset..], width = offset);
+        std::fs::write(dump.path(), xml).unwrap();
+
+        let ids = store
+            .checkpointed_fold(&path, std::time::Duration::MAX, fold, merge)
+            .unwrap();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        let checkpoint: Checkpoint<Vec<i64>> = Checkpoint::load(&path).unwrap().unwrap();
+        assert!(checkpoint.completed_dumps().contains(dump.path()));
+        assert!(checkpoint.partial_dumps.is_empty());
+
+        // A completed scan is not read again
+        let ids = store
+            .checkpointed_fold(&path, std::time::Duration::MAX, |_, _| panic!(), merge)
+            .unwrap();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
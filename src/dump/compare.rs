@@ -0,0 +1,381 @@
+use super::{DumpStore, Page, Schedule, ScheduledPages};
+
+/// A difference between a page in an older and a newer set of dumps of the same wiki.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum PageChange {
+    /// The page is only in the newer dumps.
+    Created { page_id: i64, title: String },
+    /// The page is only in the older dumps.
+    Deleted { page_id: i64, title: String },
+    /// The page has a different title (including any namespace prefix) in the newer dumps.
+    Renamed {
+        page_id: i64,
+        old_title: String,
+        new_title: String,
+    },
+    /// Revisions of the page which are only in the newer dumps.
+    RevisionsAdded {
+        page_id: i64,
+        title: String,
+        revision_ids: Vec<i64>,
+    },
+    /// Revisions of the page which are only in the older dumps, because they have since been
+    /// deleted or suppressed (oversighted).
+    RevisionsVanished {
+        page_id: i64,
+        title: String,
+        revision_ids: Vec<i64>,
+    },
+}
+
+impl PageChange {
+    /// The ID of the changed page.
+    pub fn page_id(self: &PageChange) -> i64 {
+        match self {
+            PageChange::Created { page_id, .. }
+            | PageChange::Deleted { page_id, .. }
+            | PageChange::Renamed { page_id, .. }
+            | PageChange::RevisionsAdded { page_id, .. }
+            | PageChange::RevisionsVanished { page_id, .. } => *page_id,
+        }
+    }
+}
+
+impl std::fmt::Display for PageChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageChange::Created { page_id, title } => {
+                write!(f, "Created {} {:?}", page_id, title)
+            }
+            PageChange::Deleted { page_id, title } => {
+                write!(f, "Deleted {} {:?}", page_id, title)
+            }
+            PageChange::Renamed {
+                page_id,
+                old_title,
+                new_title,
+            } => write!(f, "Renamed {} {:?} to {:?}", page_id, old_title, new_title),
+            PageChange::RevisionsAdded {
+                page_id,
+                title,
+                revision_ids,
+            } => write!(
+                f,
+                "Added {} revisions to {} {:?}",
+                revision_ids.len(),
+                page_id,
+                title
+            ),
+            PageChange::RevisionsVanished {
+                page_id,
+                title,
+                revision_ids,
+            } => write!(
+                f,
+                "Removed {} revisions from {} {:?}",
+                revision_ids.len(),
+                page_id,
+                title
+            ),
+        }
+    }
+}
+
+/// Counts of the changes between two sets of dumps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ComparisonSummary {
+    pub created: usize,
+    pub deleted: usize,
+    pub renamed: usize,
+    /// Pages with revisions added.
+    pub edited: usize,
+    pub revisions_added: usize,
+    pub revisions_vanished: usize,
+}
+
+impl ComparisonSummary {
+    /// Count a change.
+    pub fn record(self: &mut ComparisonSummary, change: &PageChange) {
+        match change {
+            PageChange::Created { .. } => self.created += 1,
+            PageChange::Deleted { .. } => self.deleted += 1,
+            PageChange::Renamed { .. } => self.renamed += 1,
+            PageChange::RevisionsAdded { revision_ids, .. } => {
+                self.edited += 1;
+                self.revisions_added += revision_ids.len();
+            }
+            PageChange::RevisionsVanished { revision_ids, .. } => {
+                self.revisions_vanished += revision_ids.len();
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ComparisonSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} deleted, {} renamed, {} edited ({} revisions added, {} vanished)",
+            self.created,
+            self.deleted,
+            self.renamed,
+            self.edited,
+            self.revisions_added,
+            self.revisions_vanished
+        )
+    }
+}
+
+/// An iterator over the [`PageChange`]s between two sets of dumps, in order of page ID. See
+/// [`DumpStore::compare`].
+pub struct DumpComparison {
+    older: std::iter::Peekable<ScheduledPages>,
+    newer: std::iter::Peekable<ScheduledPages>,
+    /// Changes found but not yet yielded.
+    pending: std::collections::VecDeque<PageChange>,
+}
+
+impl DumpComparison {
+    /// Write every remaining change to `writer` as a change log with one JSON object per line,
+    /// returning counts of the changes.
+    pub fn write_change_log(
+        self: DumpComparison,
+        writer: impl std::io::Write,
+    ) -> std::io::Result<ComparisonSummary> {
+        let mut writer = std::io::BufWriter::new(writer);
+        let mut summary = ComparisonSummary::default();
+        for change in self {
+            serde_json::to_writer(&mut writer, &change)?;
+            std::io::Write::write_all(&mut writer, b"\n")?;
+            summary.record(&change);
+        }
+        std::io::Write::flush(&mut writer)?;
+        Ok(summary)
+    }
+
+    /// Queue the changes between two copies of a page.
+    fn compare_pages(self: &mut DumpComparison, older: &Page, newer: &Page) {
+        if older.title() != newer.title() {
+            self.pending.push_back(PageChange::Renamed {
+                page_id: newer.id(),
+                old_title: older.title().clone(),
+                new_title: newer.title().clone(),
+            });
+        }
+        let older_ids: std::collections::BTreeSet<i64> =
+            older.revisions().iter().map(|r| r.id()).collect();
+        let newer_ids: std::collections::BTreeSet<i64> =
+            newer.revisions().iter().map(|r| r.id()).collect();
+        let added: Vec<i64> = newer_ids.difference(&older_ids).copied().collect();
+        if !added.is_empty() {
+            self.pending.push_back(PageChange::RevisionsAdded {
+                page_id: newer.id(),
+                title: newer.title().clone(),
+                revision_ids: added,
+            });
+        }
+        let vanished: Vec<i64> = older_ids.difference(&newer_ids).copied().collect();
+        if !vanished.is_empty() {
+            self.pending.push_back(PageChange::RevisionsVanished {
+                page_id: newer.id(),
+                title: newer.title().clone(),
+                revision_ids: vanished,
+            });
+        }
+    }
+}
+
+impl Iterator for DumpComparison {
+    type Item = PageChange;
+
+    fn next(&mut self) -> Option<PageChange> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Some(change);
+            }
+
+            // Both sides are in order of page ID, so the page with the lower ID is missing from
+            // the other side
+            let older_id = self.older.peek().map(Page::id);
+            let newer_id = self.newer.peek().map(Page::id);
+            match (older_id, newer_id) {
+                (None, None) => return None,
+                (Some(older_id), Some(newer_id)) if older_id == newer_id => {
+                    let older = self.older.next()?;
+                    let newer = self.newer.next()?;
+                    self.compare_pages(&older, &newer);
+                }
+                (Some(older_id), newer_id) if newer_id.is_none_or(|id| older_id < id) => {
+                    let older = self.older.next()?;
+                    return Some(PageChange::Deleted {
+                        page_id: older.id(),
+                        title: older.title().clone(),
+                    });
+                }
+                _ => {
+                    let newer = self.newer.next()?;
+                    return Some(PageChange::Created {
+                        page_id: newer.id(),
+                        title: newer.title().clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl DumpStore {
+    /// Compare the pages in this [`DumpStore`] with those in `newer`, a [`DumpStore`] of dumps of
+    /// the same wiki from a later date. Both are read at the same time in order of page ID (see
+    /// [`Schedule::with_page_id_order`]), so the page ID ranges of the dumps in each store should
+    /// not overlap (see [`DumpStore::coverage`]).
+    pub fn compare(self: &DumpStore, newer: &DumpStore) -> DumpComparison {
+        let schedule = Schedule::default().with_page_id_order(true);
+        DumpComparison {
+            older: self.scheduled_pages(&schedule).peekable(),
+            newer: newer.scheduled_pages(&schedule).peekable(),
+            pending: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+
+    const TIME: &str = "2020-01-01T00:00:00Z";
+
+    /// A store of `pages`, split between two dumps at page ID 5.
+    fn store(name: &str, pages: &[Page]) -> DumpStore {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-compare-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let (first, second): (Vec<Page>, Vec<Page>) =
+            pages.iter().map(Page::duplicate).partition(|p| p.id() <= 5);
+        let date = if name.starts_with("old") {
+            "20230101"
+        } else {
+            "20230201"
+        };
+        crate::dump::test_dump(
+            &dir,
+            &format!("enwiki-{}-pages-meta-history1.xml-p1p5.7z", date),
+            &first,
+        );
+        crate::dump::test_dump(
+            &dir,
+            &format!("enwiki-{}-pages-meta-history2.xml-p6p10.7z", date),
+            &second,
+        );
+        DumpStore::new(dir).unwrap()
+    }
+
+    fn stores(name: &str) -> (DumpStore, DumpStore) {
+        let older = store(
+            &format!("old-{}", name),
+            &[
+                test_page(1, "A", &[(10, TIME, "a")]),
+                test_page(2, "B", &[(20, TIME, "b"), (21, TIME, "b")]),
+                test_page(3, "C", &[(30, TIME, "c")]),
+                test_page(7, "G", &[(70, TIME, "g")]),
+            ],
+        );
+        let newer = store(
+            &format!("new-{}", name),
+            &[
+                test_page(1, "A", &[(10, TIME, "a"), (11, TIME, "a")]),
+                test_page(2, "Moved B", &[(20, TIME, "b"), (22, TIME, "b")]),
+                test_page(4, "D", &[(40, TIME, "d")]),
+                test_page(7, "G", &[(70, TIME, "g")]),
+                test_page(8, "H", &[(80, TIME, "h")]),
+            ],
+        );
+        (older, newer)
+    }
+
+    fn remove(stores: (DumpStore, DumpStore)) {
+        std::fs::remove_dir_all(stores.0.dump_dir()).unwrap();
+        std::fs::remove_dir_all(stores.1.dump_dir()).unwrap();
+    }
+
+    #[test]
+    fn changes_are_found_in_page_order() {
+        let (older, newer) = stores("changes");
+        let changes: Vec<PageChange> = older.compare(&newer).collect();
+        assert_eq!(
+            changes,
+            [
+                PageChange::RevisionsAdded {
+                    page_id: 1,
+                    title: "A".to_string(),
+                    revision_ids: vec![11],
+                },
+                PageChange::Renamed {
+                    page_id: 2,
+                    old_title: "B".to_string(),
+                    new_title: "Moved B".to_string(),
+                },
+                PageChange::RevisionsAdded {
+                    page_id: 2,
+                    title: "Moved B".to_string(),
+                    revision_ids: vec![22],
+                },
+                PageChange::RevisionsVanished {
+                    page_id: 2,
+                    title: "Moved B".to_string(),
+                    revision_ids: vec![21],
+                },
+                PageChange::Deleted {
+                    page_id: 3,
+                    title: "C".to_string(),
+                },
+                PageChange::Created {
+                    page_id: 4,
+                    title: "D".to_string(),
+                },
+                PageChange::Created {
+                    page_id: 8,
+                    title: "H".to_string(),
+                },
+            ]
+        );
+        remove((older, newer));
+    }
+
+    #[test]
+    fn change_logs_have_a_json_object_per_line() {
+        let (older, newer) = stores("log");
+        let mut log = Vec::new();
+        let summary = older.compare(&newer).write_change_log(&mut log).unwrap();
+        assert_eq!(
+            summary,
+            ComparisonSummary {
+                created: 2,
+                deleted: 1,
+                renamed: 1,
+                edited: 2,
+                revisions_added: 2,
+                revisions_vanished: 1,
+            }
+        );
+
+        let log = String::from_utf8(log).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[1],
+            r#"{"change":"renamed","page_id":2,"old_title":"B","new_title":"Moved B"}"#
+        );
+        assert_eq!(lines[4], r#"{"change":"deleted","page_id":3,"title":"C"}"#);
+        let changes: Vec<PageChange> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(changes, older.compare(&newer).collect::<Vec<_>>());
+        remove((older, newer));
+    }
+}
@@ -0,0 +1,320 @@
+use super::{Dump, DumpStore, Parser, ScanOptions};
+use rayon::prelude::*;
+
+/// A problem with the page ID ranges of the dumps in a [`DumpStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverageIssue {
+    /// No dump claims the page IDs from `first` to `last`, although dumps claim page IDs on either
+    /// side (or, for a gap starting at 1, after it).
+    Gap { first: i64, last: i64 },
+    /// Two dumps claim the page IDs from `first` to `last`.
+    Overlap {
+        first: i64,
+        last: i64,
+        dumps: (std::path::PathBuf, std::path::PathBuf),
+    },
+    /// Several dumps claim exactly the same range of page IDs.
+    Duplicate {
+        range: (i64, i64),
+        dumps: Vec<std::path::PathBuf>,
+    },
+    /// The file name of a dump does not give a range of page IDs, so its coverage is unknown.
+    Unranged { dump: std::path::PathBuf },
+    /// Parsing the dump found pages outside its declared range of page IDs.
+    OutOfRange {
+        dump: std::path::PathBuf,
+        page_ids: Vec<i64>,
+    },
+}
+
+impl std::fmt::Display for CoverageIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoverageIssue::Gap { first, last } => {
+                write!(f, "Gap: no dump claims page IDs {} to {}", first, last)
+            }
+            CoverageIssue::Overlap { first, last, dumps } => write!(
+                f,
+                "Overlap: page IDs {} to {} are claimed by {} and {}",
+                first,
+                last,
+                dumps.0.display(),
+                dumps.1.display()
+            ),
+            CoverageIssue::Duplicate { range, dumps } => {
+                write!(
+                    f,
+                    "Duplicate: page IDs {} to {} are claimed by",
+                    range.0, range.1
+                )?;
+                for dump in dumps {
+                    write!(f, " {}", dump.display())?;
+                }
+                Ok(())
+            }
+            CoverageIssue::Unranged { dump } => {
+                write!(f, "Unranged: {} has no page ID range", dump.display())
+            }
+            CoverageIssue::OutOfRange { dump, page_ids } => write!(
+                f,
+                "Out of range: {} contains {} pages outside its range, like page {}",
+                dump.display(),
+                page_ids.len(),
+                page_ids[0]
+            ),
+        }
+    }
+}
+
+/// The result of checking the page ID ranges of the dumps in a [`DumpStore`].
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    /// The lowest and highest page IDs claimed by the dumps (if any dumps have a range).
+    pub page_id_range: Option<(i64, i64)>,
+    /// The problems found.
+    pub issues: Vec<CoverageIssue>,
+}
+
+impl CoverageReport {
+    /// Returns [`true`] if the dumps cover a contiguous range of page IDs starting at 1.
+    pub fn is_ok(self: &CoverageReport) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl std::fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.page_id_range {
+            Some((first, last)) => writeln!(f, "Dumps claim page IDs {} to {}", first, last)?,
+            None => writeln!(f, "No dumps have a page ID range")?,
+        }
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the page ID ranges of a set of dumps of the same kind.
+fn check_ranges(dumps: &[&Dump], report: &mut CoverageReport) {
+    let mut ranged: Vec<(i64, i64, &std::path::PathBuf)> = Vec::new();
+    for dump in dumps {
+        match dump.page_id_range() {
+            Some((first, last)) => ranged.push((first, last, dump.path())),
+            None => report.issues.push(CoverageIssue::Unranged {
+                dump: dump.path().clone(),
+            }),
+        }
+    }
+    ranged.sort();
+
+    // Dumps with identical ranges are reported once as duplicates rather than as overlaps
+    let mut distinct: Vec<(i64, i64, &std::path::PathBuf)> = Vec::new();
+    for group in ranged.chunk_by(|a, b| (a.0, a.1) == (b.0, b.1)) {
+        if group.len() > 1 {
+            report.issues.push(CoverageIssue::Duplicate {
+                range: (group[0].0, group[0].1),
+                dumps: group.iter().map(|(_, _, path)| (*path).clone()).collect(),
+            });
+        }
+        distinct.push(group[0]);
+    }
+
+    // The highest page ID claimed so far, and the dump claiming it
+    let mut covered: Option<(i64, &std::path::PathBuf)> = None;
+    for (first, last, path) in distinct {
+        let next_id = covered.map_or(1, |(last, _)| last + 1);
+        if first > next_id {
+            report.issues.push(CoverageIssue::Gap {
+                first: next_id,
+                last: first - 1,
+            });
+        } else if let Some((covered_last, covered_path)) = covered.filter(|_| first < next_id) {
+            report.issues.push(CoverageIssue::Overlap {
+                first,
+                last: last.min(covered_last),
+                dumps: (covered_path.clone(), path.clone()),
+            });
+        }
+        if covered.is_none_or(|(covered_last, _)| last > covered_last) {
+            covered = Some((last, path));
+        }
+        report.page_id_range = Some(match report.page_id_range {
+            Some((min_id, max_id)) => (min_id.min(first), max_id.max(last)),
+            None => (first, last),
+        });
+    }
+}
+
+impl DumpStore {
+    /// Check that the page ID ranges given in the file names of the dumps cover a contiguous range
+    /// of page IDs starting at 1, without overlaps. Dumps of each wiki, date and kind are checked
+    /// separately.
+    ///
+    /// As the ranges only say which page IDs a dump purports to claim (see
+    /// [`Dump::contains_page_id`]), if `parse` is [`true`] every dump is also read to check that
+    /// its pages actually lie within its range.
+    pub fn coverage(self: &DumpStore, parse: bool) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        let mut groups: std::collections::BTreeMap<_, Vec<&Dump>> =
+            std::collections::BTreeMap::new();
+        for dump in self.dumps() {
+            groups
+                .entry((dump.dbname(), dump.date(), dump.kind()))
+                .or_default()
+                .push(dump);
+        }
+        for dumps in groups.values() {
+            check_ranges(dumps, &mut report);
+        }
+
+        if parse {
+            // Every page is read, whatever the page shard and increments of the store's options
+            let mut options = ScanOptions::new().with_parser(Parser::Fast);
+            if let Some(token) = self.options().cancellation() {
+                options = options.with_cancellation(token.clone());
+            }
+            let out_of_range: Vec<CoverageIssue> = self
+                .dumps()
+                .par_iter()
+                .filter(|d| d.page_id_range().is_some() && d.kind().contains_pages())
+                .filter_map(|dump| {
+                    let page_ids: Vec<i64> = dump
+                        .pages_with(&options)
+                        .map(|p| p.id())
+                        .filter(|&id| !dump.contains_page_id(id))
+                        .collect();
+                    (!page_ids.is_empty()).then(|| CoverageIssue::OutOfRange {
+                        dump: dump.path().clone(),
+                        page_ids,
+                    })
+                })
+                .collect();
+            report.issues.extend(out_of_range);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(range: &str) -> std::path::PathBuf {
+        match range {
+            "" => "enwiki-20230101-pages-meta-history.xml.7z".into(),
+            range => format!("enwiki-20230101-pages-meta-history1.xml-{}.7z", range).into(),
+        }
+    }
+
+    /// Check the ranges of dumps with the given `p{first}p{last}` ranges (or none, if empty).
+    fn check(ranges: &[&str]) -> CoverageReport {
+        let dumps: Vec<Dump> = ranges
+            .iter()
+            .map(|range| Dump::new(path(range)).unwrap())
+            .collect();
+        let mut report = CoverageReport::default();
+        check_ranges(&dumps.iter().collect::<Vec<_>>(), &mut report);
+        report
+    }
+
+    #[test]
+    fn contiguous_ranges_are_ok() {
+        let report = check(&["p11p20", "p1p10", "p21p25"]);
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.page_id_range, Some((1, 25)));
+    }
+
+    #[test]
+    fn gaps_are_reported() {
+        let report = check(&["p5p10", "p21p30"]);
+        assert_eq!(
+            report.issues,
+            [
+                CoverageIssue::Gap { first: 1, last: 4 },
+                CoverageIssue::Gap {
+                    first: 11,
+                    last: 20
+                },
+            ]
+        );
+        assert_eq!(report.page_id_range, Some((5, 30)));
+    }
+
+    #[test]
+    fn overlaps_are_reported() {
+        let report = check(&["p1p10", "p8p20", "p12p15", "p21p30"]);
+        assert_eq!(
+            report.issues,
+            [
+                CoverageIssue::Overlap {
+                    first: 8,
+                    last: 10,
+                    dumps: (path("p1p10"), path("p8p20")),
+                },
+                // Entirely within the previous range
+                CoverageIssue::Overlap {
+                    first: 12,
+                    last: 15,
+                    dumps: (path("p8p20"), path("p12p15")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_and_unranged_dumps_are_reported() {
+        let report = check(&["p1p10", "", "p1p10"]);
+        assert_eq!(
+            report.issues,
+            [
+                CoverageIssue::Unranged { dump: path("") },
+                CoverageIssue::Duplicate {
+                    range: (1, 10),
+                    dumps: vec![path("p1p10"), path("p1p10")],
+                },
+            ]
+        );
+        assert_eq!(report.page_id_range, Some((1, 10)));
+
+        let report = check(&[""]);
+        assert_eq!(report.page_id_range, None);
+        assert_eq!(report.issues.len(), 1);
+    }
+
+    #[test]
+    fn pages_outside_the_range_are_reported_when_parsing() {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-coverage-{}",
+            std::process::id()
+        ));
+        let pages: Vec<crate::Page> = [1, 2, 5]
+            .into_iter()
+            .map(|id| crate::dump::page::test_page(id, "A", &[(id, "2020-01-01T00:00:00Z", "")]))
+            .collect();
+        let dump = crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p3.7z",
+            &pages,
+        );
+        // Page 5 is not in the shard, but is still read
+        let index = if crate::Shard::new(0, 2).unwrap().contains_page_id(5) {
+            1
+        } else {
+            0
+        };
+        let store = DumpStore::new(dir.clone())
+            .unwrap()
+            .with_options(ScanOptions::new().with_page_shard(crate::Shard::new(index, 2).unwrap()));
+
+        assert!(store.coverage(false).is_ok());
+        assert_eq!(
+            store.coverage(true).issues,
+            [CoverageIssue::OutOfRange {
+                dump: dump.path().clone(),
+                page_ids: vec![5],
+            }]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use super::{DumpStore, Page, Revision};
+use rayon::prelude::*;
+
+/// A search for a regular expression in the bodies of every revision of pages.
+#[derive(Debug, Clone)]
+pub struct Grep {
+    regex: regex::Regex,
+    all_revisions: bool,
+    namespaces: Vec<i64>,
+}
+
+/// A revision of a page in the results of a [`Grep`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GrepRevision {
+    pub revision_id: i64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// User name or IP address of the contributor, unless it has been deleted.
+    pub contributor: Option<String>,
+}
+
+/// A run of consecutive revisions of a page whose bodies match.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GrepSpan {
+    /// The first matching revision of the run, which added the match.
+    pub added: GrepRevision,
+    /// The first revision after the run, which removed the match, or [`None`] if the latest
+    /// revision matches.
+    pub removed: Option<GrepRevision>,
+    /// Number of revisions in the run.
+    pub revisions: usize,
+}
+
+/// The revisions of a page whose bodies match a [`Grep`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PageMatches {
+    pub page_id: i64,
+    pub namespace: i64,
+    pub title: String,
+    /// The earliest matching revision.
+    pub first: GrepRevision,
+    /// The latest matching revision.
+    pub last: GrepRevision,
+    /// The runs of matching revisions, in order of time. The match is still present if the last
+    /// run was never removed.
+    pub spans: Vec<GrepSpan>,
+    /// Every matching revision, if requested with [`Grep::with_all_revisions`] (otherwise empty).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<GrepRevision>,
+}
+
+impl GrepRevision {
+    fn new(rev: &Revision) -> GrepRevision {
+        GrepRevision {
+            revision_id: rev.id(),
+            timestamp: *rev.timestamp(),
+            contributor: rev.contributor_username().or(rev.contributor_ip()).cloned(),
+        }
+    }
+}
+
+impl PageMatches {
+    /// Return whether the latest revision of the page matches.
+    pub fn is_present(self: &PageMatches) -> bool {
+        self.spans.last().is_some_and(|span| span.removed.is_none())
+    }
+
+    /// Total number of matching revisions.
+    pub fn revision_count(self: &PageMatches) -> usize {
+        self.spans.iter().map(|span| span.revisions).sum()
+    }
+}
+
+impl Grep {
+    /// Create a [`Grep`] for `regex`.
+    pub fn new(regex: regex::Regex) -> Grep {
+        Grep {
+            regex,
+            all_revisions: false,
+            namespaces: Vec::new(),
+        }
+    }
+
+    /// List every matching revision in [`PageMatches::revisions`].
+    pub fn with_all_revisions(mut self: Grep, all_revisions: bool) -> Grep {
+        self.all_revisions = all_revisions;
+        self
+    }
+
+    /// Only search pages in the specified namespaces. If none are specified (the default), pages in
+    /// every namespace are searched.
+    pub fn with_namespaces(mut self: Grep, namespaces: impl IntoIterator<Item = i64>) -> Grep {
+        self.namespaces = namespaces.into_iter().collect();
+        self
+    }
+
+    /// Return the namespaces searched, or an empty slice if every namespace is searched.
+    pub fn namespaces(self: &Grep) -> &[i64] {
+        &self.namespaces
+    }
+
+    /// Return the regular expression searched for.
+    pub fn regex(self: &Grep) -> &regex::Regex {
+        &self.regex
+    }
+
+    /// Search the revisions of `page` (which are in order of time), returning [`None`] if none
+    /// of them match or the page is not in a namespace searched. This fails if a body spilled to
+    /// disk cannot be read back.
+    pub fn search(self: &Grep, page: &Page) -> std::io::Result<Option<PageMatches>> {
+        if !self.namespaces.is_empty() && !self.namespaces.contains(&page.namespace()) {
+            return Ok(None);
+        }
+        let mut spans: Vec<GrepSpan> = Vec::new();
+        let mut revisions = Vec::new();
+        let mut matching = false;
+        let mut last = None;
+        let mut previous_body = None;
+        for rev in page.revisions() {
+            let body = rev.read_body()?;
+            // Consecutive revisions often have the same body (e.g. those recording page moves)
+            if previous_body.as_ref() != Some(&body) {
+                matching = self.regex.is_match(&body);
+                previous_body = Some(body);
+            }
+            match spans.last_mut() {
+                Some(span) if span.removed.is_none() => {
+                    if matching {
+                        span.revisions += 1;
+                    } else {
+                        span.removed = Some(GrepRevision::new(rev));
+                    }
+                }
+                _ if matching => spans.push(GrepSpan {
+                    added: GrepRevision::new(rev),
+                    removed: None,
+                    revisions: 1,
+                }),
+                _ => {}
+            }
+            if matching {
+                last = Some(rev);
+                if self.all_revisions {
+                    revisions.push(GrepRevision::new(rev));
+                }
+            }
+        }
+
+        let Some(last) = last else {
+            return Ok(None);
+        };
+        Ok(Some(PageMatches {
+            page_id: page.id(),
+            namespace: page.namespace(),
+            title: page.title().clone(),
+            first: spans[0].added.clone(),
+            last: GrepRevision::new(last),
+            spans,
+            revisions,
+        }))
+    }
+}
+
+impl DumpStore {
+    /// Search the bodies of every revision of every page for a match of `grep`, returning a
+    /// parallel iterator over the pages with a match (in no particular order), or the errors
+    /// reading back bodies spilled to disk.
+    pub fn grep<'a>(
+        self: &'a DumpStore,
+        grep: &'a Grep,
+    ) -> impl rayon::iter::ParallelIterator<Item = std::io::Result<PageMatches>> + 'a {
+        self.par_pages()
+            .filter_map(move |page| grep.search(&page).transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(pattern: &str) -> Grep {
+        Grep::new(regex::Regex::new(pattern).unwrap())
+    }
+
+    fn page() -> Page {
+        crate::dump::page::test_page(
+            1,
+            "Example",
+            &[
+                (10, "2020-01-01T00:00:00Z", "plain"),
+                (11, "2020-02-01T00:00:00Z", "with [[Category:X]]"),
+                (12, "2020-03-01T00:00:00Z", "with [[Category:X]]"),
+                (13, "2020-04-01T00:00:00Z", "plain again"),
+                (14, "2020-05-01T00:00:00Z", "[[Category:X]] back"),
+            ],
+        )
+    }
+
+    #[test]
+    fn spans_record_when_matches_are_added_and_removed() {
+        let matches = grep(r"\[\[Category:X\]\]")
+            .search(&page())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (matches.first.revision_id, matches.last.revision_id),
+            (11, 14)
+        );
+        let spans: Vec<(i64, Option<i64>, usize)> = matches
+            .spans
+            .iter()
+            .map(|s| {
+                let removed = s.removed.as_ref().map(|r| r.revision_id);
+                (s.added.revision_id, removed, s.revisions)
+            })
+            .collect();
+        assert_eq!(spans, [(11, Some(13), 2), (14, None, 1)]);
+        assert!(matches.is_present());
+        assert_eq!(matches.revision_count(), 3);
+        assert_eq!(matches.first.contributor.as_deref(), Some("User 11"));
+        assert!(matches.revisions.is_empty());
+    }
+
+    #[test]
+    fn removed_matches_are_not_present() {
+        let matches = grep("with").search(&page()).unwrap().unwrap();
+        assert_eq!(matches.spans.len(), 1);
+        assert_eq!(
+            matches.spans[0].removed.as_ref().map(|r| r.revision_id),
+            Some(13)
+        );
+        assert!(!matches.is_present());
+    }
+
+    #[test]
+    fn all_revisions_are_listed_when_requested() {
+        let matches = grep("plain")
+            .with_all_revisions(true)
+            .search(&page())
+            .unwrap()
+            .unwrap();
+        let ids: Vec<i64> = matches.revisions.iter().map(|r| r.revision_id).collect();
+        assert_eq!(ids, [10, 13]);
+        assert_eq!(matches.spans.len(), 2);
+    }
+
+    #[test]
+    fn pages_without_matches_or_in_other_namespaces_are_skipped() {
+        assert_eq!(grep("missing").search(&page()).unwrap(), None);
+        let in_talk = grep("plain").with_namespaces([1]);
+        assert_eq!(in_talk.search(&page()).unwrap(), None);
+        let in_main = grep("plain").with_namespaces([1, 0]);
+        assert!(in_main.search(&page()).unwrap().is_some());
+    }
+}
@@ -0,0 +1,322 @@
+use super::catalog::DumpCatalog;
+use super::{DumpKind, Page};
+use rayon::prelude::*;
+
+// The regex for edit comments of revisions recording a page move, like
+// `Example moved page [[Old title]] to [[New title]]: reason` (or `moved [[..]] to [[..]]` in
+// older revisions)
+static MOVE_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r"moved (?:page )?\[\[([^\]]+)\]\] to \[\[([^\]]+)\]\]").unwrap()
+});
+
+/// The namespace and title of a page from a dump date until the next [`TitleRecord`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TitleRecord {
+    /// Date of the first dump in which the page had this namespace and title.
+    pub date: chrono::NaiveDate,
+    pub namespace: i64,
+    /// Title, including any namespace prefix.
+    pub title: String,
+}
+
+/// A page move recorded by the edit comment of a revision.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PageMove {
+    /// ID of the revision whose comment records the move.
+    pub revision_id: i64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Title before the move.
+    pub from: String,
+    /// Title after the move.
+    pub to: String,
+}
+
+/// The history of the title and namespace of a page (identified by its page ID).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TitleHistory {
+    page_id: i64,
+    titles: Vec<TitleRecord>,
+    last_seen: chrono::NaiveDate,
+    moves: Vec<PageMove>,
+}
+
+impl TitleHistory {
+    /// Page ID.
+    pub fn page_id(self: &TitleHistory) -> i64 {
+        self.page_id
+    }
+
+    /// The titles of the page, from oldest to newest. A new record is only added when the
+    /// namespace or title differs from the previous dump date.
+    pub fn titles(self: &TitleHistory) -> &Vec<TitleRecord> {
+        &self.titles
+    }
+
+    /// Date of the latest dump in which the page was found.
+    pub fn last_seen(self: &TitleHistory) -> chrono::NaiveDate {
+        self.last_seen
+    }
+
+    /// The moves of the page recorded in the edit comments of its revisions, in order of time.
+    /// Unlike [`TitleHistory::titles`], these include moves between dump dates.
+    pub fn moves(self: &TitleHistory) -> &Vec<PageMove> {
+        &self.moves
+    }
+
+    /// The namespace and title of the page in the dump from `date` (or the latest dump before),
+    /// if the page had been seen by then.
+    pub fn title_on(self: &TitleHistory, date: chrono::NaiveDate) -> Option<&TitleRecord> {
+        self.titles.iter().rev().find(|record| record.date <= date)
+    }
+}
+
+/// The [`TitleHistory`] of every page seen in dumps from several dates, by page ID.
+///
+/// Dumps must be recorded in date order, e.g. with [`DumpCatalog::title_histories`].
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct TitleHistories {
+    pages: std::collections::BTreeMap<i64, TitleHistory>,
+}
+
+impl TitleHistories {
+    /// Create an empty [`TitleHistories`].
+    pub fn new() -> TitleHistories {
+        TitleHistories::default()
+    }
+
+    /// Record the title and namespace of a page in the dump from `date`, and any moves recorded in
+    /// the comments of its revisions.
+    pub fn record(self: &mut TitleHistories, date: chrono::NaiveDate, page: &Page) {
+        let moves = page_moves(page);
+        self.record_title(date, page.id(), page.namespace(), page.title(), moves);
+    }
+
+    fn record_title(
+        self: &mut TitleHistories,
+        date: chrono::NaiveDate,
+        page_id: i64,
+        namespace: i64,
+        title: &str,
+        moves: Vec<PageMove>,
+    ) {
+        let record = TitleRecord {
+            date,
+            namespace,
+            title: title.to_string(),
+        };
+        let history = self.pages.entry(page_id).or_insert_with(|| TitleHistory {
+            page_id,
+            titles: Vec::new(),
+            last_seen: date,
+            moves: Vec::new(),
+        });
+        match history.titles.last() {
+            Some(last) if last.namespace == namespace && last.title == title => {}
+            _ => history.titles.push(record),
+        }
+        history.last_seen = history.last_seen.max(date);
+
+        // Moves are included in the dumps of every later date, so they are only added once
+        for page_move in moves {
+            if !history
+                .moves
+                .iter()
+                .any(|m| m.revision_id == page_move.revision_id)
+            {
+                history.moves.push(page_move);
+            }
+        }
+        history.moves.sort_by_key(|m| (m.timestamp, m.revision_id));
+    }
+
+    /// Return the history of the page with the specified page ID (if it has been seen).
+    pub fn get(self: &TitleHistories, page_id: i64) -> Option<&TitleHistory> {
+        self.pages.get(&page_id)
+    }
+
+    /// Return an iterator over the history of every page seen, in order of page ID.
+    pub fn iter(self: &TitleHistories) -> impl Iterator<Item = &TitleHistory> {
+        self.pages.values()
+    }
+
+    /// Return the number of pages seen.
+    pub fn len(self: &TitleHistories) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns [`true`] if no pages have been seen.
+    pub fn is_empty(self: &TitleHistories) -> bool {
+        self.pages.is_empty()
+    }
+}
+
+/// Return the moves recorded in the comments of the revisions of a page.
+///
+/// When a page is moved, the redirect left at the old title starts with a revision with the same
+/// comment. So working back from the current title, a move is only kept if it is to the title the
+/// page had at that point.
+fn page_moves(page: &Page) -> Vec<PageMove> {
+    let mut title = page.title().as_str();
+    let mut moves = Vec::new();
+    for rev in page.revisions().iter().rev() {
+        let Some(caps) = rev.comment().and_then(|c| MOVE_REGEX.captures(c)) else {
+            continue;
+        };
+        let (from, to) = (caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str());
+        if to != title {
+            continue;
+        }
+        moves.push(PageMove {
+            revision_id: rev.id(),
+            timestamp: *rev.timestamp(),
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+        title = from;
+    }
+    moves.reverse();
+    moves
+}
+
+impl DumpCatalog {
+    /// Read the dumps of a kind for a wiki from every date in the catalog, from oldest to newest,
+    /// and return the title history of every page seen. Only the comments of the revisions in the
+    /// dumps are searched for moves, so history dumps (like [`DumpKind::PagesMetaHistory`]) find
+    /// the most.
+    pub fn title_histories(self: &DumpCatalog, dbname: &str, kind: DumpKind) -> TitleHistories {
+        let mut histories = TitleHistories::new();
+        for date in self.dates(dbname) {
+            let store = match self.store(dbname, date, kind) {
+                Some(store) => store,
+                None => continue,
+            };
+            let titles: Vec<(i64, i64, String, Vec<PageMove>)> = store
+                .par_pages()
+                .map(|page| {
+                    let moves = page_moves(&page);
+                    (page.id(), page.namespace(), page.title().clone(), moves)
+                })
+                .collect();
+            for (page_id, namespace, title, moves) in titles {
+                histories.record_title(date, page_id, namespace, &title, moves);
+            }
+        }
+        histories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A page moved from `Old` to `Middle` and then to `New`, followed by the redirect left at
+    /// `Old`.
+    const DUMP: &str = r#"<mediawiki>
+  <page>
+    <title>New</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>10</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <contributor><username>A</username><id>1</id></contributor>
+      <comment>Created page</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="4">Body</text>
+    </revision>
+    <revision>
+      <id>11</id>
+      <timestamp>2020-02-01T00:00:00Z</timestamp>
+      <contributor><username>B</username><id>2</id></contributor>
+      <comment>B moved [[Old]] to [[Middle]]</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="4">Body</text>
+    </revision>
+    <revision>
+      <id>13</id>
+      <timestamp>2020-03-01T00:00:00Z</timestamp>
+      <contributor><username>C</username><id>3</id></contributor>
+      <comment>C moved page [[Middle]] to [[New]]: better title</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="4">Body</text>
+    </revision>
+  </page>
+  <page>
+    <title>Old</title>
+    <ns>0</ns>
+    <id>2</id>
+    <revision>
+      <id>12</id>
+      <timestamp>2020-02-01T00:00:00Z</timestamp>
+      <contributor><username>B</username><id>2</id></contributor>
+      <comment>B moved [[Old]] to [[Middle]]</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="21">#REDIRECT [[Middle]]</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+
+    fn pages() -> Vec<Page> {
+        crate::dump::PageIterator::from_reader(DUMP.as_bytes()).collect()
+    }
+
+    fn date(s: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn move_regex_matches_both_comment_formats() {
+        for (comment, from, to) in [
+            ("A moved page [[Old]] to [[New]]", "Old", "New"),
+            (
+                "A moved [[Talk:Old]] to [[Talk:New]]: reason",
+                "Talk:Old",
+                "Talk:New",
+            ),
+        ] {
+            let caps = MOVE_REGEX.captures(comment).unwrap();
+            assert_eq!((&caps[1], &caps[2]), (from, to));
+        }
+        assert!(MOVE_REGEX.captures("Moved a paragraph").is_none());
+        assert!(MOVE_REGEX.captures("moved [[Old]] into [[New]]").is_none());
+    }
+
+    #[test]
+    fn moves_are_only_recorded_against_the_moved_page() {
+        let pages = pages();
+        let moves = page_moves(&pages[0]);
+        let moves: Vec<(i64, &str, &str)> = moves
+            .iter()
+            .map(|m| (m.revision_id, m.from.as_str(), m.to.as_str()))
+            .collect();
+        assert_eq!(moves, [(11, "Old", "Middle"), (13, "Middle", "New")]);
+        // The redirect left at the old title has the same comment, but was not moved
+        assert!(page_moves(&pages[1]).is_empty());
+    }
+
+    #[test]
+    fn titles_are_recorded_when_they_change() {
+        let mut histories = TitleHistories::new();
+        let pages = pages();
+        for day in ["2020-04-01", "2020-05-01"] {
+            histories.record(date(day), &pages[0]);
+        }
+        let history = histories.get(1).unwrap();
+        assert_eq!(history.titles().len(), 1);
+        assert_eq!(history.last_seen(), date("2020-05-01"));
+        // Moves found in both dumps are only recorded once
+        assert_eq!(history.moves().len(), 2);
+        assert_eq!(history.title_on(date("2020-03-01")), None);
+        assert_eq!(
+            history
+                .title_on(date("2020-06-01"))
+                .map(|r| r.title.as_str()),
+            Some("New")
+        );
+    }
+}
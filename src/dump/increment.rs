@@ -0,0 +1,347 @@
+use super::page::PageSource;
+use super::{Dump, DumpStore, Page, ScanOptions};
+
+/// The pages of daily incremental ("adds-changes") dumps, merged in date order, which are layered
+/// over the pages of a [`DumpStore`].
+#[derive(Debug)]
+pub(crate) struct Increments {
+    /// The incremental dumps, in date order.
+    dumps: Vec<Dump>,
+    /// The pages of all the incremental dumps, by page ID.
+    pages: std::collections::HashMap<i64, Page>,
+    /// The IDs of the incremental pages which each dump of the store (by path) is responsible for,
+    /// in order. Those which are not found in the dump are new pages, which are yielded among its
+    /// pages.
+    claims: std::collections::HashMap<std::path::PathBuf, Vec<i64>>,
+}
+
+impl Increments {
+    /// Read the incremental dumps, and assign each of their pages to a dump of `store`.
+    ///
+    /// Every page of the incremental dumps is held in memory, with the bodies of all its
+    /// revisions, for as long as the store (or any [`ScanOptions`] cloned from it) is kept. This
+    /// is roughly the uncompressed size of the incremental dumps, so it is only suitable for a
+    /// limited number of daily dumps. An error is returned if an incremental dump cannot be read.
+    fn new(store: &DumpStore, mut dumps: Vec<Dump>) -> std::io::Result<Increments> {
+        dumps.sort_by_key(|d| (d.date(), d.part()));
+        let options = ScanOptions::new().with_parser(store.options().parser());
+        let mut pages: std::collections::HashMap<i64, Page> = Default::default();
+        for dump in &dumps {
+            for page in dump.try_pages_with(&options) {
+                let page = page.map_err(|e| {
+                    std::io::Error::new(
+                        e.kind(),
+                        format!("Failed to read {}: {}", dump.path().display(), e),
+                    )
+                })?;
+                match pages.get_mut(&page.id()) {
+                    Some(existing) => existing.merge(&page),
+                    None => {
+                        pages.insert(page.id(), page);
+                    }
+                }
+            }
+        }
+
+        // A page is claimed by the dump whose range contains it, otherwise by a dump without a
+        // range, otherwise (for pages created since the base dumps) by the dump with the
+        // highest range
+        let page_dumps = store.page_dumps();
+        let ranged: Vec<&Dump> = page_dumps
+            .iter()
+            .filter(|d| d.page_id_range().is_some())
+            .collect();
+        let fallback = page_dumps
+            .iter()
+            .find(|d| d.page_id_range().is_none())
+            .or_else(|| ranged.iter().copied().max_by_key(|d| d.page_id_range()));
+        let mut claims: std::collections::HashMap<std::path::PathBuf, Vec<i64>> =
+            Default::default();
+        for &id in pages.keys() {
+            let owner = ranged
+                .iter()
+                .copied()
+                .find(|d| d.contains_page_id(id))
+                .or(fallback);
+            if let Some(owner) = owner {
+                claims.entry(owner.path().clone()).or_default().push(id);
+            }
+        }
+        for ids in claims.values_mut() {
+            ids.sort_unstable();
+        }
+
+        Ok(Increments {
+            dumps,
+            pages,
+            claims,
+        })
+    }
+
+    /// Return the incremental dumps, in date order.
+    pub(crate) fn dumps(self: &Increments) -> &Vec<Dump> {
+        &self.dumps
+    }
+
+    /// Return a copy of the incremental page with the specified ID (if any).
+    pub(crate) fn page(self: &Increments, page_id: i64) -> Option<Page> {
+        self.pages.get(&page_id).map(Page::duplicate)
+    }
+
+    /// Add the newer revisions of a page from the incremental dumps (if any).
+    pub(crate) fn merge_into(self: &Increments, page: &mut Page) {
+        if let Some(newer) = self.pages.get(&page.id()) {
+            page.merge(newer);
+        }
+    }
+}
+
+/// An iterator over the pages of a dump with the pages of the incremental dumps merged in.
+pub(crate) struct MergedPages<I: PageSource> {
+    pages: I,
+    options: ScanOptions,
+    /// IDs of incremental pages claimed by the dump which have not been yielded yet.
+    pending: std::collections::VecDeque<i64>,
+    /// A page read from the dump which follows the next pending page.
+    held: Option<Page>,
+    /// The error which stopped reading the dump (if any).
+    error: Option<std::io::Error>,
+}
+
+impl<I: PageSource> MergedPages<I> {
+    pub(crate) fn new(pages: I, dump: &Dump, options: &ScanOptions) -> MergedPages<I> {
+        let pending = options
+            .increments()
+            .and_then(|increments| increments.claims.get(dump.path()))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&id| options.page_shard().is_none_or(|s| s.contains_page_id(id)))
+            .collect();
+        MergedPages {
+            pages,
+            options: options.clone(),
+            pending,
+            held: None,
+            error: None,
+        }
+    }
+}
+
+impl<I: PageSource> MergedPages<I> {
+    /// Returns [`true`] if the dump's page with this ID was skipped by the [`MemoryBudget`], in
+    /// which case its newer revisions are skipped too.
+    ///
+    /// [`MemoryBudget`]: super::MemoryBudget
+    fn skipped(self: &MergedPages<I>, page_id: i64) -> bool {
+        self.options.memory_budget().is_some_and(|budget| {
+            matches!(budget.policy(), super::OversizePolicy::Skip)
+                && budget
+                    .oversized_pages()
+                    .iter()
+                    .any(|page| page.id == page_id)
+        })
+    }
+}
+
+impl<I: PageSource> Iterator for MergedPages<I> {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        let increments = match self.options.increments() {
+            Some(increments) => increments.clone(),
+            None => return self.pages.next(),
+        };
+        loop {
+            let page = match self.held.take() {
+                Some(page) => Some(page),
+                None => self.pages.next(),
+            };
+
+            let id = match page {
+                Some(mut page) => {
+                    // Pages are stored in order of their IDs, so pending pages with lower IDs are
+                    // new pages which should come first (or pages skipped from the dump)
+                    match self.pending.front() {
+                        Some(&id) if id < page.id() => {
+                            self.pending.pop_front();
+                            self.held = Some(page);
+                            id
+                        }
+                        Some(&id) if id == page.id() => {
+                            self.pending.pop_front();
+                            increments.merge_into(&mut page);
+                            return Some(page);
+                        }
+                        _ => return Some(page),
+                    }
+                }
+                // The remaining pending pages are new pages, unless reading the dump was cancelled
+                // or failed
+                None if self.options.is_cancelled() => return None,
+                None => {
+                    if self.error.is_none() {
+                        self.error = self.pages.take_error();
+                    }
+                    if self.error.is_some() {
+                        return None;
+                    }
+                    self.pending.pop_front()?
+                }
+            };
+            if !self.skipped(id) {
+                return increments.page(id);
+            }
+        }
+    }
+}
+
+impl<I: PageSource> PageSource for MergedPages<I> {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take().or_else(|| self.pages.take_error())
+    }
+}
+
+impl DumpStore {
+    /// Layer daily incremental ("adds-changes") dumps over the dumps in the [`DumpStore`], which
+    /// are read immediately. Their pages are kept in memory in full (roughly the uncompressed
+    /// size of the incremental dumps), so this suits days or weeks of daily dumps, not months.
+    /// Pages read from the store then include the newer revisions (and the current title and
+    /// namespace) from the incremental dumps, and pages created since are yielded among the pages
+    /// of the dump whose range contains them (or after the pages of the dump with the highest
+    /// range). An error is returned if an incremental dump cannot be read.
+    ///
+    /// Only dumps of kind [`DumpKind::PagesMetaHistIncr`](super::DumpKind::PagesMetaHistIncr) are
+    /// used. Revisions already in a page are not duplicated, so increments may overlap the base
+    /// dumps or each other. Pages skipped by the [`MemoryBudget`](super::MemoryBudget) of the
+    /// store are skipped entirely, rather than yielded with only their newer revisions. Note that
+    /// a [`WorkerPool`](super::WorkerPool) cannot read a store with incremental dumps.
+    pub fn with_increments(
+        self: DumpStore,
+        increments: impl IntoIterator<Item = Dump>,
+    ) -> std::io::Result<DumpStore> {
+        let dumps: Vec<Dump> = increments
+            .into_iter()
+            .filter(|d| d.kind() == super::DumpKind::PagesMetaHistIncr)
+            .collect();
+        let increments = Increments::new(&self, dumps)?;
+        let options = self
+            .options()
+            .clone()
+            .with_increments(Some(std::sync::Arc::new(increments)));
+        Ok(self.with_options(options))
+    }
+
+    /// Return the incremental dumps layered over the dumps in the [`DumpStore`], in date order.
+    pub fn increments(self: &DumpStore) -> &[Dump] {
+        match self.options().increments() {
+            Some(increments) => increments.dumps(),
+            None => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+    use crate::dump::{MemoryBudget, OversizePolicy};
+
+    /// A base dump of pages 1, 3 and 5, and an increment with a new revision of page 3 and new
+    /// pages 4 and 12.
+    fn store(name: &str) -> (DumpStore, Vec<Dump>) {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-increment-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let base = [
+            test_page(1, "A", &[(10, "2020-01-01T00:00:00Z", "a")]),
+            test_page(3, "C", &[(30, "2020-01-01T00:00:00Z", "a long body")]),
+            test_page(5, "E", &[(50, "2020-01-01T00:00:00Z", "e")]),
+        ];
+        crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p10.7z",
+            &base,
+        );
+        let increment = [
+            test_page(3, "C", &[(31, "2023-01-02T00:00:00Z", "c")]),
+            test_page(4, "D", &[(40, "2023-01-02T00:00:00Z", "d")]),
+            test_page(12, "L", &[(120, "2023-01-02T00:00:00Z", "l")]),
+        ];
+        let increments = vec![crate::dump::test_dump(
+            &dir.join("incr"),
+            "enwiki-20230102-pages-meta-hist-incr.xml.7z",
+            &increment,
+        )];
+        (DumpStore::new(dir).unwrap(), increments)
+    }
+
+    fn pages(store: &DumpStore) -> Vec<(i64, Vec<i64>)> {
+        store
+            .pages()
+            .map(|page| {
+                let revisions = page.revisions().iter().map(|r| r.id()).collect();
+                (page.id(), revisions)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn increments_are_merged_in_page_order() {
+        let (store, increments) = store("merge");
+        let dir = store.dump_dir().clone();
+        let store = store.with_increments(increments).unwrap();
+        assert_eq!(
+            pages(&store),
+            [
+                (1, vec![10]),
+                (3, vec![30, 31]),
+                (4, vec![40]),
+                (5, vec![50]),
+                (12, vec![120]),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pages_skipped_from_the_dump_are_not_new_pages() {
+        let (store, increments) = store("skip");
+        let dir = store.dump_dir().clone();
+        let budget = MemoryBudget::per_page(5, OversizePolicy::Skip);
+        let store = store
+            .with_options(ScanOptions::new().with_memory_budget(budget))
+            .with_increments(increments)
+            .unwrap();
+        assert_eq!(
+            pages(&store),
+            [(1, vec![10]), (4, vec![40]), (5, vec![50]), (12, vec![120])]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn new_pages_are_not_yielded_after_a_failed_read() {
+        let (store, increments) = store("failed");
+        let dir = store.dump_dir().clone();
+        let store = store.with_increments(increments).unwrap();
+        let dump = store.dumps()[0].clone();
+        std::fs::remove_file(dump.path()).unwrap();
+
+        let pages: Vec<std::io::Result<Page>> = dump.try_pages_with(store.options()).collect();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_increments_are_an_error() {
+        let (store, increments) = store("unreadable");
+        let dir = store.dump_dir().clone();
+        std::fs::remove_file(increments[0].path()).unwrap();
+        assert!(store.with_increments(increments).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,453 @@
+use super::page::PageSource;
+use super::{Dump, DumpStore, Page, PageIterator, Parser, ScanOptions};
+use rayon::prelude::*;
+use std::io::{BufRead, Write};
+
+/// The first line of an index file.
+const INDEX_HEADER: &str = "wiki-dump-analyzer page index\t1";
+
+/// Identifies the version of a dump file an index was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DumpFingerprint {
+    file_name: String,
+    size: u64,
+    /// Modification time, in seconds since the Unix epoch.
+    modified: u64,
+}
+
+impl DumpFingerprint {
+    fn new(dump: &Dump) -> std::io::Result<DumpFingerprint> {
+        let metadata = std::fs::metadata(dump.path())?;
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(DumpFingerprint {
+            file_name: file_name(dump),
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+fn file_name(dump: &Dump) -> String {
+    dump.path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Where a page is stored.
+#[derive(Debug, Clone, Copy)]
+struct PageLocation {
+    /// Position of the dump in [`PageIndex::dumps`].
+    dump: u32,
+    /// Offset of the page in the decompressed dump (see [`PageIterator::position`]).
+    offset: u64,
+}
+
+/// An index of the pages stored in the dumps of a [`DumpStore`], mapping page IDs to the dump
+/// (and position within it) where the page is stored, and page titles to page IDs.
+///
+/// An index is built by reading every dump once, and can then be saved to a file. It records the
+/// size and modification time of each dump, so that an index which no longer matches the dumps
+/// can be detected (see [`PageIndex::is_current`]).
+#[derive(Debug, Clone)]
+pub struct PageIndex {
+    dumps: Vec<DumpFingerprint>,
+    pages: std::collections::HashMap<i64, PageLocation>,
+    titles: std::collections::HashMap<String, i64>,
+}
+
+impl PageIndex {
+    /// Build an index by reading every page dump in `store` (in parallel).
+    pub fn build(store: &DumpStore) -> std::io::Result<PageIndex> {
+        let page_dumps = store.page_dumps();
+        let dumps = page_dumps
+            .iter()
+            .map(DumpFingerprint::new)
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let mut options = ScanOptions::new().with_parser(Parser::Fast);
+        if let Some(token) = store.options().cancellation() {
+            options = options.with_cancellation(token.clone());
+        }
+
+        let entries: Vec<(i64, PageLocation, String)> = page_dumps
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(i, dump)| {
+                let mut pages = PageIterator::from_path_with(dump.path(), options.clone());
+                std::iter::from_fn(move || {
+                    let offset = pages.position();
+                    let page = pages.next()?;
+                    let location = PageLocation {
+                        dump: i as u32,
+                        offset,
+                    };
+                    Some((page.id(), location, page.title().clone()))
+                })
+            })
+            .collect();
+
+        if store.options().is_cancelled() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "Building the index was cancelled",
+            ));
+        }
+
+        let mut pages = std::collections::HashMap::with_capacity(entries.len());
+        let mut titles = std::collections::HashMap::with_capacity(entries.len());
+        for (id, location, title) in entries {
+            pages.insert(id, location);
+            titles.insert(title, id);
+        }
+        Ok(PageIndex {
+            dumps,
+            pages,
+            titles,
+        })
+    }
+
+    /// Read an index from a file written by [`PageIndex::save`].
+    pub fn load(path: &std::path::Path) -> std::io::Result<PageIndex> {
+        let invalid = |message: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid page index {:?}: {}", path, message),
+            )
+        };
+
+        let mut lines = std::io::BufReader::new(std::fs::File::open(path)?).lines();
+        let mut next_line = || {
+            lines
+                .next()
+                .ok_or_else(|| invalid("unexpected end of file"))?
+        };
+
+        if next_line()? != INDEX_HEADER {
+            return Err(invalid("unknown format"));
+        }
+        let dump_count: usize = next_line()?
+            .parse()
+            .map_err(|_| invalid("bad dump count"))?;
+
+        let mut dumps = Vec::with_capacity(dump_count);
+        for _ in 0..dump_count {
+            let line = next_line()?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                [file_name, size, modified] => dumps.push(DumpFingerprint {
+                    file_name: file_name.to_string(),
+                    size: size.parse().map_err(|_| invalid("bad dump size"))?,
+                    modified: modified.parse().map_err(|_| invalid("bad dump time"))?,
+                }),
+                _ => return Err(invalid("bad dump line")),
+            }
+        }
+
+        let mut pages = std::collections::HashMap::new();
+        let mut titles = std::collections::HashMap::new();
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(4, '\t');
+            let mut field = || fields.next().ok_or_else(|| invalid("bad page line"));
+            let id: i64 = field()?.parse().map_err(|_| invalid("bad page ID"))?;
+            let dump: u32 = field()?.parse().map_err(|_| invalid("bad dump number"))?;
+            let offset: u64 = field()?.parse().map_err(|_| invalid("bad page offset"))?;
+            let title = field()?.to_string();
+            if dump as usize >= dumps.len() {
+                return Err(invalid("bad dump number"));
+            }
+            pages.insert(id, PageLocation { dump, offset });
+            // Pages whose title was shared with another page are saved without one
+            if !title.is_empty() {
+                titles.insert(title, id);
+            }
+        }
+
+        Ok(PageIndex {
+            dumps,
+            pages,
+            titles,
+        })
+    }
+
+    /// Write the index to a file. The file is replaced atomically, so an interrupted write leaves
+    /// the previous index intact.
+    pub fn save(self: &PageIndex, path: &std::path::Path) -> std::io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = std::path::PathBuf::from(tmp_path);
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+        writeln!(file, "{}", INDEX_HEADER)?;
+        writeln!(file, "{}", self.dumps.len())?;
+        for dump in &self.dumps {
+            writeln!(file, "{}\t{}\t{}", dump.file_name, dump.size, dump.modified)?;
+        }
+        let titles: std::collections::HashMap<i64, &str> = self
+            .titles
+            .iter()
+            .map(|(title, id)| (*id, title.as_str()))
+            .collect();
+        for (id, location) in &self.pages {
+            let title = titles.get(id).copied().unwrap_or_default();
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}",
+                id, location.dump, location.offset, title
+            )?;
+        }
+        file.into_inner()?.sync_all()?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    /// Returns [`true`] if the index was built from exactly the page dumps currently in `store`.
+    pub fn is_current(self: &PageIndex, store: &DumpStore) -> bool {
+        let mut indexed: Vec<&DumpFingerprint> = self.dumps.iter().collect();
+        let current: std::io::Result<Vec<DumpFingerprint>> = store
+            .page_dumps()
+            .iter()
+            .map(DumpFingerprint::new)
+            .collect();
+        match current {
+            Ok(mut current) => {
+                indexed.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+                current.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+                indexed.into_iter().eq(current.iter())
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Number of pages in the index.
+    pub fn len(self: &PageIndex) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns [`true`] if the index contains no pages.
+    pub fn is_empty(self: &PageIndex) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Return the ID of the page with the specified title (including any namespace prefix).
+    pub fn page_id(self: &PageIndex, title: &str) -> Option<i64> {
+        self.titles.get(title).copied()
+    }
+
+    /// Return the file name of the dump containing the specified page, and the offset of the
+    /// page within the decompressed dump.
+    pub fn locate(self: &PageIndex, page_id: i64) -> Option<(&str, u64)> {
+        let location = self.pages.get(&page_id)?;
+        let dump = &self.dumps[location.dump as usize];
+        Some((&dump.file_name, location.offset))
+    }
+}
+
+impl DumpStore {
+    /// Use `index` to look up pages. This has no effect if the index does not match the dumps in
+    /// the store (see [`PageIndex::is_current`]).
+    pub fn with_index(mut self: DumpStore, index: PageIndex) -> DumpStore {
+        self.index = if index.is_current(&self) {
+            Some(std::sync::Arc::new(index))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Load the index saved at `path` and use it to look up pages. If there is no index at `path`,
+    /// or it no longer matches the dumps in the store, a new index is built and saved there.
+    pub fn with_index_file(self: DumpStore, path: &std::path::Path) -> std::io::Result<DumpStore> {
+        if let Ok(index) = PageIndex::load(path) {
+            if index.is_current(&self) {
+                return Ok(self.with_index(index));
+            }
+        }
+        let index = PageIndex::build(&self)?;
+        index.save(path)?;
+        Ok(self.with_index(index))
+    }
+
+    /// Return the index used to look up pages (if any).
+    pub fn index(self: &DumpStore) -> Option<&PageIndex> {
+        self.index.as_deref()
+    }
+
+    /// Return the [`Page`] with the specified title (including any namespace prefix), if it can be
+    /// found in the dumps. Without an index, this reads every dump until the page is found. An
+    /// error is returned if a dump cannot be read.
+    pub fn page_by_title(self: &DumpStore, title: &str) -> std::io::Result<Option<Page>> {
+        match self.index() {
+            Some(index) => match index.page_id(title) {
+                Some(page_id) => self.page_by_id(page_id),
+                None => Ok(None),
+            },
+            None => {
+                for dump in self.page_dumps() {
+                    for page in dump.try_pages_with(self.options()) {
+                        let page = page?;
+                        if page.title() == title {
+                            return Ok(Some(page));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read the page with the specified ID using the index, returning [`None`] if it is not indexed
+    /// (or not found where the index says it is).
+    pub(crate) fn indexed_page(self: &DumpStore, page_id: i64) -> std::io::Result<Option<Page>> {
+        let Some((dump_file_name, offset)) = self.index().and_then(|index| index.locate(page_id))
+        else {
+            return Ok(None);
+        };
+        let Some(dump) = self
+            .page_dumps()
+            .into_iter()
+            .find(|d| file_name(d) == dump_file_name)
+        else {
+            return Ok(None);
+        };
+        let mut pages = PageIterator::from_path_at(dump.path(), offset, self.options().clone());
+        let Some(mut page) = pages.find(|p| p.id() == page_id) else {
+            return match pages.take_error() {
+                Some(e) => Err(e),
+                None => Ok(None),
+            };
+        };
+        if let Some(increments) = self.options().increments() {
+            increments.merge_into(&mut page);
+        }
+        Ok(Some(page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn example_index() -> PageIndex {
+        let dumps = vec![
+            DumpFingerprint {
+                file_name: "enwiki-20230101-pages-meta-history1.xml-p1p10.7z".to_string(),
+                size: 1234,
+                modified: 1672531200,
+            },
+            DumpFingerprint {
+                file_name: "enwiki-20230101-pages-meta-history2.xml-p11p20.7z".to_string(),
+                size: 5678,
+                modified: 1672531201,
+            },
+        ];
+        let pages = [(1, 0, 100), (2, 0, 2500), (12, 1, 0)]
+            .into_iter()
+            .map(|(id, dump, offset)| (id, PageLocation { dump, offset }))
+            .collect();
+        let titles = [("Example", 1), ("Talk:Example\twith a tab", 2)]
+            .into_iter()
+            .map(|(title, id)| (title.to_string(), id))
+            .collect();
+        PageIndex {
+            dumps,
+            pages,
+            titles,
+        }
+    }
+
+    #[test]
+    fn index_round_trips_through_a_file() {
+        let path = temp_path("index-round-trip");
+        let index = example_index();
+        index.save(&path).unwrap();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!std::path::Path::new(&tmp_path).exists());
+        let loaded = PageIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.dumps, index.dumps);
+        assert_eq!(loaded.len(), 3);
+        for id in [1, 2, 12] {
+            assert_eq!(loaded.locate(id), index.locate(id));
+        }
+        assert_eq!(
+            loaded.locate(12),
+            Some(("enwiki-20230101-pages-meta-history2.xml-p11p20.7z", 0))
+        );
+        assert_eq!(loaded.locate(3), None);
+        assert_eq!(loaded.page_id("Example"), Some(1));
+        // The title is the last field, so it may contain tabs
+        assert_eq!(loaded.page_id("Talk:Example\twith a tab"), Some(2));
+        // Page 12 has no title, so it is only found by ID
+        assert_eq!(loaded.titles.len(), 2);
+    }
+
+    #[test]
+    fn invalid_index_files_are_rejected() {
+        let path = temp_path("index-invalid");
+        for contents in [
+            "not an index\n".to_string(),
+            format!("{}\n", INDEX_HEADER),
+            format!("{}\n1\nonly-a-name\n", INDEX_HEADER),
+            format!("{}\n1\ndump.7z\t1\t2\n5\t1\t0\tTitle\n", INDEX_HEADER),
+            format!("{}\n1\ndump.7z\t1\t2\nx\t0\t0\tTitle\n", INDEX_HEADER),
+        ] {
+            std::fs::write(&path, &contents).unwrap();
+            let error = PageIndex::load(&path).unwrap_err();
+            assert_eq!(
+                error.kind(),
+                std::io::ErrorKind::InvalidData,
+                "{:?}",
+                contents
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pages_are_read_using_the_index() {
+        let dir = temp_path("index-lookup");
+        let pages: Vec<Page> = (1..=3)
+            .map(|id| {
+                let title = format!("Page {}", id);
+                crate::dump::page::test_page(id, &title, &[(id, "2020-01-01T00:00:00Z", "Body")])
+            })
+            .collect();
+        let dump = crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p3.7z",
+            &pages,
+        );
+        let store = DumpStore::new(dir.clone())
+            .unwrap()
+            .with_index_file(&dir.join("index"))
+            .unwrap();
+        assert_eq!(store.index().map(PageIndex::len), Some(3));
+
+        let page = store.page_by_id(2).unwrap().unwrap();
+        assert_eq!(page.title(), "Page 2");
+        let page = store.page_by_title("Page 3").unwrap().unwrap();
+        assert_eq!(page.id(), 3);
+        assert!(store.page_by_id(4).unwrap().is_none());
+        assert!(store.page_by_title("Page 4").unwrap().is_none());
+
+        // Failing to read the dump is an error rather than a missing page
+        std::fs::remove_file(dump.path()).unwrap();
+        assert!(store.page_by_id(2).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+/// The kind of content in a dump file, as given by its name.
+///
+/// More information about each kind is available [here](https://meta.wikimedia.org/wiki/Data_dumps/What%27s_available_for_download).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DumpKind {
+    /// Every revision of every page, with text (`pages-meta-history`).
+    PagesMetaHistory,
+    /// The current revision of every page, with text (`pages-meta-current`).
+    PagesMetaCurrent,
+    /// The current revision of articles, templates and other content pages (`pages-articles`).
+    PagesArticles,
+    /// The same pages as [`DumpKind::PagesArticles`], stored as many independently compressed
+    /// streams (`pages-articles-multistream`).
+    PagesArticlesMultistream,
+    /// The position of each page within a [`DumpKind::PagesArticlesMultistream`] file
+    /// (`pages-articles-multistream-index`). This is a text file, not XML.
+    MultistreamIndex,
+    /// Every revision of every page, without text (`stub-meta-history`).
+    StubMetaHistory,
+    /// The current revision of every page, without text (`stub-meta-current`).
+    StubMetaCurrent,
+    /// The current revision of content pages, without text (`stub-articles`).
+    StubArticles,
+    /// Log events, like page moves and deletions (`pages-logging`).
+    Logging,
+    /// Abstracts of articles (`abstract`).
+    Abstract,
+    /// Revisions created since the previous daily incremental dump, with text
+    /// (`pages-meta-hist-incr`).
+    PagesMetaHistIncr,
+    /// Revisions created since the previous daily incremental dump, without text
+    /// (`stubs-meta-hist-incr`).
+    StubsMetaHistIncr,
+}
+
+/// Every [`DumpKind`], in order.
+const KINDS: [DumpKind; 12] = [
+    DumpKind::PagesMetaHistory,
+    DumpKind::PagesMetaCurrent,
+    DumpKind::PagesArticles,
+    DumpKind::PagesArticlesMultistream,
+    DumpKind::MultistreamIndex,
+    DumpKind::StubMetaHistory,
+    DumpKind::StubMetaCurrent,
+    DumpKind::StubArticles,
+    DumpKind::Logging,
+    DumpKind::Abstract,
+    DumpKind::PagesMetaHistIncr,
+    DumpKind::StubsMetaHistIncr,
+];
+
+impl DumpKind {
+    /// Every [`DumpKind`].
+    pub fn all() -> &'static [DumpKind] {
+        &KINDS
+    }
+
+    /// The name of the kind as it appears in file names, like `pages-meta-history`.
+    pub fn as_str(self: &DumpKind) -> &'static str {
+        match self {
+            DumpKind::PagesMetaHistory => "pages-meta-history",
+            DumpKind::PagesMetaCurrent => "pages-meta-current",
+            DumpKind::PagesArticles => "pages-articles",
+            DumpKind::PagesArticlesMultistream => "pages-articles-multistream",
+            DumpKind::MultistreamIndex => "pages-articles-multistream-index",
+            DumpKind::StubMetaHistory => "stub-meta-history",
+            DumpKind::StubMetaCurrent => "stub-meta-current",
+            DumpKind::StubArticles => "stub-articles",
+            DumpKind::Logging => "pages-logging",
+            DumpKind::Abstract => "abstract",
+            DumpKind::PagesMetaHistIncr => "pages-meta-hist-incr",
+            DumpKind::StubsMetaHistIncr => "stubs-meta-hist-incr",
+        }
+    }
+
+    /// Returns [`true`] if dumps of this kind consist of `<page>` elements which can be read with
+    /// a [`PageIterator`](super::PageIterator). Note that the revisions in stub dumps have empty
+    /// bodies.
+    pub fn contains_pages(self: &DumpKind) -> bool {
+        !matches!(
+            self,
+            DumpKind::MultistreamIndex | DumpKind::Logging | DumpKind::Abstract
+        )
+    }
+
+    /// Returns [`true`] if dumps of this kind include every revision (for incremental dumps, every
+    /// new revision), rather than only the current one.
+    pub fn is_history(self: &DumpKind) -> bool {
+        matches!(
+            self,
+            DumpKind::PagesMetaHistory
+                | DumpKind::StubMetaHistory
+                | DumpKind::PagesMetaHistIncr
+                | DumpKind::StubsMetaHistIncr
+        )
+    }
+
+    /// Returns [`true`] if dumps of this kind are daily incremental ("adds-changes") dumps.
+    pub fn is_incremental(self: &DumpKind) -> bool {
+        matches!(
+            self,
+            DumpKind::PagesMetaHistIncr | DumpKind::StubsMetaHistIncr
+        )
+    }
+}
+
+impl std::fmt::Display for DumpKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DumpKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DumpKind, String> {
+        KINDS
+            .iter()
+            .find(|kind| kind.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown dump kind {:?}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip_through_names() {
+        for kind in DumpKind::all() {
+            assert_eq!(kind.as_str().parse::<DumpKind>(), Ok(*kind));
+            assert_eq!(kind.to_string(), kind.as_str());
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        assert_eq!(
+            "pages-meta".parse::<DumpKind>(),
+            Err("unknown dump kind \"pages-meta\"".to_string())
+        );
+        assert!("".parse::<DumpKind>().is_err());
+        assert!("Pages-Articles".parse::<DumpKind>().is_err());
+    }
+
+    #[test]
+    fn only_page_kinds_contain_pages() {
+        assert!(DumpKind::PagesMetaHistory.contains_pages());
+        assert!(DumpKind::StubArticles.contains_pages());
+        assert!(DumpKind::PagesMetaHistIncr.contains_pages());
+        assert!(!DumpKind::MultistreamIndex.contains_pages());
+        assert!(!DumpKind::Logging.contains_pages());
+        assert!(!DumpKind::Abstract.contains_pages());
+    }
+}
@@ -0,0 +1,288 @@
+use super::increment::Increments;
+use super::page::PageSource;
+use super::{Dump, DumpStore, Page, PageIterator};
+
+/// A dump to be searched for some of the requested pages.
+struct LookupTask {
+    dump: Dump,
+    /// Requested page IDs which may be in the dump.
+    page_ids: std::collections::HashSet<i64>,
+    /// Offset in the decompressed dump at which to start reading (from the index, if any).
+    offset: u64,
+}
+
+/// An iterator over the pages found by [`DumpStore::lookup_pages`], which are read on background
+/// threads and yielded as soon as they are found.
+///
+/// Once the iterator is exhausted, [`PageLookup::missing`] reports the requested pages which were
+/// not found, and [`PageLookup::error`] whether that is because a dump could not be read.
+pub struct PageLookup {
+    receiver: std::sync::mpsc::Receiver<std::io::Result<Page>>,
+    requested: std::collections::HashSet<i64>,
+    found: std::collections::HashSet<i64>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+    /// The incremental dumps, from which pages which are not in the dumps are yielded last.
+    increments: Option<std::sync::Arc<Increments>>,
+    /// The first error from reading a dump (if any).
+    error: Option<std::io::Error>,
+}
+
+impl PageLookup {
+    fn new(store: &DumpStore, page_ids: std::collections::HashSet<i64>) -> PageLookup {
+        let mut tasks: Vec<LookupTask> = match store.index() {
+            // With an index, only the dumps known to contain the pages are read, starting from
+            // the first requested page
+            Some(index) => {
+                let mut tasks: std::collections::HashMap<&str, LookupTask> = Default::default();
+                for &id in &page_ids {
+                    let (file_name, offset) = match index.locate(id) {
+                        Some(location) => location,
+                        None => continue,
+                    };
+                    let dump = match store.dumps().iter().find(|d| {
+                        d.path().file_name().and_then(|name| name.to_str()) == Some(file_name)
+                    }) {
+                        Some(dump) => dump,
+                        None => continue,
+                    };
+                    let task = tasks.entry(file_name).or_insert_with(|| LookupTask {
+                        dump: dump.clone(),
+                        page_ids: Default::default(),
+                        offset,
+                    });
+                    task.page_ids.insert(id);
+                    task.offset = task.offset.min(offset);
+                }
+                tasks.into_values().collect()
+            }
+            None => store
+                .page_dumps()
+                .into_iter()
+                .map(|dump| LookupTask {
+                    dump: dump.clone(),
+                    page_ids: page_ids
+                        .iter()
+                        .copied()
+                        .filter(|id| dump.contains_page_id(*id))
+                        .collect(),
+                    offset: 0,
+                })
+                .filter(|task| !task.page_ids.is_empty())
+                .collect(),
+        };
+        tasks.sort_by_cached_key(|t| std::cmp::Reverse(t.dump.file_size().unwrap_or(0)));
+
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let (sender, receiver) = std::sync::mpsc::sync_channel(threads);
+        let queue = std::sync::Arc::new(std::sync::Mutex::new(tasks));
+        let workers = (0..threads)
+            .map(|_| {
+                let (queue, sender) = (queue.clone(), sender.clone());
+                let options = store.options().clone();
+                std::thread::spawn(move || loop {
+                    let task = match queue.lock().unwrap().pop() {
+                        Some(task) => task,
+                        None => return,
+                    };
+                    let mut remaining = task.page_ids;
+                    let last_id = remaining.iter().copied().max().unwrap_or(i64::MIN);
+                    let mut pages =
+                        PageIterator::from_path_at(task.dump.path(), task.offset, options.clone());
+                    for mut page in pages.by_ref() {
+                        let id = page.id();
+                        if remaining.remove(&id) {
+                            if let Some(increments) = options.increments() {
+                                increments.merge_into(&mut page);
+                            }
+                            if sender.send(Ok(page)).is_err() {
+                                return;
+                            }
+                        }
+                        // Pages are stored in order of their IDs, so none of the rest are requested
+                        if remaining.is_empty() || id >= last_id {
+                            break;
+                        }
+                    }
+                    if let Some(e) = pages.take_error() {
+                        let e = std::io::Error::new(
+                            e.kind(),
+                            format!("Failed to read {}: {}", task.dump.path().display(), e),
+                        );
+                        if sender.send(Err(e)).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        PageLookup {
+            receiver,
+            requested: page_ids,
+            found: Default::default(),
+            workers,
+            increments: store.options().increments().cloned(),
+            error: None,
+        }
+    }
+
+    /// The error which stopped a dump from being read completely (if any). Pages in that dump are
+    /// reported by [`PageLookup::missing`] even if they are in it.
+    pub fn error(self: &PageLookup) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    /// The requested page IDs which have not been found. Once the iterator is exhausted, and
+    /// unless there was an [error](PageLookup::error), these are the pages which are not in any
+    /// dump.
+    pub fn missing(self: &PageLookup) -> Vec<i64> {
+        let mut missing: Vec<i64> = self.requested.difference(&self.found).copied().collect();
+        missing.sort_unstable();
+        missing
+    }
+}
+
+impl Iterator for PageLookup {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        loop {
+            match self.receiver.recv() {
+                Ok(Ok(page)) => {
+                    self.found.insert(page.id());
+                    return Some(page);
+                }
+                // The other dumps are still read after one fails
+                Ok(Err(e)) => {
+                    self.error.get_or_insert(e);
+                }
+                Err(_) => {
+                    // Propagate any panic from parsing a dump
+                    for worker in self.workers.drain(..) {
+                        if let Err(e) = worker.join() {
+                            std::panic::resume_unwind(e);
+                        }
+                    }
+
+                    // Pages created since the dumps may only be in the incremental dumps
+                    let page = self
+                        .missing()
+                        .into_iter()
+                        .find_map(|id| self.increments.as_ref()?.page(id))?;
+                    self.found.insert(page.id());
+                    return Some(page);
+                }
+            }
+        }
+    }
+}
+
+impl PageSource for PageLookup {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+impl DumpStore {
+    /// Look up the pages with the specified page IDs. Only the dumps which may contain the pages
+    /// are read (in parallel), and each stops as soon as all the requested pages it may contain
+    /// have been found. Pages are yielded in the order they are found.
+    pub fn lookup_pages(self: &DumpStore, page_ids: impl IntoIterator<Item = i64>) -> PageLookup {
+        PageLookup::new(self, page_ids.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+
+    fn store(name: &str) -> DumpStore {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-lookup-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let pages: Vec<Page> = [1, 2, 4, 7, 8, 9]
+            .into_iter()
+            .map(|id| {
+                test_page(
+                    id,
+                    &format!("Page {}", id),
+                    &[(id, "2020-01-01T00:00:00Z", "Body")],
+                )
+            })
+            .collect();
+        crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p5.7z",
+            &pages[..3],
+        );
+        crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history2.xml-p6p10.7z",
+            &pages[3..],
+        );
+        DumpStore::new(dir).unwrap()
+    }
+
+    fn lookup(store: &DumpStore, page_ids: &[i64]) -> (Vec<i64>, Vec<i64>) {
+        let mut lookup = store.lookup_pages(page_ids.iter().copied());
+        let mut found: Vec<i64> = lookup.by_ref().map(|page| page.id()).collect();
+        found.sort();
+        assert!(lookup.error().is_none());
+        (found, lookup.missing())
+    }
+
+    #[test]
+    fn requested_pages_are_found_or_missing() {
+        let store = store("find");
+        assert_eq!(
+            lookup(&store, &[2, 3, 8, 9, 12]),
+            (vec![2, 8, 9], vec![3, 12])
+        );
+        assert_eq!(lookup(&store, &[]), (vec![], vec![]));
+
+        // The same pages are found using an index
+        let dir = store.dump_dir().clone();
+        let indexed = store.with_index_file(&dir.join("index")).unwrap();
+        assert!(indexed.index().is_some());
+        assert_eq!(
+            lookup(&indexed, &[2, 3, 8, 9, 12]),
+            (vec![2, 8, 9], vec![3, 12])
+        );
+
+        let mut pages: Vec<(i64, String)> = indexed
+            .pages_by_id([7, 1, 5])
+            .unwrap()
+            .into_iter()
+            .map(|page| (page.id(), page.title().clone()))
+            .collect();
+        pages.sort();
+        assert_eq!(
+            pages,
+            [(1, "Page 1".to_string()), (7, "Page 7".to_string())]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_errors_are_returned() {
+        let store = store("error");
+        let failing = store.dumps().iter().find(|d| d.part() == Some(2)).unwrap();
+        std::fs::remove_file(failing.path()).unwrap();
+
+        let mut lookup = store.lookup_pages([2, 8]);
+        let found: Vec<i64> = lookup.by_ref().map(|page| page.id()).collect();
+        assert_eq!(found, [2]);
+        let error = lookup.error().expect("No error");
+        assert!(error.to_string().contains("history2"), "{}", error);
+        assert_eq!(lookup.missing(), [8]);
+
+        assert!(store.pages_by_id([2, 8]).is_err());
+        assert_eq!(store.pages_by_id([1]).unwrap().len(), 1);
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+}
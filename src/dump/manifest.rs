@@ -0,0 +1,355 @@
+use super::{Dump, DumpStore, MemoryBudget, OversizePolicy, Parser, ScanOptions, Shard};
+use rayon::prelude::*;
+
+/// A dump file listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestDump {
+    pub path: std::path::PathBuf,
+    /// Size of the (compressed) file in bytes.
+    pub size: u64,
+    /// SHA-1 checksum of the file, as lowercase hexadecimal.
+    pub sha1: String,
+    pub page_id_range: Option<(i64, i64)>,
+}
+
+impl ManifestDump {
+    fn new(dump: &Dump) -> std::io::Result<ManifestDump> {
+        Ok(ManifestDump {
+            path: dump.path().clone(),
+            size: dump.file_size()?,
+            sha1: super::verify::checksums(dump.path())?.1,
+            page_id_range: dump.page_id_range(),
+        })
+    }
+
+    /// Return a description of how the file on disk differs from this entry (if it does).
+    fn check(self: &ManifestDump) -> std::io::Result<Option<String>> {
+        let size = std::fs::metadata(&self.path)?.len();
+        if size != self.size {
+            return Ok(Some(format!(
+                "{}: size is {} (expected {})",
+                self.path.display(),
+                size,
+                self.size
+            )));
+        }
+        let sha1 = super::verify::checksums(&self.path)?.1;
+        if sha1 != self.sha1 {
+            return Ok(Some(format!(
+                "{}: SHA-1 is {} (expected {})",
+                self.path.display(),
+                sha1,
+                self.sha1
+            )));
+        }
+        Ok(None)
+    }
+}
+
+/// The [`ScanOptions`] which affect which pages and revisions are read, as recorded in a
+/// [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestOptions {
+    /// `"xml"` or `"fast"`.
+    pub parser: String,
+    /// The per-page limit of the [`MemoryBudget`] (if any).
+    pub memory_limit: Option<usize>,
+    /// `"spill"`, `"skip"` or `"truncate"`, if there is a [`MemoryBudget`].
+    pub oversize_policy: Option<String>,
+    /// The [`Shard`] pages must belong to (if any), as `k/N`.
+    pub page_shard: Option<String>,
+}
+
+impl ManifestOptions {
+    pub(crate) fn new(options: &ScanOptions) -> ManifestOptions {
+        ManifestOptions {
+            parser: match options.parser() {
+                Parser::Xml => "xml",
+                Parser::Fast => "fast",
+            }
+            .to_string(),
+            memory_limit: options.memory_budget().map(MemoryBudget::page_limit),
+            oversize_policy: options.memory_budget().map(|budget| {
+                match budget.policy() {
+                    OversizePolicy::Spill(_) => "spill",
+                    OversizePolicy::Skip => "skip",
+                    OversizePolicy::Truncate => "truncate",
+                }
+                .to_string()
+            }),
+            page_shard: options.page_shard().map(|shard| shard.to_string()),
+        }
+    }
+
+    /// Return the [`ScanOptions`] described. Bodies of oversized pages are spilled to the
+    /// system's temporary directory.
+    pub(crate) fn scan_options(self: &ManifestOptions) -> std::io::Result<ScanOptions> {
+        let invalid = |what: &str, value: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid {} in manifest: {:?}", what, value),
+            )
+        };
+        let mut options = ScanOptions::new().with_parser(match self.parser.as_str() {
+            "xml" => Parser::Xml,
+            "fast" => Parser::Fast,
+            parser => return Err(invalid("parser", parser)),
+        });
+        if let (Some(limit), Some(policy)) = (self.memory_limit, &self.oversize_policy) {
+            let policy = match policy.as_str() {
+                "spill" => OversizePolicy::Spill(std::env::temp_dir()),
+                "skip" => OversizePolicy::Skip,
+                "truncate" => OversizePolicy::Truncate,
+                policy => return Err(invalid("oversize policy", policy)),
+            };
+            options = options.with_memory_budget(MemoryBudget::per_page(limit, policy));
+        }
+        if let Some(shard) = &self.page_shard {
+            let shard: Shard = shard.parse().map_err(|_| invalid("page shard", shard))?;
+            options = options.with_page_shard(shard);
+        }
+        Ok(options)
+    }
+}
+
+/// Counts of the pages and revisions read, as recorded in a [`Manifest`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestCounts {
+    /// Pages read with the [`ScanOptions`].
+    pub pages: u64,
+    /// Revisions read with the [`ScanOptions`].
+    pub revisions: u64,
+    /// Pages skipped entirely by the [`MemoryBudget`].
+    pub skipped_pages: usize,
+    /// Pages of which only some revisions were kept by the [`MemoryBudget`].
+    pub truncated_pages: usize,
+    /// Revisions not kept by the [`MemoryBudget`].
+    pub skipped_revisions: usize,
+}
+
+impl ManifestCounts {
+    fn new(options: &ScanOptions) -> ManifestCounts {
+        let mut counts = ManifestCounts {
+            pages: options.read_counts().pages(),
+            revisions: options.read_counts().revisions(),
+            ..ManifestCounts::default()
+        };
+        for page in options
+            .memory_budget()
+            .map(MemoryBudget::oversized_pages)
+            .unwrap_or_default()
+        {
+            if page.kept_revision_count == 0 {
+                counts.skipped_pages += 1;
+            } else if page.kept_revision_count < page.revision_count {
+                counts.truncated_pages += 1;
+            }
+            counts.skipped_revisions += page.revision_count - page.kept_revision_count;
+        }
+        counts
+    }
+}
+
+/// A record of exactly which inputs (and options) an analysis of a [`DumpStore`] used, so that
+/// results can be published with it and the analysis repeated. See [`DumpStore::manifest`] and
+/// [`DumpStore::from_manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// Version of this crate which wrote the manifest.
+    pub crate_version: String,
+    /// When the manifest was written.
+    pub created: chrono::DateTime<chrono::Utc>,
+    pub dump_dir: std::path::PathBuf,
+    pub dumps: Vec<ManifestDump>,
+    /// Incremental dumps layered over the dumps (see [`DumpStore::with_increments`]).
+    pub increments: Vec<ManifestDump>,
+    pub options: ManifestOptions,
+    pub counts: ManifestCounts,
+}
+
+impl Manifest {
+    /// Read a manifest from a JSON file.
+    pub fn load(path: &std::path::Path) -> std::io::Result<Manifest> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Write the manifest to a JSON file.
+    pub fn save(self: &Manifest, path: &std::path::Path) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        std::io::Write::write_all(&mut file, b"\n")?;
+        std::io::Write::flush(&mut file)
+    }
+}
+
+impl DumpStore {
+    /// Return a [`Manifest`] of the dumps and options of the [`DumpStore`], with counts of the pages
+    /// and revisions read so far with its [`ScanOptions`]. Every dump is read in full to compute
+    /// its checksum.
+    pub fn manifest(self: &DumpStore) -> std::io::Result<Manifest> {
+        let describe = |dumps: &[Dump]| -> std::io::Result<Vec<ManifestDump>> {
+            dumps.par_iter().map(ManifestDump::new).collect()
+        };
+        Ok(Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: chrono::Utc::now(),
+            dump_dir: self.dump_dir().clone(),
+            dumps: describe(self.dumps())?,
+            increments: describe(self.increments())?,
+            options: ManifestOptions::new(self.options()),
+            counts: ManifestCounts::new(self.options()),
+        })
+    }
+
+    /// Re-open the [`DumpStore`] described by the manifest at `path`, with the same options (except
+    /// that bodies of oversized pages are spilled to the system's temporary directory).
+    /// Every dump is read in full to check that it is unchanged, and an error of kind
+    /// [`std::io::ErrorKind::InvalidData`] is returned if any dump differs. The store is returned
+    /// along with a warning (if any) that the manifest was written by another version of this
+    /// crate, which may read the dumps differently.
+    pub fn from_manifest(path: &std::path::Path) -> std::io::Result<(DumpStore, Option<String>)> {
+        let manifest = Manifest::load(path)?;
+        let warning = (manifest.crate_version != env!("CARGO_PKG_VERSION")).then(|| {
+            format!(
+                "manifest was written by version {} (this is version {})",
+                manifest.crate_version,
+                env!("CARGO_PKG_VERSION")
+            )
+        });
+
+        let entries: Vec<&ManifestDump> =
+            manifest.dumps.iter().chain(&manifest.increments).collect();
+        let problems: Vec<String> = entries
+            .par_iter()
+            .map(|entry| entry.check())
+            .collect::<std::io::Result<Vec<Option<String>>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        if !problems.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Dumps do not match the manifest:\n{}", problems.join("\n")),
+            ));
+        }
+
+        let open = |entries: &[ManifestDump]| -> std::io::Result<Vec<Dump>> {
+            entries
+                .iter()
+                .map(|entry| {
+                    Dump::new(entry.path.clone()).ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("Not a dump: {}", entry.path.display()),
+                        )
+                    })
+                })
+                .collect()
+        };
+        let store = DumpStore::from_dumps(manifest.dump_dir.clone(), open(&manifest.dumps)?)
+            .with_options(manifest.options.scan_options()?);
+        let increments = open(&manifest.increments)?;
+        let store = if increments.is_empty() {
+            store
+        } else {
+            store.with_increments(increments)?
+        };
+        Ok((store, warning))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+
+    const TIME: &str = "2020-01-01T00:00:00Z";
+    const DUMP: &str = "enwiki-20230101-pages-meta-history1.xml-p1p10.7z";
+
+    fn store(name: &str) -> DumpStore {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-manifest-{}-{}",
+            name,
+            std::process::id()
+        ));
+        crate::dump::test_dump(
+            &dir,
+            DUMP,
+            &[
+                test_page(1, "A", &[(10, TIME, "a"), (11, TIME, "aa")]),
+                test_page(2, "B", &[(20, TIME, "b")]),
+            ],
+        );
+        DumpStore::new(dir)
+            .unwrap()
+            .with_options(ScanOptions::new().with_parser(Parser::Fast))
+    }
+
+    #[test]
+    fn manifests_describe_the_dumps_options_and_pages_read() {
+        let store = store("describe");
+        assert_eq!(store.pages().count(), 2);
+        let manifest = store.manifest().unwrap();
+        assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest.dumps.len(), 1);
+        let dump = &manifest.dumps[0];
+        assert_eq!(dump.path, store.dump_dir().join(DUMP));
+        assert_eq!(dump.size, std::fs::metadata(&dump.path).unwrap().len());
+        assert_eq!(
+            dump.sha1,
+            crate::dump::verify::checksums(&dump.path).unwrap().1
+        );
+        assert_eq!(dump.page_id_range, Some((1, 10)));
+        assert_eq!(manifest.options.parser, "fast");
+        assert_eq!(manifest.options.memory_limit, None);
+        // Counted without a Progress
+        assert_eq!(
+            manifest.counts,
+            ManifestCounts {
+                pages: 2,
+                revisions: 3,
+                ..ManifestCounts::default()
+            }
+        );
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+
+    #[test]
+    fn stores_are_reopened_from_manifests() {
+        let store = store("reopen");
+        let path = store.dump_dir().join("manifest.json");
+        let mut manifest = store.manifest().unwrap();
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path).unwrap(), manifest);
+
+        let (reopened, warning) = DumpStore::from_manifest(&path).unwrap();
+        assert_eq!(warning, None);
+        assert_eq!(reopened.dumps().len(), 1);
+        assert_eq!(reopened.options().parser(), Parser::Fast);
+        assert_eq!(reopened.pages().count(), 2);
+
+        // Another version is reported to the caller
+        manifest.crate_version = "0.0.0".to_string();
+        manifest.save(&path).unwrap();
+        let (_, warning) = DumpStore::from_manifest(&path).unwrap();
+        assert_eq!(
+            warning.unwrap(),
+            format!(
+                "manifest was written by version 0.0.0 (this is version {})",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        // A changed dump is refused
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(store.dump_dir().join(DUMP))
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"\n").unwrap();
+        let error = DumpStore::from_manifest(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("size is"));
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+}
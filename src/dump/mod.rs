@@ -1,35 +1,109 @@
+use page::PageSource;
 use rayon::prelude::*;
 
+pub mod cancel;
+pub mod catalog;
+pub mod checkpoint;
+pub mod compare;
+pub mod coverage;
+pub mod grep;
+pub mod history;
+pub mod increment;
+pub mod index;
+pub mod kind;
+pub mod lookup;
+pub mod manifest;
+pub mod options;
 pub mod page;
-pub use page::{Page, PageIterator, Revision};
+pub mod progress;
+pub mod schedule;
+pub mod shard;
+pub mod siteinfo;
+pub mod verify;
+pub mod worker;
+pub use cancel::CancellationToken;
+pub use catalog::DumpCatalog;
+pub use checkpoint::Checkpoint;
+pub use compare::{ComparisonSummary, DumpComparison, PageChange};
+pub use coverage::{CoverageIssue, CoverageReport};
+pub use grep::{Grep, GrepRevision, GrepSpan, PageMatches};
+pub use history::{PageMove, TitleHistories, TitleHistory, TitleRecord};
+pub use index::PageIndex;
+pub use kind::DumpKind;
+pub use lookup::PageLookup;
+pub use manifest::{Manifest, ManifestCounts, ManifestDump, ManifestOptions};
+pub use options::{Parser, ScanOptions};
+pub use page::{
+    CompactPage, MemoryBudget, OversizePolicy, OversizedPage, Page, PageIterator, Revision,
+};
+pub use progress::{Progress, ProgressReport};
+pub use schedule::{Schedule, ScheduledPages};
+pub use shard::{Shard, ShardMode};
+pub use siteinfo::SiteInfo;
+pub use verify::{Verification, VerificationPolicy, VerificationReport};
+pub use worker::{run_worker_if_requested, SkippedPage, WorkerPool, WorkerReport};
 
-// The regex for file paths that can be interpreted as dumps
-const DUMP_REGEX: &str = r"[^\.]*\.xml-p([^p]+)p([^\.]+)\.7z";
+// The regex for file names that can be interpreted as dumps, following the Wikimedia naming scheme
+// `{dbname}-{YYYYMMDD}-{kind}{part}.{xml|txt}[-p{first}p{last}].{7z|bz2|gz}`
+const DUMP_REGEX: &str =
+    r"^([a-z0-9_]+)-(\d{8})-([a-z-]+?)(\d+)?\.(?:xml|txt)(?:-p(\d+)p(\d+))?\.(?:7z|bz2|gz)$";
 
-#[derive(Debug)]
+/// A single dump file.
+#[derive(Debug, Clone)]
 pub struct Dump {
     path: std::path::PathBuf,
-    page_id_range: (i64, i64),
+    dbname: String,
+    date: chrono::NaiveDate,
+    kind: DumpKind,
+    part: Option<u32>,
+    page_id_range: Option<(i64, i64)>,
 }
 
 impl Dump {
-    /// Create a [`Dump`] from a path to a `.7z` file.
+    /// Create a [`Dump`] from a path to a dump file (compressed with `7z`, `bzip2` or `gzip`), whose
+    /// name follows the Wikimedia naming scheme, like `enwiki-20230101-pages-meta-history1.xml-p1p844.7z`.
     pub fn new(path: std::path::PathBuf) -> Option<Dump> {
-        let file_name_str = path.file_name().and_then(|name| name.to_str());
-        if let Some(file_name) = file_name_str {
-            let re = regex::Regex::new(DUMP_REGEX).unwrap();
-            if let Some(caps) = re.captures(file_name) {
-                let page_id_range = (caps[1].parse().ok()?, caps[2].parse().ok()?);
-                Some(Dump {
-                    path,
-                    page_id_range,
-                })
-            } else {
-                None
+        let file_name = path.file_name()?.to_str()?;
+        let re = regex::Regex::new(DUMP_REGEX).unwrap();
+        let caps = re.captures(file_name)?;
+
+        let page_id_range = match (caps.get(5), caps.get(6)) {
+            (Some(first), Some(last)) => {
+                Some((first.as_str().parse().ok()?, last.as_str().parse().ok()?))
             }
-        } else {
-            None
-        }
+            _ => None,
+        };
+        Some(Dump {
+            dbname: caps[1].to_string(),
+            date: chrono::NaiveDate::parse_from_str(&caps[2], "%Y%m%d").ok()?,
+            kind: caps[3].parse().ok()?,
+            part: match caps.get(4) {
+                Some(part) => Some(part.as_str().parse().ok()?),
+                None => None,
+            },
+            page_id_range,
+            path,
+        })
+    }
+
+    /// Return the name of the wiki's database, like `enwiki`.
+    pub fn dbname(self: &Dump) -> &str {
+        &self.dbname
+    }
+
+    /// Return the date of the dump run which produced the file.
+    pub fn date(self: &Dump) -> chrono::NaiveDate {
+        self.date
+    }
+
+    /// Return the kind of content in the dump.
+    pub fn kind(self: &Dump) -> DumpKind {
+        self.kind
+    }
+
+    /// Return the part number of the dump, when the content is split across several files.
+    pub fn part(self: &Dump) -> Option<u32> {
+        self.part
     }
 
     /// Return the path of the dump file.
@@ -37,35 +111,132 @@ impl Dump {
         &self.path
     }
 
+    /// Return the size of the (compressed) dump file in bytes.
+    pub fn file_size(self: &Dump) -> std::io::Result<u64> {
+        Ok(std::fs::metadata(&self.path)?.len())
+    }
+
     /// Return an iterator over the pages stored in the dump.
     pub fn pages(self: &Dump) -> impl Iterator<Item = Page> {
-        PageIterator::from_path(&self.path)
+        self.pages_with(&ScanOptions::default())
+    }
+
+    /// Return an iterator over the pages stored in the dump, read according to `options`.
+    ///
+    /// # Panics
+    /// Panics if reading the dump fails, such as when the bodies of a page cannot be spilled to
+    /// disk by a [`MemoryBudget`]. Use [`Dump::try_pages_with`] to handle such errors.
+    pub fn pages_with(self: &Dump, options: &ScanOptions) -> impl Iterator<Item = Page> {
+        let path = self.path.clone();
+        self.try_pages_with(options).map(move |page| {
+            page.unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e))
+        })
+    }
+
+    /// Return an iterator over the pages stored in the dump, read according to `options`. If
+    /// reading the dump fails, the error is the last item.
+    pub fn try_pages_with(
+        self: &Dump,
+        options: &ScanOptions,
+    ) -> impl Iterator<Item = std::io::Result<Page>> {
+        self.try_pages_at(options, 0)
+            .map(|page| page.map(|(page, _)| page))
     }
 
-    /// Return the range of page IDs covered by this dump.
-    pub fn page_id_range(self: &Dump) -> (i64, i64) {
+    /// Like [`Dump::try_pages_with`], but starting `offset` bytes into the decompressed dump (see
+    /// [`PageIterator::from_path_at`]). Each page is returned with the offset from which the pages
+    /// after it can be read. The offsets are only meaningful when `options` has no incremental
+    /// dumps, since pages from the dump may be held back while new pages are yielded before them.
+    pub(crate) fn try_pages_at(
+        self: &Dump,
+        options: &ScanOptions,
+        offset: u64,
+    ) -> impl Iterator<Item = std::io::Result<(Page, u64)>> {
+        let position = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(offset));
+        let pages = PageIterator::from_path_at(&self.path, offset, options.clone());
+        let pages = page::PositionedPages::new(pages, offset, position.clone());
+        let pages = increment::MergedPages::new(pages, self, options);
+        let mut pages = progress::TrackedPages::new(pages, self, options);
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+            match pages.next() {
+                Some(page) => Some(Ok((
+                    page,
+                    position.load(std::sync::atomic::Ordering::Relaxed),
+                ))),
+                None => {
+                    failed = true;
+                    pages.take_error().map(Err)
+                }
+            }
+        })
+    }
+
+    /// Return the range of page IDs covered by this dump, if it is given in the file name.
+    pub fn page_id_range(self: &Dump) -> Option<(i64, i64)> {
         self.page_id_range
     }
 
-    /// Returns [`true`] when the dump purports to claim the specified page ID.
+    /// Returns [`true`] when the dump purports to claim the specified page ID (which is always
+    /// the case when the file name does not give a range of page IDs).
     /// However, the dump will need to be parsed to confirm that the page is actually present.
     pub fn contains_page_id(self: &Dump, page_id: i64) -> bool {
-        let (min_id, max_id) = self.page_id_range();
-        min_id <= page_id && page_id <= max_id
+        match self.page_id_range() {
+            Some((min_id, max_id)) => min_id <= page_id && page_id <= max_id,
+            None => true,
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DumpStore {
     dump_dir: std::path::PathBuf,
     dumps: Vec<Dump>,
+    options: ScanOptions,
+    index: Option<std::sync::Arc<PageIndex>>,
 }
 
 impl DumpStore {
-    /// Create a [`DumpStore`] from a path to a directory containing `.7z` files.
+    /// Create a [`DumpStore`] from a path to a directory containing dump files. Every file whose
+    /// name follows the Wikimedia naming scheme is included, whatever its [`DumpKind`], although
+    /// pages are only read from the [`DumpStore::page_dumps`]. A directory containing several kinds
+    /// of page dump should be narrowed with [`DumpStore::of_kind`]. Compressed files with other
+    /// names are skipped, and can be listed with [`DumpStore::unrecognized_files`].
     pub fn new(dump_dir: std::path::PathBuf) -> std::io::Result<DumpStore> {
         let dumps = directory_dumps(&dump_dir)?;
-        Ok(DumpStore { dump_dir, dumps })
+        Ok(DumpStore {
+            dump_dir,
+            dumps,
+            options: ScanOptions::default(),
+            index: None,
+        })
+    }
+
+    /// Create a [`DumpStore`] from a list of dump files. `dump_dir` is only used to describe the
+    /// store, so the dumps do not need to be in it.
+    pub fn from_dumps(dump_dir: std::path::PathBuf, dumps: Vec<Dump>) -> DumpStore {
+        DumpStore {
+            dump_dir,
+            dumps,
+            options: ScanOptions::default(),
+            index: None,
+        }
+    }
+
+    /// Use `options` when reading pages from the dumps in the [`DumpStore`], replacing the current
+    /// options. As the incremental dumps (see [`DumpStore::with_increments`]) are part of the
+    /// options, they are only kept if `options` is derived from [`DumpStore::options`].
+    pub fn with_options(mut self: DumpStore, options: ScanOptions) -> DumpStore {
+        self.options = options;
+        self
+    }
+
+    /// Return the options used when reading pages from the dumps.
+    pub fn options(self: &DumpStore) -> &ScanOptions {
+        &self.options
     }
 
     /// Return the path of the directory containing the dump files [`DumpStore`].
@@ -78,43 +249,284 @@ impl DumpStore {
         &self.dumps
     }
 
+    /// Return the dumps in the [`DumpStore`] which consist of pages (see
+    /// [`DumpKind::contains_pages`]). Only these are read by [`DumpStore::pages`] and the other
+    /// methods which read pages, so that other files in the directory (like logging dumps and
+    /// multistream indexes) are skipped rather than parsed as empty page dumps.
+    pub fn page_dumps(self: &DumpStore) -> Vec<Dump> {
+        self.dumps
+            .iter()
+            .filter(|d| d.kind().contains_pages())
+            .cloned()
+            .collect()
+    }
+
+    /// Return the compressed files (ending in `.7z`, `.bz2` or `.gz`) in the dump directory whose
+    /// names do not follow the Wikimedia naming scheme, and so were skipped by [`DumpStore::new`].
+    /// This includes files renamed from their original names, which earlier versions accepted as
+    /// long as they ended in `.xml-p{first}p{last}.7z`.
+    pub fn unrecognized_files(self: &DumpStore) -> std::io::Result<Vec<std::path::PathBuf>> {
+        let mut files: Vec<std::path::PathBuf> = std::fs::read_dir(&self.dump_dir)?
+            .filter_map(|res| res.ok())
+            .map(|e| e.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "7z" || ext == "bz2" || ext == "gz")
+            })
+            .filter(|path| path.is_file() && Dump::new(path.clone()).is_none())
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Return the distinct kinds of dump in the [`DumpStore`].
+    pub fn kinds(self: &DumpStore) -> Vec<DumpKind> {
+        let kinds: std::collections::BTreeSet<DumpKind> =
+            self.dumps.iter().map(Dump::kind).collect();
+        kinds.into_iter().collect()
+    }
+
+    /// Return a [`DumpStore`] containing only the dumps of the specified kind.
+    pub fn of_kind(self: &DumpStore, kind: DumpKind) -> DumpStore {
+        DumpStore {
+            dumps: self
+                .dumps
+                .iter()
+                .filter(|d| d.kind() == kind)
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Return a [`DumpStore`] containing only the dumps which may hold pages with IDs from `first`
+    /// to `last` (inclusive): those whose page ID range overlaps it, and those without a range.
+    /// All dumps are kept if the store has incremental dumps, since pages new in an increment may
+    /// be yielded by any of them.
+    pub fn in_page_id_range(self: &DumpStore, first: i64, last: i64) -> DumpStore {
+        if !self.increments().is_empty() {
+            return self.clone();
+        }
+        DumpStore {
+            dumps: self
+                .dumps
+                .iter()
+                .filter(|d| {
+                    d.page_id_range()
+                        .is_none_or(|(start, end)| start <= last && first <= end)
+                })
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
     /// Return an iterator over the all the pages stored in all the dumps.
     pub fn pages(self: &DumpStore) -> impl Iterator<Item = Page> + '_ {
-        self.dumps().iter().flat_map(Dump::pages)
+        let dumps = self.page_dumps();
+        if let Some(progress) = self.options.progress() {
+            progress.expect_dumps(&dumps);
+        }
+        dumps.into_iter().flat_map(|d| d.pages_with(&self.options))
     }
 
-    /// Return a parallel iterator over the all the pages stored in all the dumps.
+    /// Return a parallel iterator over the all the pages stored in all the dumps. The dumps are
+    /// read according to the default [`Schedule`].
     pub fn par_pages(self: &DumpStore) -> impl rayon::iter::ParallelIterator<Item = Page> + '_ {
-        self.dumps().par_iter().flat_map(|d| d.pages().par_bridge())
+        self.scheduled_pages(&Schedule::default()).par_bridge()
+    }
+
+    /// Return an iterator over all the pages stored in all the dumps, which are read on background
+    /// threads according to `schedule`.
+    pub fn scheduled_pages(self: &DumpStore, schedule: &Schedule) -> ScheduledPages {
+        let dumps = self.page_dumps();
+        if let Some(progress) = self.options.progress() {
+            progress.expect_dumps(&dumps);
+        }
+        ScheduledPages::new(&dumps, &self.options, schedule)
     }
 
     /// Return a collection of pages with the specified page IDs. There is no guarantee that a requested
-    /// page will be included, and the order of the returned vector is independent of the input vector.
-    pub fn pages_by_id(self: &DumpStore, page_ids: Vec<i64>) -> Vec<Page> {
-        self.dumps()
-            .iter()
-            .filter(|d| page_ids.iter().any(|id| d.contains_page_id(*id)))
-            .flat_map(|d| d.pages())
-            .filter(|p| page_ids.contains(&p.id()))
-            .collect()
+    /// page will be included, and the order of the returned vector is independent of the input.
+    /// An error is returned if a dump cannot be read. See [`DumpStore::lookup_pages`] to receive
+    /// pages as they are found.
+    pub fn pages_by_id(
+        self: &DumpStore,
+        page_ids: impl IntoIterator<Item = i64>,
+    ) -> std::io::Result<Vec<Page>> {
+        let mut lookup = self.lookup_pages(page_ids);
+        let pages = lookup.by_ref().collect();
+        match lookup.take_error() {
+            Some(e) => Err(e),
+            None => Ok(pages),
+        }
     }
 
-    /// Return a [`Page`] with the specified page ID, if it can be found in the dumps.
-    pub fn page_by_id(self: &DumpStore, page_id: i64) -> Option<Page> {
-        self.dumps()
-            .iter()
-            .filter(|d| d.contains_page_id(page_id))
-            .flat_map(|d| d.pages())
-            .filter(|p| p.id() == page_id)
-            .next()
+    /// Return a [`Page`] with the specified page ID, if it can be found in the dumps. If the
+    /// [`DumpStore`] has an index, only the dump containing the page is read, and the pages before
+    /// it are skipped without being parsed (although they must still be decompressed). An error is
+    /// returned if a dump cannot be read.
+    pub fn page_by_id(self: &DumpStore, page_id: i64) -> std::io::Result<Option<Page>> {
+        let page = if self.index().is_some() {
+            self.indexed_page(page_id)?
+        } else {
+            let mut found = None;
+            for dump in self.page_dumps() {
+                if !dump.contains_page_id(page_id) {
+                    continue;
+                }
+                for page in dump.try_pages_with(&self.options) {
+                    let page = page?;
+                    if page.id() == page_id {
+                        found = Some(page);
+                        break;
+                    }
+                }
+                if found.is_some() {
+                    break;
+                }
+            }
+            found
+        };
+
+        // Pages created since the dumps may only be in the incremental dumps
+        Ok(page.or_else(|| self.options.increments()?.page(page_id)))
     }
 }
 
 fn directory_dumps(dump_dir: &std::path::Path) -> std::io::Result<Vec<Dump>> {
     Ok(std::fs::read_dir(dump_dir)?
-        .into_iter()
         .filter_map(|res| res.ok())
         .map(|e| e.path())
         .flat_map(Dump::new)
         .collect())
 }
+
+/// Write a dump file named `file_name` to `dir` for tests, containing `pages` as uncompressed XML.
+///
+/// Dumps are read by running `7z`, which may not be installed, so the first call puts a `7z`
+/// script which just copies the file at the start of `$PATH`.
+#[cfg(test)]
+pub(crate) fn test_dump(dir: &std::path::Path, file_name: &str, pages: &[Page]) -> Dump {
+    static FAKE_7Z: std::sync::Once = std::sync::Once::new();
+    FAKE_7Z.call_once(|| {
+        use std::os::unix::fs::PermissionsExt;
+        let bin =
+            std::env::temp_dir().join(format!("wiki-dump-analyzer-fake-7z-{}", std::process::id()));
+        std::fs::create_dir_all(&bin).unwrap();
+        // Called as `7z x -so <path>`
+        std::fs::write(
+            bin.join("7z"),
+            "#!/bin/sh\nfor last; do :; done\nexec cat \"$last\" 2>/dev/null\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(bin.join("7z"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let paths = std::iter::once(bin).chain(std::env::split_paths(&path));
+        std::env::set_var("PATH", std::env::join_paths(paths).unwrap());
+    });
+
+    let mut xml = String::from("<mediawiki>\n");
+    for page in pages {
+        xml += &format!(
+            "  <page>\n    <title>{}</title>\n    <ns>{}</ns>\n    <id>{}</id>\n",
+            page.title(),
+            page.namespace(),
+            page.id()
+        );
+        for rev in page.revisions() {
+            xml += &format!(
+                "    <revision>\n      <id>{}</id>\n      <timestamp>{}</timestamp>\n      \
+                 <contributor><username>{}</username></contributor>\n      \
+                 <model>{}</model>\n      <format>{}</format>\n      <text>{}</text>\n    \
+                 </revision>\n",
+                rev.id(),
+                rev.timestamp().format("%Y-%m-%dT%H:%M:%SZ"),
+                rev.contributor_username().unwrap(),
+                rev.model(),
+                rev.format(),
+                rev.body()
+            );
+        }
+        xml += "  </page>\n";
+    }
+    xml += "</mediawiki>\n";
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join(file_name), xml).unwrap();
+    Dump::new(dir.join(file_name)).expect("Bad dump name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(file_name: &str) -> Option<Dump> {
+        Dump::new(std::path::PathBuf::from("/dumps").join(file_name))
+    }
+
+    #[test]
+    fn dump_names_are_parsed() {
+        let d = dump("enwiki-20230101-pages-meta-history12.xml-p1p844.7z").unwrap();
+        assert_eq!(d.dbname(), "enwiki");
+        assert_eq!(
+            d.date(),
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()
+        );
+        assert_eq!(d.kind(), DumpKind::PagesMetaHistory);
+        assert_eq!(d.part(), Some(12));
+        assert_eq!(d.page_id_range(), Some((1, 844)));
+        assert!(d.contains_page_id(844) && !d.contains_page_id(845));
+
+        let d = dump("simplewiki-20240301-pages-articles-multistream-index.txt.bz2").unwrap();
+        assert_eq!(d.kind(), DumpKind::MultistreamIndex);
+        assert_eq!((d.part(), d.page_id_range()), (None, None));
+        assert!(d.contains_page_id(123));
+
+        let d = dump("zh_min_nanwiki-20240301-pages-logging.xml.gz").unwrap();
+        assert_eq!(d.dbname(), "zh_min_nanwiki");
+        assert_eq!(d.kind(), DumpKind::Logging);
+    }
+
+    #[test]
+    fn other_names_are_rejected() {
+        for name in [
+            // Renamed files, which earlier versions accepted
+            "history1.xml-p1p844.7z",
+            "enwiki-pages-meta-history1.xml-p1p844.7z",
+            "enwiki-20230101-pages-meta-history1.xml-p1p844.7z.part",
+            "enwiki-20231301-pages-meta-history1.xml-p1p844.7z",
+            "enwiki-20230101-pages-unknown.xml.7z",
+            "enwiki-20230101-pages-articles.xml",
+            "enwiki-20230101-md5sums.txt",
+        ] {
+            assert!(dump(name).is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_page_dumps_are_read() {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-store-files-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "enwiki-20230101-pages-articles1.xml-p1p10.bz2",
+            "enwiki-20230101-pages-logging.xml.gz",
+            "enwiki-20230101-md5sums.txt",
+            "history1.xml-p1p844.7z",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let store = DumpStore::new(dir.clone()).unwrap();
+        assert_eq!(store.kinds(), [DumpKind::PagesArticles, DumpKind::Logging]);
+        let page_dumps: Vec<DumpKind> = store.page_dumps().iter().map(Dump::kind).collect();
+        assert_eq!(page_dumps, [DumpKind::PagesArticles]);
+        assert_eq!(
+            store.unrecognized_files().unwrap(),
+            [dir.join("history1.xml-p1p844.7z")]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
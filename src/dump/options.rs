@@ -0,0 +1,124 @@
+use super::cancel::CancellationToken;
+use super::increment::Increments;
+use super::page::budget::MemoryBudget;
+use super::progress::{Progress, ReadCounts};
+use super::shard::Shard;
+
+/// The parser used to read pages from a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parser {
+    /// A general-purpose XML parser.
+    #[default]
+    Xml,
+    /// A scanner specialised for the MediaWiki export format, which is considerably faster than
+    /// [`Parser::Xml`] but does not check that the dump is well-formed XML.
+    Fast,
+}
+
+/// Options controlling how pages are read from dumps.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    parser: Parser,
+    memory_budget: Option<MemoryBudget>,
+    progress: Option<Progress>,
+    cancellation: Option<CancellationToken>,
+    page_shard: Option<Shard>,
+    increments: Option<std::sync::Arc<Increments>>,
+    read_counts: ReadCounts,
+}
+
+impl ScanOptions {
+    /// Create a [`ScanOptions`] with the default settings.
+    pub fn new() -> ScanOptions {
+        ScanOptions::default()
+    }
+
+    /// Use `parser` to read pages.
+    pub fn with_parser(mut self: ScanOptions, parser: Parser) -> ScanOptions {
+        self.parser = parser;
+        self
+    }
+
+    /// The parser used to read pages.
+    pub fn parser(self: &ScanOptions) -> Parser {
+        self.parser
+    }
+
+    /// Limit the memory used by the revision bodies of each page.
+    pub fn with_memory_budget(mut self: ScanOptions, budget: MemoryBudget) -> ScanOptions {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// The [`MemoryBudget`] applied to each page (if any).
+    pub fn memory_budget(self: &ScanOptions) -> Option<&MemoryBudget> {
+        self.memory_budget.as_ref()
+    }
+
+    /// Record the progress of reading pages in `progress`.
+    pub fn with_progress(mut self: ScanOptions, progress: Progress) -> ScanOptions {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// The [`Progress`] updated as pages are read (if any).
+    pub fn progress(self: &ScanOptions) -> Option<&Progress> {
+        self.progress.as_ref()
+    }
+
+    /// Stop recording progress, for example when it would be reported to a terminal which is in
+    /// use for something else.
+    pub fn without_progress(mut self: ScanOptions) -> ScanOptions {
+        self.progress = None;
+        self
+    }
+
+    /// Stop reading pages once `token` is cancelled.
+    pub fn with_cancellation(mut self: ScanOptions, token: CancellationToken) -> ScanOptions {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// The [`CancellationToken`] which stops reading pages (if any).
+    pub fn cancellation(self: &ScanOptions) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Returns [`true`] if reading pages has been cancelled.
+    pub fn is_cancelled(self: &ScanOptions) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Only keep pages whose IDs belong to `shard` (see [`ShardMode::PageIds`](super::ShardMode::PageIds)).
+    pub fn with_page_shard(mut self: ScanOptions, shard: Shard) -> ScanOptions {
+        self.page_shard = Some(shard);
+        self
+    }
+
+    /// The [`Shard`] which pages must belong to in order to be kept (if any).
+    pub fn page_shard(self: &ScanOptions) -> Option<Shard> {
+        self.page_shard
+    }
+
+    /// Merge the pages of incremental dumps into the pages read (see
+    /// [`DumpStore::with_increments`](super::DumpStore::with_increments)).
+    pub(crate) fn with_increments(
+        mut self: ScanOptions,
+        increments: Option<std::sync::Arc<Increments>>,
+    ) -> ScanOptions {
+        self.increments = increments;
+        self
+    }
+
+    /// The incremental dumps merged into the pages read (if any).
+    pub(crate) fn increments(self: &ScanOptions) -> Option<&std::sync::Arc<Increments>> {
+        self.increments.as_ref()
+    }
+
+    /// Counts of the pages and revisions read with these options (and their clones).
+    pub(crate) fn read_counts(self: &ScanOptions) -> &ReadCounts {
+        &self.read_counts
+    }
+}
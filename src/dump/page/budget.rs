@@ -0,0 +1,299 @@
+use super::Revision;
+use std::io::{Read, Seek, Write};
+
+/// What to do with a page whose revision bodies exceed the limit of a [`MemoryBudget`].
+#[derive(Debug, Clone)]
+pub enum OversizePolicy {
+    /// Write the revision bodies to a temporary file in the given directory, and read them back
+    /// each time [`Revision::read_body`] is called. The file is removed once the page is dropped.
+    Spill(std::path::PathBuf),
+    /// Skip the page entirely.
+    Skip,
+    /// Keep only the revisions (in the order they appear in the dump) which fit within the limit.
+    Truncate,
+}
+
+/// A page which exceeded the limit of a [`MemoryBudget`].
+#[derive(Debug, Clone)]
+pub struct OversizedPage {
+    /// Page ID.
+    pub id: i64,
+    /// Page title.
+    pub title: String,
+    /// Total size of all revision bodies of the page (in bytes).
+    pub body_size: usize,
+    /// Number of revisions of the page in the dump.
+    pub revision_count: usize,
+    /// Number of revisions which were kept (this is zero for skipped pages).
+    pub kept_revision_count: usize,
+}
+
+/// A limit on the memory used by the revision bodies of a single page, and what to do with pages
+/// which exceed it.
+///
+/// Clones of a [`MemoryBudget`] share the same record of oversized pages, so a budget can be given
+/// to a [`crate::DumpStore`] and later inspected with [`MemoryBudget::oversized_pages`].
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    page_limit: usize,
+    policy: OversizePolicy,
+    oversized: std::sync::Arc<std::sync::Mutex<Vec<OversizedPage>>>,
+}
+
+impl MemoryBudget {
+    /// Create a [`MemoryBudget`] which applies `policy` to pages whose revision bodies total more
+    /// than `page_limit` bytes.
+    pub fn per_page(page_limit: usize, policy: OversizePolicy) -> MemoryBudget {
+        MemoryBudget {
+            page_limit,
+            policy,
+            oversized: Default::default(),
+        }
+    }
+
+    /// The maximum number of bytes of revision bodies kept in memory for a single page.
+    pub fn page_limit(self: &MemoryBudget) -> usize {
+        self.page_limit
+    }
+
+    /// What happens to pages which exceed the limit.
+    pub fn policy(self: &MemoryBudget) -> &OversizePolicy {
+        &self.policy
+    }
+
+    /// Return the pages which have exceeded the limit so far.
+    pub fn oversized_pages(self: &MemoryBudget) -> Vec<OversizedPage> {
+        self.oversized.lock().unwrap().clone()
+    }
+
+    fn record(self: &MemoryBudget, page: OversizedPage) {
+        self.oversized.lock().unwrap().push(page);
+    }
+}
+
+/// A temporary file containing revision bodies, which is removed when dropped.
+pub(crate) struct SpillFile {
+    path: std::path::PathBuf,
+    file: std::sync::Mutex<std::fs::File>,
+}
+
+impl SpillFile {
+    fn create(dir: &std::path::Path) -> std::io::Result<SpillFile> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = dir.join(format!(
+            "wiki-dump-analyzer-{}-{}.spill",
+            std::process::id(),
+            n
+        ));
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(SpillFile {
+            path,
+            file: std::sync::Mutex::new(file),
+        })
+    }
+
+    /// Append `body` to the file, returning its offset.
+    fn append(self: &SpillFile, body: &str) -> std::io::Result<u64> {
+        let mut file = self.file.lock().unwrap();
+        let offset = file.seek(std::io::SeekFrom::End(0))?;
+        file.write_all(body.as_bytes())?;
+        Ok(offset)
+    }
+
+    /// Read a body of `len` bytes previously written at `offset`.
+    pub(crate) fn read(self: &SpillFile, offset: u64, len: usize) -> std::io::Result<String> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(std::io::SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len];
+        file.read_exact(&mut bytes)?;
+        String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Collects the revisions of a page as it is parsed, applying a [`MemoryBudget`].
+pub(crate) struct RevisionCollector<'a> {
+    budget: Option<&'a MemoryBudget>,
+    revisions: Vec<Revision>,
+    body_size: usize,
+    revision_count: usize,
+    spill_file: Option<std::sync::Arc<SpillFile>>,
+}
+
+impl<'a> RevisionCollector<'a> {
+    pub(crate) fn new(budget: Option<&'a MemoryBudget>) -> RevisionCollector<'a> {
+        RevisionCollector {
+            budget,
+            revisions: Vec::new(),
+            body_size: 0,
+            revision_count: 0,
+            spill_file: None,
+        }
+    }
+
+    /// Add a revision of the page, which fails if its body cannot be spilled to disk.
+    pub(crate) fn push(self: &mut RevisionCollector<'a>, mut rev: Revision) -> std::io::Result<()> {
+        self.body_size += rev.body_len();
+        self.revision_count += 1;
+
+        let budget = match self.budget {
+            Some(budget) if self.body_size > budget.page_limit => budget,
+            _ => {
+                self.revisions.push(rev);
+                return Ok(());
+            }
+        };
+
+        match budget.policy() {
+            OversizePolicy::Spill(dir) => {
+                let spill_file = match &self.spill_file {
+                    Some(spill_file) => spill_file,
+                    None => {
+                        let spill_file = std::sync::Arc::new(SpillFile::create(dir)?);
+                        for previous in &mut self.revisions {
+                            previous.spill(&spill_file)?;
+                        }
+                        self.spill_file.insert(spill_file)
+                    }
+                };
+                rev.spill(spill_file)?;
+                self.revisions.push(rev);
+            }
+            OversizePolicy::Skip => self.revisions.clear(),
+            OversizePolicy::Truncate => {}
+        }
+        Ok(())
+    }
+
+    /// Return the collected revisions, or [`None`] if the page should be skipped.
+    pub(crate) fn finish(
+        self: RevisionCollector<'a>,
+        id: i64,
+        title: &str,
+    ) -> Option<Vec<Revision>> {
+        let budget = match self.budget {
+            Some(budget) if self.body_size > budget.page_limit => budget,
+            _ => return Some(self.revisions),
+        };
+
+        let skip = matches!(budget.policy(), OversizePolicy::Skip);
+        budget.record(OversizedPage {
+            id,
+            title: title.to_string(),
+            body_size: self.body_size,
+            revision_count: self.revision_count,
+            kept_revision_count: if skip { 0 } else { self.revisions.len() },
+        });
+
+        if skip {
+            None
+        } else {
+            Some(self.revisions)
+        }
+    }
+}
+
+impl Revision {
+    /// Move the body of the revision into `spill_file`.
+    fn spill(self: &mut Revision, spill_file: &std::sync::Arc<SpillFile>) -> std::io::Result<()> {
+        let body = self.take_body()?;
+        let offset = spill_file.append(&body)?;
+        self.set_spilled_body(spill_file.clone(), offset, body.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::{PageIterator, Parser, ScanOptions};
+
+    const DUMP: &str = r#"<mediawiki>
+  <page>
+    <title>Example</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>10</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <contributor><username>A</username><id>1</id></contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="10">first body</text>
+    </revision>
+    <revision>
+      <id>11</id>
+      <parentid>10</parentid>
+      <timestamp>2020-01-02T00:00:00Z</timestamp>
+      <contributor><ip>127.0.0.1</ip></contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="11">second body</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+
+    fn read_pages(policy: OversizePolicy, parser: Parser) -> PageIterator<&'static [u8]> {
+        let options = ScanOptions::new()
+            .with_parser(parser)
+            .with_memory_budget(MemoryBudget::per_page(15, policy));
+        PageIterator::from_reader_with(DUMP.as_bytes(), options)
+    }
+
+    #[test]
+    fn spilled_bodies_are_read_back() {
+        let dir = std::env::temp_dir();
+        for parser in [Parser::Xml, Parser::Fast] {
+            let mut pages = read_pages(OversizePolicy::Spill(dir.clone()), parser);
+            let page = pages.next().unwrap();
+            let revisions = page.revisions();
+            assert!(revisions.iter().all(Revision::is_spilled));
+            assert_eq!(revisions[0].body_len(), 10);
+            assert_eq!(revisions[0].read_body().unwrap(), "first body");
+            assert_eq!(revisions[1].read_body().unwrap(), "second body");
+            // Reading a body again reads it from the file again
+            assert_eq!(revisions[1].read_body().unwrap(), "second body");
+            assert!(pages.next().is_none());
+            assert!(pages.error().is_none());
+        }
+    }
+
+    #[test]
+    fn spill_failure_stops_the_iterator() {
+        let dir = std::env::temp_dir().join("wiki-dump-analyzer-missing-spill-dir");
+        for parser in [Parser::Xml, Parser::Fast] {
+            let mut pages = read_pages(OversizePolicy::Spill(dir.clone()), parser);
+            assert!(pages.next().is_none());
+            assert!(pages.error().is_some());
+        }
+    }
+
+    #[test]
+    fn truncate_and_skip_record_oversized_pages() {
+        let mut pages = read_pages(OversizePolicy::Truncate, Parser::Xml);
+        let page = pages.next().unwrap();
+        assert_eq!(page.revisions().len(), 1);
+        assert_eq!(page.revisions()[0].body(), "first body");
+
+        let budget = MemoryBudget::per_page(15, OversizePolicy::Skip);
+        let options = ScanOptions::new().with_memory_budget(budget.clone());
+        let mut pages = PageIterator::from_reader_with(DUMP.as_bytes(), options);
+        assert!(pages.next().is_none());
+        let oversized = budget.oversized_pages();
+        assert_eq!(oversized.len(), 1);
+        assert_eq!(oversized[0].revision_count, 2);
+        assert_eq!(oversized[0].kept_revision_count, 0);
+    }
+}
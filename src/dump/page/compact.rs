@@ -0,0 +1,302 @@
+use super::{Page, Revision};
+
+/// Number of revisions between full copies of a body when none is specified.
+pub const DEFAULT_KEYFRAME_INTERVAL: usize = 64;
+
+/// A page on a wiki whose revision bodies are stored as deltas against the previous revision.
+///
+/// Consecutive revisions of a page are usually near-identical, so this can be much smaller than
+/// the equivalent [`Page`]. Bodies are reconstructed on demand, starting from the nearest
+/// preceding revision whose body is stored in full (a "keyframe").
+pub struct CompactPage {
+    /// Page ID.
+    id: i64,
+    /// Namespace (on Wikipedia, 0 is for articles, 1 is talk pages, 2 is user pages, etc.)
+    namespace: i64,
+    /// Page title.
+    title: String,
+    /// Page revisions, with their bodies removed.
+    revisions: Vec<Revision>,
+    /// The stored body of each revision in `revisions`.
+    bodies: Vec<StoredBody>,
+}
+
+/// The body of a revision, either in full or relative to the body of the previous revision.
+enum StoredBody {
+    Full(String),
+    Delta {
+        /// Number of bytes shared with the start of the previous body.
+        prefix: usize,
+        /// Number of bytes shared with the end of the previous body.
+        suffix: usize,
+        /// Text between the shared prefix and suffix.
+        inserted: String,
+    },
+}
+
+impl StoredBody {
+    fn encode(previous: &str, body: &str) -> StoredBody {
+        let (prefix, suffix) = shared_affixes(previous, body);
+        let inserted = &body[prefix..body.len() - suffix];
+        // Deltas which do not save anything are stored in full instead
+        if inserted.len() >= body.len() {
+            StoredBody::Full(body.to_string())
+        } else {
+            StoredBody::Delta {
+                prefix,
+                suffix,
+                inserted: inserted.to_string(),
+            }
+        }
+    }
+
+    /// Replace `body` (the body of the previous revision) with the body this represents.
+    fn apply(&self, body: &mut String) {
+        match self {
+            StoredBody::Full(full) => full.clone_into(body),
+            StoredBody::Delta {
+                prefix,
+                suffix,
+                inserted,
+            } => {
+                let suffix_start = body.len() - suffix;
+                body.replace_range(*prefix..suffix_start, inserted);
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            StoredBody::Full(full) => full.len(),
+            StoredBody::Delta { inserted, .. } => inserted.len(),
+        }
+    }
+}
+
+/// Return the lengths (in bytes) of the longest common prefix and suffix of two strings, such that
+/// the two do not overlap and both fall on character boundaries.
+fn shared_affixes(a: &str, b: &str) -> (usize, usize) {
+    let (a_bytes, b_bytes) = (a.as_bytes(), b.as_bytes());
+
+    let mut prefix = a_bytes
+        .iter()
+        .zip(b_bytes)
+        .take_while(|(x, y)| x == y)
+        .count();
+    while !b.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = a_bytes.len().min(b_bytes.len()) - prefix;
+    let mut suffix = a_bytes
+        .iter()
+        .rev()
+        .zip(b_bytes.iter().rev())
+        .take(max_suffix)
+        .take_while(|(x, y)| x == y)
+        .count();
+    while !b.is_char_boundary(b.len() - suffix) {
+        suffix -= 1;
+    }
+
+    (prefix, suffix)
+}
+
+impl CompactPage {
+    /// Create a [`CompactPage`] from a [`Page`], storing every `keyframe_interval`-th body in full.
+    /// Larger intervals use less memory, but make reconstructing an individual body slower. This
+    /// fails if a body spilled to disk by a [`MemoryBudget`](super::MemoryBudget) cannot be read
+    /// back.
+    pub fn from_page(page: Page, keyframe_interval: usize) -> std::io::Result<CompactPage> {
+        let keyframe_interval = keyframe_interval.max(1);
+        let mut revisions = page.revisions;
+        let mut bodies = Vec::with_capacity(revisions.len());
+
+        let mut previous = String::new();
+        for (i, rev) in revisions.iter_mut().enumerate() {
+            let body = rev.take_body()?;
+            let stored = if i % keyframe_interval == 0 {
+                StoredBody::Full(body.clone())
+            } else {
+                StoredBody::encode(&previous, &body)
+            };
+            bodies.push(stored);
+            previous = body;
+        }
+
+        Ok(CompactPage {
+            id: page.id,
+            namespace: page.namespace,
+            title: page.title,
+            revisions,
+            bodies,
+        })
+    }
+
+    /// Page ID.
+    pub fn id(self: &CompactPage) -> i64 {
+        self.id
+    }
+
+    /// Namespace (on Wikipedia, 0 is for articles, 1 is talk pages, 2 is user pages, etc.)
+    /// More information for Wikipedia available [here](https://en.wikipedia.org/wiki/Wikipedia:Namespace).
+    pub fn namespace(self: &CompactPage) -> i64 {
+        self.namespace
+    }
+
+    /// Page title.
+    pub fn title(self: &CompactPage) -> &String {
+        &self.title
+    }
+
+    /// Number of revisions of the page.
+    pub fn revision_count(self: &CompactPage) -> usize {
+        self.revisions.len()
+    }
+
+    /// Reconstruct the body of the revision at `index` (in timestamp order).
+    pub fn body(self: &CompactPage, index: usize) -> Option<String> {
+        if index >= self.bodies.len() {
+            return None;
+        }
+
+        let keyframe = self.bodies[..=index]
+            .iter()
+            .rposition(|b| matches!(b, StoredBody::Full(_)))
+            .expect("The first body is always stored in full");
+
+        let mut body = String::new();
+        for stored in &self.bodies[keyframe..=index] {
+            stored.apply(&mut body);
+        }
+        Some(body)
+    }
+
+    /// Reconstruct the revision at `index` (in timestamp order), including its body.
+    pub fn revision(self: &CompactPage, index: usize) -> Option<Revision> {
+        let body = self.body(index)?;
+        Some(self.revisions[index].with_body(body))
+    }
+
+    /// Return an iterator which reconstructs every revision in turn. This is much faster than
+    /// calling [`CompactPage::revision`] for each index.
+    pub fn revisions(self: &CompactPage) -> impl Iterator<Item = Revision> + '_ {
+        let mut body = String::new();
+        self.revisions
+            .iter()
+            .zip(&self.bodies)
+            .map(move |(rev, stored)| {
+                stored.apply(&mut body);
+                rev.with_body(body.clone())
+            })
+    }
+
+    /// Approximate number of bytes used to store the revision bodies.
+    pub fn stored_body_size(self: &CompactPage) -> usize {
+        self.bodies.iter().map(StoredBody::size).sum()
+    }
+
+    /// Convert back into a [`Page`], reconstructing every revision body.
+    pub fn into_page(self) -> Page {
+        let revisions = self.revisions().collect();
+        Page {
+            id: self.id,
+            namespace: self.namespace,
+            title: self.title,
+            revisions,
+        }
+    }
+}
+
+impl std::fmt::Debug for CompactPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CompactPage {{ id: {:?}, title: {:?}, namespace: {:?}, revision_count: {:?}, stored_body_size: {:?} }}",
+            self.id,
+            self.title,
+            self.namespace,
+            self.revisions.len(),
+            self.stored_body_size()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_affixes_do_not_overlap() {
+        assert_eq!(shared_affixes("abcdef", "abcxyzdef"), (3, 3));
+        assert_eq!(shared_affixes("", "abc"), (0, 0));
+        assert_eq!(shared_affixes("abc", "abc"), (3, 0));
+        // "aa" -> "aaa": the prefix takes both shared bytes, leaving one for the suffix
+        assert_eq!(shared_affixes("aa", "aaa"), (2, 0));
+        assert_eq!(shared_affixes("aba", "a"), (1, 0));
+    }
+
+    #[test]
+    fn shared_affixes_fall_on_char_boundaries() {
+        // "é" and "è" share their first byte
+        let (prefix, suffix) = shared_affixes("café!", "cafè!");
+        assert_eq!((prefix, suffix), (3, 1));
+        assert!("cafè!".is_char_boundary(prefix));
+    }
+
+    #[test]
+    fn encode_then_apply_reconstructs_body() {
+        let cases = [
+            ("", "new page"),
+            ("The cat sat.", "The black cat sat."),
+            ("The black cat sat.", "The cat sat."),
+            ("same", "same"),
+            ("naïve café", "naïve cafés"),
+            ("something entirely different", "xyz"),
+        ];
+        for (previous, body) in cases {
+            let mut reconstructed = previous.to_string();
+            StoredBody::encode(previous, body).apply(&mut reconstructed);
+            assert_eq!(reconstructed, body, "from {:?}", previous);
+        }
+    }
+
+    #[test]
+    fn encode_stores_unrelated_bodies_in_full() {
+        assert!(matches!(
+            StoredBody::encode("abc", "xyz"),
+            StoredBody::Full(_)
+        ));
+        match StoredBody::encode("The cat sat.", "The black cat sat.") {
+            StoredBody::Delta {
+                prefix,
+                suffix,
+                inserted,
+            } => assert_eq!((prefix, suffix, inserted.as_str()), (4, 8, "black ")),
+            StoredBody::Full(_) => panic!("Expected a delta"),
+        }
+    }
+
+    #[test]
+    fn compact_page_reconstructs_every_revision() {
+        let bodies = ["one", "one two", "one two three", "two three", "", "four"];
+        let revisions: Vec<_> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| (i as i64 + 1, "2020-01-01T00:00:00Z", *body))
+            .collect();
+        let page = crate::dump::page::test_page(1, "Example", &revisions);
+
+        for interval in [1, 2, DEFAULT_KEYFRAME_INTERVAL] {
+            let compact = CompactPage::from_page(page.duplicate(), interval).unwrap();
+            assert_eq!(compact.revision_count(), bodies.len());
+            for (i, body) in bodies.iter().enumerate() {
+                assert_eq!(compact.body(i).as_deref(), Some(*body));
+            }
+            assert_eq!(compact.body(bodies.len()), None);
+            let reconstructed: Vec<String> =
+                compact.revisions().map(|rev| rev.body().clone()).collect();
+            assert_eq!(reconstructed, bodies);
+        }
+    }
+}
@@ -1,6 +1,14 @@
+pub mod budget;
+pub mod compact;
 pub mod revision;
+mod scanner;
+pub mod sevenzip;
+pub use budget::{MemoryBudget, OversizePolicy, OversizedPage};
+pub use compact::CompactPage;
 pub use revision::Revision;
 
+use crate::dump::options::{Parser, ScanOptions};
+
 /// A page on a wiki
 pub struct Page {
     /// Page ID.
@@ -33,7 +41,57 @@ impl Page {
 
     /// Page revisions.
     pub fn revisions(self: &Page) -> &Vec<Revision> {
-        &&self.revisions
+        &self.revisions
+    }
+
+    /// Return the revision with the specified revision ID (if any).
+    pub fn revision_by_id(self: &Page, revision_id: i64) -> Option<&Revision> {
+        self.revisions.iter().find(|rev| rev.id() == revision_id)
+    }
+
+    /// Return the revision which was current at `timestamp`: the latest revision created at or
+    /// before it.
+    pub fn revision_at(
+        self: &Page,
+        timestamp: &chrono::DateTime<chrono::Utc>,
+    ) -> Option<&Revision> {
+        self.revisions
+            .iter()
+            .take_while(|rev| rev.timestamp() <= timestamp)
+            .last()
+    }
+
+    /// Convert into a [`CompactPage`], which stores revision bodies as deltas against the previous
+    /// revision. This fails if a body spilled to disk cannot be read back.
+    pub fn compact(self) -> std::io::Result<CompactPage> {
+        CompactPage::from_page(self, compact::DEFAULT_KEYFRAME_INTERVAL)
+    }
+
+    /// Return a copy of the page. Bodies spilled to disk are not read back.
+    pub(crate) fn duplicate(self: &Page) -> Page {
+        Page {
+            id: self.id,
+            namespace: self.namespace,
+            title: self.title.clone(),
+            revisions: self.revisions.iter().map(Revision::duplicate).collect(),
+        }
+    }
+
+    /// Add the revisions of `newer`, a later copy of the same page, which are not already
+    /// present, and take its title and namespace (which may have changed since).
+    pub(crate) fn merge(self: &mut Page, newer: &Page) {
+        let known: std::collections::HashSet<i64> = self.revisions.iter().map(|r| r.id()).collect();
+        self.revisions.extend(
+            newer
+                .revisions
+                .iter()
+                .filter(|rev| !known.contains(&rev.id()))
+                .map(Revision::duplicate),
+        );
+        self.revisions
+            .sort_by_cached_key(|rev| rev.timestamp().timestamp());
+        self.namespace = newer.namespace;
+        self.title = newer.title.clone();
     }
 }
 
@@ -51,32 +109,173 @@ impl std::fmt::Debug for Page {
 }
 
 pub struct PageIterator<B: std::io::BufRead> {
-    xml_reader: quick_xml::Reader<B>,
+    parser: PageParser<B>,
+    options: ScanOptions,
+    /// The error which stopped the iterator early (if any).
+    error: Option<std::io::Error>,
+}
+
+/// An iterator over pages which may stop early because of an error.
+pub(crate) trait PageSource: Iterator<Item = Page> {
+    /// Return the error which stopped the iterator (if any), leaving [`None`] in its place.
+    fn take_error(&mut self) -> Option<std::io::Error>;
+}
+
+/// The state of the [`Parser`] used by a [`PageIterator`].
+enum PageParser<B: std::io::BufRead> {
+    Xml(quick_xml::Reader<B>),
+    Fast(scanner::Scanner<B>),
+}
+
+impl<B: std::io::BufRead> PageParser<B> {
+    fn new(bufreader: B, parser: Parser) -> PageParser<B> {
+        match parser {
+            Parser::Xml => PageParser::Xml(quick_xml::Reader::from_reader(bufreader)),
+            Parser::Fast => PageParser::Fast(scanner::Scanner::new(bufreader)),
+        }
+    }
+
+    fn position(self: &PageParser<B>) -> u64 {
+        match self {
+            PageParser::Xml(xml_reader) => xml_reader.buffer_position() as u64,
+            PageParser::Fast(scanner) => scanner.position(),
+        }
+    }
+
+    fn into_inner(self: PageParser<B>) -> B {
+        match self {
+            PageParser::Xml(xml_reader) => xml_reader.into_underlying_reader(),
+            PageParser::Fast(scanner) => scanner.into_inner(),
+        }
+    }
 }
 
 impl<B: std::io::BufRead> PageIterator<B> {
     /// Create a [`PageIterator`] from an [`std::io::BufRead`].
     pub fn from_reader(bufreader: B) -> PageIterator<B> {
-        let xml_reader = quick_xml::Reader::from_reader(bufreader);
-        PageIterator { xml_reader }
+        PageIterator::from_reader_with(bufreader, ScanOptions::default())
+    }
+
+    /// Create a [`PageIterator`] from an [`std::io::BufRead`] which reads pages according to `options`.
+    pub fn from_reader_with(bufreader: B, options: ScanOptions) -> PageIterator<B> {
+        PageIterator {
+            parser: PageParser::new(bufreader, options.parser()),
+            options,
+            error: None,
+        }
+    }
+
+    /// Number of bytes of (decompressed) input consumed so far. Before a call to
+    /// [`Iterator::next`] this is at or before the start of the next page, so a dump can later be
+    /// resumed from that page by skipping this many bytes (see [`PageIterator::from_path_at`]).
+    pub fn position(self: &PageIterator<B>) -> u64 {
+        self.parser.position()
+    }
+
+    /// The error which stopped the iterator before the end of the dump (if any), such as a failure
+    /// to read the dump, or to spill revision bodies to disk (see [`MemoryBudget`]).
+    pub fn error(self: &PageIterator<B>) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Use `options` when reading pages. This should be called before any pages are read.
+    pub fn with_options(self: PageIterator<B>, options: ScanOptions) -> PageIterator<B> {
+        PageIterator::from_reader_with(self.parser.into_inner(), options)
     }
 }
 
-impl PageIterator<std::io::BufReader<std::process::ChildStdout>> {
+impl PageIterator<std::io::BufReader<sevenzip::SevenZipReader>> {
     pub fn from_path(path: &std::path::Path) -> PageIterator<impl std::io::BufRead> {
-        // This requires that the 7z command be installed and on the $PATH
-        // TODO: Replace unwraps
-        let file = std::process::Command::new("7z")
-            .arg("x")
-            .arg("-so")
-            .arg(path)
-            .stdout(std::process::Stdio::piped())
-            .spawn()
-            .unwrap()
-            .stdout
-            .unwrap();
+        PageIterator::from_path_with(path, ScanOptions::default())
+    }
+
+    /// Create a [`PageIterator`] from a path to a compressed dump file which reads pages according to `options`.
+    pub fn from_path_with(
+        path: &std::path::Path,
+        options: ScanOptions,
+    ) -> PageIterator<impl std::io::BufRead> {
+        PageIterator::from_path_at(path, 0, options)
+    }
+
+    /// Create a [`PageIterator`] from a path to a compressed dump file which starts reading at `offset`
+    /// bytes into the decompressed file, which should be a [`PageIterator::position`] recorded
+    /// while reading the same file. Unless `offset` is zero this always uses [`Parser::Fast`],
+    /// since the input does not start at the beginning of the XML document.
+    pub fn from_path_at(
+        path: &std::path::Path,
+        offset: u64,
+        options: ScanOptions,
+    ) -> PageIterator<impl std::io::BufRead> {
+        // Failing to start reading is reported by the iterator (see [`PageIterator::error`])
+        let mut error = None;
+        let mut file: Box<dyn std::io::Read + Send> = match sevenzip::SevenZipReader::new(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                error = Some(std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to run 7z: {}", e),
+                ));
+                Box::new(std::io::empty())
+            }
+        };
+        let options = if offset > 0 {
+            if let Err(e) = std::io::copy(
+                &mut std::io::Read::take(&mut file, offset),
+                &mut std::io::sink(),
+            ) {
+                error.get_or_insert(e);
+            }
+            options.with_parser(Parser::Fast)
+        } else {
+            options
+        };
+        let mut pages = PageIterator::from_reader_with(std::io::BufReader::new(file), options);
+        pages.error = error;
+        pages
+    }
+}
+
+/// A [`PageIterator`] which publishes its position in the dump (counting from the start of the
+/// decompressed file) after each page, so that it can still be read once the iterator has been
+/// wrapped by others.
+pub(crate) struct PositionedPages<B: std::io::BufRead> {
+    pages: PageIterator<B>,
+    /// The offset at which `pages` started reading.
+    start: u64,
+    position: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
 
-        PageIterator::from_reader(std::io::BufReader::new(file))
+impl<B: std::io::BufRead> PositionedPages<B> {
+    pub(crate) fn new(
+        pages: PageIterator<B>,
+        start: u64,
+        position: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    ) -> PositionedPages<B> {
+        position.store(start, std::sync::atomic::Ordering::Relaxed);
+        PositionedPages {
+            pages,
+            start,
+            position,
+        }
+    }
+}
+
+impl<B: std::io::BufRead> Iterator for PositionedPages<B> {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        let page = self.pages.next();
+        self.position.store(
+            self.start + self.pages.position(),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        page
+    }
+}
+
+impl<B: std::io::BufRead> PageSource for PositionedPages<B> {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        self.pages.take_error()
     }
 }
 
@@ -84,29 +283,270 @@ impl<B: std::io::BufRead> Iterator for PageIterator<B> {
     type Item = Page;
 
     fn next(&mut self) -> Option<Page> {
-        let mut rev_iter = revision::RevisionIterator::new(&mut self.xml_reader);
+        loop {
+            if self.options.is_cancelled() || self.error.is_some() {
+                return None;
+            }
 
-        let mut revisions = Vec::new();
-        for rev in &mut rev_iter {
-            revisions.push(rev);
-        }
+            let mut collector = budget::RevisionCollector::new(self.options.memory_budget());
 
-        // In rare cases, revisions are not stored in the order of their timestamps. This
-        // fixes those cases.
-        revisions.sort_by_cached_key(|rev| rev.timestamp().timestamp());
+            let header = match &mut self.parser {
+                PageParser::Xml(xml_reader) => {
+                    let mut rev_iter = revision::RevisionIterator::new(xml_reader);
+                    for rev in &mut rev_iter {
+                        if let Err(e) = collector.push(rev) {
+                            self.error = Some(e);
+                            return None;
+                        }
+                        if self.options.is_cancelled() {
+                            return None;
+                        }
+                    }
+                    if let Some(e) = rev_iter.error.take() {
+                        self.error = Some(e);
+                        return None;
+                    }
+                    scanner::PageHeader {
+                        id: rev_iter.page_id,
+                        namespace: rev_iter.page_namespace,
+                        title: rev_iter.page_title,
+                    }
+                }
+                PageParser::Fast(scanner) => {
+                    let options = &self.options;
+                    let mut error = None;
+                    let header = scanner.read_page(|rev| {
+                        if let Err(e) = collector.push(rev) {
+                            error = Some(e);
+                            std::ops::ControlFlow::Break(())
+                        } else if options.is_cancelled() {
+                            std::ops::ControlFlow::Break(())
+                        } else {
+                            std::ops::ControlFlow::Continue(())
+                        }
+                    });
+                    if let Some(e) = error.or_else(|| scanner.take_error()) {
+                        self.error = Some(e);
+                        return None;
+                    }
+                    header
+                }
+            };
 
-        if rev_iter.page_id.is_none()
-            || rev_iter.page_namespace.is_none()
-            || rev_iter.page_title.is_none()
-        {
-            return None;
+            if self.options.is_cancelled() {
+                return None;
+            }
+
+            let (id, namespace, title) = match header {
+                scanner::PageHeader {
+                    id: Some(id),
+                    namespace: Some(namespace),
+                    title: Some(title),
+                } => (id, namespace, title),
+                _ => return None,
+            };
+
+            // Pages belonging to other shards are skipped
+            if let Some(shard) = self.options.page_shard() {
+                if !shard.contains_page_id(id) {
+                    continue;
+                }
+            }
+
+            // Pages which exceed the memory budget may be skipped
+            let mut revisions = match collector.finish(id, &title) {
+                Some(revisions) => revisions,
+                None => continue,
+            };
+
+            // In rare cases, revisions are not stored in the order of their timestamps. This
+            // fixes those cases.
+            revisions.sort_by_cached_key(|rev| rev.timestamp().timestamp());
+
+            return Some(Page {
+                id,
+                namespace,
+                title,
+                revisions,
+            });
         }
+    }
+}
 
-        Some(Page {
-            id: rev_iter.page_id.unwrap(),
-            namespace: rev_iter.page_namespace.unwrap(),
-            title: rev_iter.page_title.unwrap(),
-            revisions,
+impl<B: std::io::BufRead> PageSource for PageIterator<B> {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+/// Create a page for tests from `(revision ID, timestamp, body)` triples, in order.
+#[cfg(test)]
+pub(crate) fn test_page(id: i64, title: &str, revisions: &[(i64, &str, &str)]) -> Page {
+    let revisions = revisions
+        .iter()
+        .map(|&(id, timestamp, body)| {
+            revision::PartialRevision {
+                id: Some(id),
+                contributor_username: Some(format!("User {}", id)),
+                timestamp: Some(timestamp.parse().expect("Bad timestamp")),
+                model: Some("wikitext".to_string()),
+                format: Some("text/x-wiki".to_string()),
+                text: Some(body.to_string()),
+                ..Default::default()
+            }
+            .finish()
+            .expect("Missing revision field")
         })
+        .collect();
+    Page {
+        id,
+        namespace: 0,
+        title: title.to_string(),
+        revisions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader which returns `data`, then fails.
+    struct FailingReader<'a> {
+        data: &'a [u8],
+    }
+
+    impl std::io::Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.data.is_empty() {
+                return Err(std::io::Error::other("archive is truncated"));
+            }
+            std::io::Read::read(&mut self.data, buf)
+        }
+    }
+
+    fn revision_ids(page: &Page) -> Vec<i64> {
+        page.revisions().iter().map(Revision::id).collect()
+    }
+
+    #[test]
+    fn merge_adds_new_revisions_in_timestamp_order() {
+        let mut page = test_page(
+            1,
+            "Old title",
+            &[
+                (10, "2020-01-01T00:00:00Z", "a"),
+                (12, "2020-03-01T00:00:00Z", "c"),
+            ],
+        );
+        let mut newer = test_page(
+            1,
+            "New title",
+            &[
+                (11, "2020-02-01T00:00:00Z", "b"),
+                (12, "2020-03-01T00:00:00Z", "c"),
+                (13, "2020-04-01T00:00:00Z", "d"),
+            ],
+        );
+        newer.namespace = 4;
+        page.merge(&newer);
+        assert_eq!(revision_ids(&page), [10, 11, 12, 13]);
+        let bodies: Vec<&str> = page.revisions().iter().map(|r| r.body().as_str()).collect();
+        assert_eq!(bodies, ["a", "b", "c", "d"]);
+        assert_eq!((page.title().as_str(), page.namespace()), ("New title", 4));
+
+        // Merging the same page again changes nothing
+        page.merge(&newer);
+        assert_eq!(revision_ids(&page), [10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn merge_keeps_existing_revisions() {
+        let mut page = test_page(1, "A", &[(10, "2020-01-01T00:00:00Z", "original")]);
+        let newer = test_page(1, "A", &[(10, "2020-01-01T00:00:00Z", "changed")]);
+        page.merge(&newer);
+        assert_eq!(revision_ids(&page), [10]);
+        assert_eq!(page.revisions()[0].body(), "original");
+
+        let mut page = test_page(1, "A", &[]);
+        page.merge(&newer);
+        assert_eq!(revision_ids(&page), [10]);
+    }
+
+    const DUMP: &str = r#"<mediawiki>
+  <page>
+    <title>A</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>10</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <contributor><username>A</username><id>1</id></contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="4">Body</text>
+    </revision>
+  </page>
+  <page>
+    <title>B</title>
+    <ns>0</ns>
+    <id>2</id>
+    <revision>
+      <id>20</id>
+"#;
+
+    #[test]
+    fn read_errors_stop_the_iterator() {
+        for parser in [Parser::Xml, Parser::Fast] {
+            let reader = std::io::BufReader::new(FailingReader {
+                data: DUMP.as_bytes(),
+            });
+            let mut pages =
+                PageIterator::from_reader_with(reader, ScanOptions::new().with_parser(parser));
+            assert_eq!(pages.next().map(|p| p.id()), Some(1));
+            assert!(pages.next().is_none());
+            let error = pages.error().expect("No error");
+            assert_eq!(error.to_string(), "archive is truncated");
+            assert!(pages.next().is_none());
+        }
+    }
+
+    #[test]
+    fn revisions_without_text_have_empty_bodies() {
+        // As in a stub dump, where `<text>` only gives the size of the body
+        let stub = r#"<mediawiki>
+  <page>
+    <title>A</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>10</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <contributor><username>A</username><id>1</id></contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="120" id="5" />
+      <sha1>abc</sha1>
+    </revision>
+    <revision>
+      <id>11</id>
+      <timestamp>2020-02-01T00:00:00Z</timestamp>
+      <contributor deleted="deleted" />
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="0" />
+    </revision>
+  </page>
+</mediawiki>
+"#;
+        for parser in [Parser::Xml, Parser::Fast] {
+            let pages: Vec<Page> = PageIterator::from_reader_with(
+                stub.as_bytes(),
+                ScanOptions::new().with_parser(parser),
+            )
+            .collect();
+            assert_eq!(pages.len(), 1);
+            assert_eq!(revision_ids(&pages[0]), [10, 11], "{:?}", parser);
+            assert!(pages[0].revisions().iter().all(|r| r.body().is_empty()));
+        }
     }
 }
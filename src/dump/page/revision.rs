@@ -1,3 +1,4 @@
+use super::budget::SpillFile;
 use chrono::DateTime;
 use quick_xml::events::Event;
 
@@ -15,12 +16,26 @@ pub struct Revision {
     parent_id: Option<i64>,
     /// Time when the revision was created.
     timestamp: DateTime<chrono::Utc>,
+    /// Edit summary (if specified). The comment may be missing because it was empty or deleted.
+    comment: Option<String>,
     /// Data model (usually 'wikitext', but not always).
     model: String,
     /// Data format (usually 'text/x-wiki', but not always).
     format: String,
     /// The body of the revision.
-    body: String,
+    body: Body,
+}
+
+/// Where the body of a revision is stored.
+#[derive(Clone)]
+enum Body {
+    InMemory(String),
+    /// Stored in a [`SpillFile`], and read back each time it is requested.
+    Spilled {
+        file: std::sync::Arc<SpillFile>,
+        offset: u64,
+        len: usize,
+    },
 }
 
 impl Revision {
@@ -36,12 +51,12 @@ impl Revision {
 
     /// Contributor username (if specified). A username might not be specified because the contributor was not logged in.
     pub fn contributor_username(self: &Revision) -> Option<&String> {
-        (&self.contributor_username).as_ref()
+        self.contributor_username.as_ref()
     }
 
     /// Contributor IP address (if specified). The contributor's IP address is generally only included if they were not logged in.
     pub fn contributor_ip(self: &Revision) -> Option<&String> {
-        (&self.contributor_ip).as_ref()
+        self.contributor_ip.as_ref()
     }
 
     /// Parent revision's ID (if this revision has a parent).
@@ -54,6 +69,11 @@ impl Revision {
         &self.timestamp
     }
 
+    /// Edit summary (if specified). The comment may be missing because it was empty or deleted.
+    pub fn comment(self: &Revision) -> Option<&String> {
+        self.comment.as_ref()
+    }
+
     /// Data model (usually 'wikitext', but not always).
     pub fn model(self: &Revision) -> &String {
         &self.model
@@ -65,15 +85,126 @@ impl Revision {
     }
 
     /// The body of the revision.
+    ///
+    /// # Panics
+    /// Panics if the body was spilled to disk by a [`MemoryBudget`](super::budget::MemoryBudget).
+    /// Use [`Revision::read_body`] for revisions which may have been spilled.
     pub fn body(self: &Revision) -> &String {
-        &self.body
+        match &self.body {
+            Body::InMemory(body) => body,
+            Body::Spilled { .. } => {
+                panic!("The body of revision {} was spilled to disk", self.id)
+            }
+        }
+    }
+
+    /// The body of the revision, which is read back from disk (without being kept in memory) if
+    /// it was spilled by a [`MemoryBudget`](super::budget::MemoryBudget).
+    pub fn read_body(self: &Revision) -> std::io::Result<std::borrow::Cow<'_, str>> {
+        match &self.body {
+            Body::InMemory(body) => Ok(std::borrow::Cow::Borrowed(body)),
+            Body::Spilled { file, offset, len } => {
+                Ok(std::borrow::Cow::Owned(file.read(*offset, *len)?))
+            }
+        }
+    }
+
+    /// Size of the body of the revision in bytes. This does not read back a spilled body.
+    pub fn body_len(self: &Revision) -> usize {
+        match &self.body {
+            Body::InMemory(body) => body.len(),
+            Body::Spilled { len, .. } => *len,
+        }
+    }
+
+    /// Returns [`true`] if the body was spilled to disk by a
+    /// [`MemoryBudget`](super::budget::MemoryBudget).
+    pub fn is_spilled(self: &Revision) -> bool {
+        matches!(self.body, Body::Spilled { .. })
+    }
+
+    /// Remove the body of the revision, leaving it empty.
+    pub(crate) fn take_body(self: &mut Revision) -> std::io::Result<String> {
+        match std::mem::replace(&mut self.body, Body::InMemory(String::new())) {
+            Body::InMemory(body) => Ok(body),
+            Body::Spilled { file, offset, len } => file.read(offset, len),
+        }
+    }
+
+    /// Replace the body of the revision with a reference to `len` bytes at `offset` in `file`.
+    pub(crate) fn set_spilled_body(
+        self: &mut Revision,
+        file: std::sync::Arc<SpillFile>,
+        offset: u64,
+        len: usize,
+    ) {
+        self.body = Body::Spilled { file, offset, len };
+    }
+
+    /// Return a copy of the revision. A spilled body is not read back, but shares the same file.
+    pub(crate) fn duplicate(self: &Revision) -> Revision {
+        Revision {
+            body: self.body.clone(),
+            ..self.with_body(String::new())
+        }
+    }
+
+    /// Return a copy of the revision with a different body.
+    pub(crate) fn with_body(self: &Revision, body: String) -> Revision {
+        Revision {
+            id: self.id,
+            contributor_id: self.contributor_id,
+            contributor_username: self.contributor_username.clone(),
+            contributor_ip: self.contributor_ip.clone(),
+            parent_id: self.parent_id,
+            timestamp: self.timestamp,
+            comment: self.comment.clone(),
+            model: self.model.clone(),
+            format: self.format.clone(),
+            body: Body::InMemory(body),
+        }
     }
 }
 
 impl std::fmt::Debug for Revision {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Revision {{ id: {:?}, parent_id: {:?}, contributor_id: {:?}, contributor_username: {:?}, contributor_ip: {:?}, timestamp: {:?}, model: {:?}, format: {:?}, text: \"...\" }}",
-            self.id, self.parent_id, self.contributor_id, self.contributor_username, self.contributor_ip, self.timestamp, self.model, self.format)
+        write!(f, "Revision {{ id: {:?}, parent_id: {:?}, contributor_id: {:?}, contributor_username: {:?}, contributor_ip: {:?}, timestamp: {:?}, comment: {:?}, model: {:?}, format: {:?}, text: \"...\" }}",
+            self.id, self.parent_id, self.contributor_id, self.contributor_username, self.contributor_ip, self.timestamp, self.comment, self.model, self.format)
+    }
+}
+
+/// The fields of a [`Revision`] collected while parsing, any of which may be missing.
+#[derive(Default)]
+pub(crate) struct PartialRevision {
+    pub(crate) id: Option<i64>,
+    pub(crate) parent_id: Option<i64>,
+    pub(crate) contributor_id: Option<i64>,
+    pub(crate) contributor_username: Option<String>,
+    pub(crate) contributor_ip: Option<String>,
+    pub(crate) timestamp: Option<DateTime<chrono::Utc>>,
+    pub(crate) comment: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) format: Option<String>,
+    pub(crate) text: Option<String>,
+}
+
+impl PartialRevision {
+    /// Return the [`Revision`], or [`None`] if any of the mandatory fields are missing. The body is
+    /// not mandatory: revisions whose `<text>` element is empty (like `<text bytes="120" />`, as in
+    /// stub dumps and for deleted revisions) have an empty body.
+    pub(crate) fn finish(self: PartialRevision) -> Option<Revision> {
+        Some(Revision {
+            id: self.id?,
+            parent_id: self.parent_id,
+            contributor_id: self.contributor_id,
+            contributor_username: self.contributor_username,
+            contributor_ip: self.contributor_ip,
+            timestamp: self.timestamp?,
+            comment: self.comment,
+            model: self.model?,
+            format: self.format?,
+            body: Body::InMemory(self.text.unwrap_or_default()),
+        })
     }
 }
 
@@ -81,6 +212,8 @@ pub struct RevisionIterator<'a, B: std::io::BufRead> {
     xml_reader: &'a mut quick_xml::Reader<B>,
     buf: Vec<u8>,
     last_page: bool,
+    /// The error which stopped reading (if any).
+    pub error: Option<std::io::Error>,
 
     pub page_id: Option<i64>,
     pub page_namespace: Option<i64>,
@@ -93,6 +226,7 @@ impl<'a, B: std::io::BufRead> RevisionIterator<'a, B> {
             xml_reader,
             buf: Vec::new(),
             last_page: false,
+            error: None,
             page_id: None,
             page_namespace: None,
             page_title: None,
@@ -104,54 +238,64 @@ impl<'a, B: std::io::BufRead> Iterator for RevisionIterator<'a, B> {
     type Item = Revision;
 
     fn next(&mut self) -> Option<Revision> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.read_revision() {
+            Ok(rev) => rev,
+            Err(e) => {
+                self.error = Some(match e {
+                    quick_xml::Error::Io(e) => e,
+                    e => std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+                });
+                None
+            }
+        }
+    }
+}
+
+impl<'a, B: std::io::BufRead> RevisionIterator<'a, B> {
+    /// Read the next revision of the page, or return [`None`] at the end of the page.
+    fn read_revision(
+        self: &mut RevisionIterator<'a, B>,
+    ) -> Result<Option<Revision>, quick_xml::Error> {
         loop {
             // Run until we reach the first <revision> tag
             loop {
-                match &self.xml_reader.read_event(&mut self.buf) {
+                match self.xml_reader.read_event(&mut self.buf) {
                     // Loop until we reach the start of a new <revision>
                     Ok(Event::Start(ref e)) if e.name() == b"revision" => break,
                     // End the iterator if we reach the EOF
                     Ok(Event::Eof) => {
                         self.last_page = true;
-                        return None;
+                        return Ok(None);
                     }
                     // Stop if we reach the end of the page
-                    Ok(Event::End(ref e)) if e.name() == b"page" => return None,
+                    Ok(Event::End(ref e)) if e.name() == b"page" => return Ok(None),
                     // If we see a tag we want, set next_page_field so that we will capture it
                     Ok(Event::Start(ref e)) => match e.name() {
-                        b"id" => {
-                            if self.page_id.is_none() {
-                                self.page_id = self
-                                    .xml_reader
-                                    .read_text(b"id", &mut self.buf)
-                                    .expect("No page ID")
-                                    .parse()
-                                    .ok();
-                            }
+                        b"id" if self.page_id.is_none() => {
+                            self.page_id = self
+                                .xml_reader
+                                .read_text(b"id", &mut self.buf)?
+                                .parse()
+                                .ok();
                         }
-                        b"ns" => {
-                            if self.page_namespace.is_none() {
-                                self.page_namespace = self
-                                    .xml_reader
-                                    .read_text(b"ns", &mut self.buf)
-                                    .expect("No namespace")
-                                    .parse()
-                                    .ok();
-                            }
+                        b"ns" if self.page_namespace.is_none() => {
+                            self.page_namespace = self
+                                .xml_reader
+                                .read_text(b"ns", &mut self.buf)?
+                                .parse()
+                                .ok();
                         }
-                        b"title" => {
-                            if self.page_title.is_none() {
-                                self.page_title = Some(
-                                    self.xml_reader
-                                        .read_text(b"title", &mut self.buf)
-                                        .expect("No title"),
-                                );
-                            }
+                        b"title" if self.page_title.is_none() => {
+                            self.page_title =
+                                Some(self.xml_reader.read_text(b"title", &mut self.buf)?);
                         }
                         _ => {}
                     },
                     Ok(_) => {}
-                    Err(e) => panic!("Error {:?}", e),
+                    Err(e) => return Err(e),
                 };
                 let _ = &self.buf.clear();
             }
@@ -159,6 +303,7 @@ impl<'a, B: std::io::BufRead> Iterator for RevisionIterator<'a, B> {
             let mut id = None;
             let mut parent_id = None;
             let mut timestamp = None;
+            let mut comment = None;
             let mut model = None;
             let mut format = None;
             let mut text = None;
@@ -170,87 +315,53 @@ impl<'a, B: std::io::BufRead> Iterator for RevisionIterator<'a, B> {
 
             // Run until we get the </revision> tag
             loop {
-                match &self.xml_reader.read_event(&mut self.buf) {
+                match self.xml_reader.read_event(&mut self.buf) {
                     Ok(Event::Start(ref e)) => match e.name() {
                         b"id" => {
-                            let text = self.xml_reader.read_text(b"id", &mut self.buf);
+                            let text = self.xml_reader.read_text(b"id", &mut self.buf)?;
                             if in_contributor {
                                 if contributor_id.is_none() {
-                                    contributor_id = text.expect("No contributor ID").parse().ok();
+                                    contributor_id = text.parse().ok();
                                 }
                             } else {
                                 if id.is_none() {
-                                    id = text.expect("No ID").parse().ok();
+                                    id = text.parse().ok();
                                 }
                             }
                         }
-                        b"username" => {
-                            if in_contributor && contributor_username.is_none() {
-                                contributor_username = Some(
-                                    self.xml_reader
-                                        .read_text(b"username", &mut self.buf)
-                                        .expect("No contributor username"),
-                                );
-                            }
+                        b"username" if in_contributor && contributor_username.is_none() => {
+                            contributor_username =
+                                Some(self.xml_reader.read_text(b"username", &mut self.buf)?);
                         }
-                        b"ip" => {
-                            if in_contributor && contributor_ip.is_none() {
-                                contributor_ip = Some(
-                                    self.xml_reader
-                                        .read_text(b"ip", &mut self.buf)
-                                        .expect("No contributor IP"),
-                                );
-                            }
+                        b"ip" if in_contributor && contributor_ip.is_none() => {
+                            contributor_ip = Some(self.xml_reader.read_text(b"ip", &mut self.buf)?);
                         }
-                        b"parentid" => {
-                            if parent_id.is_none() {
-                                parent_id = self
-                                    .xml_reader
-                                    .read_text(b"parentid", &mut self.buf)
-                                    .expect("No parent ID")
-                                    .parse()
-                                    .ok();
-                            }
+                        b"parentid" if parent_id.is_none() => {
+                            parent_id = self
+                                .xml_reader
+                                .read_text(b"parentid", &mut self.buf)?
+                                .parse()
+                                .ok();
                         }
-                        b"timestamp" => {
-                            if timestamp.is_none() {
-                                timestamp = Some(DateTime::from(
-                                    DateTime::parse_from_rfc3339(
-                                        &self
-                                            .xml_reader
-                                            .read_text(b"timestamp", &mut self.buf)
-                                            .expect("No timestamp"),
-                                    )
-                                    .expect("Bad timestamp"),
-                                ));
-                            }
+                        b"timestamp" if timestamp.is_none() => {
+                            timestamp = Some(DateTime::from(
+                                DateTime::parse_from_rfc3339(
+                                    &self.xml_reader.read_text(b"timestamp", &mut self.buf)?,
+                                )
+                                .expect("Bad timestamp"),
+                            ));
                         }
-                        b"model" => {
-                            if model.is_none() {
-                                model = Some(
-                                    self.xml_reader
-                                        .read_text(b"model", &mut self.buf)
-                                        .expect("No model"),
-                                );
-                            }
+                        b"comment" if comment.is_none() => {
+                            comment = Some(self.xml_reader.read_text(b"comment", &mut self.buf)?);
                         }
-                        b"format" => {
-                            if format.is_none() {
-                                format = Some(
-                                    self.xml_reader
-                                        .read_text(b"format", &mut self.buf)
-                                        .expect("No format"),
-                                );
-                            }
+                        b"model" if model.is_none() => {
+                            model = Some(self.xml_reader.read_text(b"model", &mut self.buf)?);
                         }
-                        b"text" => {
-                            if text.is_none() {
-                                text = Some(
-                                    self.xml_reader
-                                        .read_text(b"text", &mut self.buf)
-                                        .expect("No text"),
-                                );
-                            }
+                        b"format" if format.is_none() => {
+                            format = Some(self.xml_reader.read_text(b"format", &mut self.buf)?);
+                        }
+                        b"text" if text.is_none() => {
+                            text = Some(self.xml_reader.read_text(b"text", &mut self.buf)?);
                         }
                         b"contributor" => in_contributor = true,
                         _ => {}
@@ -261,35 +372,32 @@ impl<'a, B: std::io::BufRead> Iterator for RevisionIterator<'a, B> {
                     // End the iterator if we reach the EOF (which we shouldn't ever reach in this loop)
                     Ok(Event::Eof) => {
                         self.last_page = true;
-                        return None;
+                        return Ok(None);
                     }
                     Ok(_) => {}
-                    Err(e) => panic!("Error {:?}", e),
+                    Err(e) => return Err(e),
                 };
                 let _ = &self.buf.clear();
             }
 
-            // If we are missing any of the mandatory fields, ignore this revision
-            if id.is_none()
-                || timestamp.is_none()
-                || model.is_none()
-                || format.is_none()
-                || text.is_none()
-            {
-                continue;
-            };
-
-            return Some(Revision {
-                id: id.unwrap(),
+            let rev = PartialRevision {
+                id,
                 parent_id,
                 contributor_id,
                 contributor_username,
                 contributor_ip,
-                timestamp: timestamp.unwrap(),
-                model: model.unwrap(),
-                format: format.unwrap(),
-                body: text.unwrap(),
-            });
+                timestamp,
+                comment,
+                model,
+                format,
+                text,
+            };
+
+            // If we are missing any of the mandatory fields, ignore this revision
+            match rev.finish() {
+                Some(rev) => return Ok(Some(rev)),
+                None => continue,
+            }
         }
     }
 }
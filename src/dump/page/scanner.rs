@@ -0,0 +1,489 @@
+use super::revision::{PartialRevision, Revision};
+use chrono::DateTime;
+
+/// The kind of the most recently read tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    /// A start tag, like `<page>`.
+    Start,
+    /// An end tag, like `</page>`.
+    End,
+    /// A self-closing tag, like `<minor />`.
+    Empty,
+    /// A processing instruction, comment or declaration, like `<?xml ...?>`.
+    Other,
+}
+
+/// The page-level fields of a page, any of which may be missing.
+#[derive(Debug, Default)]
+pub(crate) struct PageHeader {
+    pub(crate) id: Option<i64>,
+    pub(crate) namespace: Option<i64>,
+    pub(crate) title: Option<String>,
+}
+
+/// A parser specialised for the MediaWiki export format.
+///
+/// Rather than producing a general stream of XML events, this searches directly for the next
+/// `<` and `>` (using [`memchr`]) and only decodes the text of the elements which make up a
+/// [`Revision`]. It makes the same assumptions about the schema as
+/// [`RevisionIterator`](super::revision::RevisionIterator), and produces the same pages.
+pub(crate) struct Scanner<B: std::io::BufRead> {
+    reader: B,
+    /// Contents of the most recently read tag (between the `<` and `>`).
+    tag: Vec<u8>,
+    /// Escaped text preceding the most recently read tag, if it was kept.
+    text: Vec<u8>,
+    /// Number of bytes consumed from `reader`.
+    position: u64,
+    /// The error which stopped reading (if any).
+    error: Option<std::io::Error>,
+}
+
+impl<B: std::io::BufRead> Scanner<B> {
+    pub(crate) fn new(reader: B) -> Scanner<B> {
+        Scanner {
+            reader,
+            tag: Vec::new(),
+            text: Vec::new(),
+            position: 0,
+            error: None,
+        }
+    }
+
+    /// Number of bytes consumed from the underlying reader.
+    pub(crate) fn position(self: &Scanner<B>) -> u64 {
+        self.position
+    }
+
+    /// Return the error which stopped reading (if any), leaving [`None`] in its place.
+    pub(crate) fn take_error(self: &mut Scanner<B>) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    /// Return the underlying reader.
+    pub(crate) fn into_inner(self: Scanner<B>) -> B {
+        self.reader
+    }
+
+    /// Read the next page, passing each of its revisions (in the order they appear) to
+    /// `on_revision`, which can stop the page from being read further. Every field of the returned
+    /// header is [`None`] once the dump is exhausted, or reading it fails (see
+    /// [`Scanner::take_error`]).
+    pub(crate) fn read_page(
+        self: &mut Scanner<B>,
+        mut on_revision: impl FnMut(Revision) -> std::ops::ControlFlow<()>,
+    ) -> PageHeader {
+        let mut header = PageHeader::default();
+        loop {
+            match self.read_tag(false) {
+                Some(TagKind::Start) => match self.name() {
+                    b"revision" => match self.read_revision() {
+                        Some(rev) => {
+                            // If we are missing any of the mandatory fields, ignore this revision
+                            if let Some(rev) = rev.finish() {
+                                if on_revision(rev).is_break() {
+                                    return header;
+                                }
+                            }
+                        }
+                        None => return header,
+                    },
+                    b"id" if header.id.is_none() => {
+                        header.id = self.read_element_text().parse().ok();
+                    }
+                    b"ns" if header.namespace.is_none() => {
+                        header.namespace = self.read_element_text().parse().ok();
+                    }
+                    b"title" if header.title.is_none() => {
+                        header.title = Some(self.read_element_text());
+                    }
+                    _ => {}
+                },
+                Some(TagKind::End) if self.name() == b"page" => return header,
+                Some(_) => {}
+                None => return header,
+            }
+        }
+    }
+
+    /// Read the fields of a revision whose `<revision>` tag was just read. Returns [`None`] if the
+    /// end of the dump is reached first.
+    fn read_revision(self: &mut Scanner<B>) -> Option<PartialRevision> {
+        let mut rev = PartialRevision::default();
+        let mut in_contributor = false;
+        loop {
+            match self.read_tag(false)? {
+                TagKind::Start => match self.name() {
+                    b"id" => {
+                        let text = self.read_element_text();
+                        if in_contributor {
+                            if rev.contributor_id.is_none() {
+                                rev.contributor_id = text.parse().ok();
+                            }
+                        } else if rev.id.is_none() {
+                            rev.id = text.parse().ok();
+                        }
+                    }
+                    b"username" if in_contributor && rev.contributor_username.is_none() => {
+                        rev.contributor_username = Some(self.read_element_text());
+                    }
+                    b"ip" if in_contributor && rev.contributor_ip.is_none() => {
+                        rev.contributor_ip = Some(self.read_element_text());
+                    }
+                    b"parentid" if rev.parent_id.is_none() => {
+                        rev.parent_id = self.read_element_text().parse().ok();
+                    }
+                    b"timestamp" if rev.timestamp.is_none() => {
+                        rev.timestamp = Some(DateTime::from(
+                            DateTime::parse_from_rfc3339(&self.read_element_text())
+                                .expect("Bad timestamp"),
+                        ));
+                    }
+                    b"comment" if rev.comment.is_none() => {
+                        rev.comment = Some(self.read_element_text());
+                    }
+                    b"model" if rev.model.is_none() => {
+                        rev.model = Some(self.read_element_text());
+                    }
+                    b"format" if rev.format.is_none() => {
+                        rev.format = Some(self.read_element_text());
+                    }
+                    b"text" if rev.text.is_none() => {
+                        rev.text = Some(self.read_element_text());
+                    }
+                    b"contributor" => in_contributor = true,
+                    _ => {}
+                },
+                TagKind::End => match self.name() {
+                    b"contributor" => in_contributor = false,
+                    b"revision" => return Some(rev),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Read up to and including the next tag, keeping the text before it in `self.text` if
+    /// `keep_text` is set. Returns [`None`] at the end of the dump, or if reading fails (when the
+    /// error is kept in `self.error`).
+    fn read_tag(self: &mut Scanner<B>, keep_text: bool) -> Option<TagKind> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.try_read_tag(keep_text) {
+            Ok(tag) => tag,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn try_read_tag(self: &mut Scanner<B>, keep_text: bool) -> std::io::Result<Option<TagKind>> {
+        self.text.clear();
+        let text = if keep_text {
+            Some(&mut self.text)
+        } else {
+            None
+        };
+        if !read_until(&mut self.reader, &mut self.position, b'<', text)? {
+            return Ok(None);
+        }
+
+        self.tag.clear();
+        if !read_until(
+            &mut self.reader,
+            &mut self.position,
+            b'>',
+            Some(&mut self.tag),
+        )? {
+            return Ok(None);
+        }
+
+        Ok(Some(match self.tag.first() {
+            Some(b'/') => TagKind::End,
+            Some(b'?') | Some(b'!') => TagKind::Other,
+            _ if self.tag.last() == Some(&b'/') => TagKind::Empty,
+            _ => TagKind::Start,
+        }))
+    }
+
+    /// The name of the most recently read tag.
+    fn name(self: &Scanner<B>) -> &[u8] {
+        let tag = self.tag.strip_prefix(b"/").unwrap_or(&self.tag);
+        let end = tag
+            .iter()
+            .position(|b| b.is_ascii_whitespace() || *b == b'/')
+            .unwrap_or(tag.len());
+        &tag[..end]
+    }
+
+    /// Read the text of the element whose start tag was just read, up to and including its end tag.
+    fn read_element_text(self: &mut Scanner<B>) -> String {
+        match self.read_tag(true) {
+            Some(TagKind::End) => unescape(&self.text),
+            // The page is abandoned once reading fails
+            None if self.error.is_some() => String::new(),
+            tag => panic!(
+                "Error: expected text, found {:?} <{}>",
+                tag,
+                String::from_utf8_lossy(&self.tag)
+            ),
+        }
+    }
+}
+
+/// Consume input up to and including `delimiter`, appending everything before it to `out` (if
+/// given) and adding the number of bytes consumed to `position`. Returns [`false`] if the end of
+/// the input is reached first.
+fn read_until<B: std::io::BufRead>(
+    reader: &mut B,
+    position: &mut u64,
+    delimiter: u8,
+    mut out: Option<&mut Vec<u8>>,
+) -> std::io::Result<bool> {
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match memchr::memchr(delimiter, buf) {
+            Some(i) => {
+                if let Some(out) = out.as_deref_mut() {
+                    out.extend_from_slice(&buf[..i]);
+                }
+                reader.consume(i + 1);
+                *position += i as u64 + 1;
+                return Ok(true);
+            }
+            None => {
+                let len = buf.len();
+                if let Some(out) = out.as_deref_mut() {
+                    out.extend_from_slice(buf);
+                }
+                reader.consume(len);
+                *position += len as u64;
+            }
+        }
+    }
+}
+
+/// Replace XML entity and character references in `raw` with the characters they represent.
+fn unescape(raw: &[u8]) -> String {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = memchr::memchr(b'&', rest) {
+        out.extend_from_slice(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = memchr::memchr(b';', rest).expect("Unterminated entity");
+        match &rest[..end] {
+            b"amp" => out.push(b'&'),
+            b"lt" => out.push(b'<'),
+            b"gt" => out.push(b'>'),
+            b"quot" => out.push(b'"'),
+            b"apos" => out.push(b'\''),
+            entity => {
+                let c = parse_char_reference(entity).unwrap_or_else(|| {
+                    panic!("Unknown entity &{};", String::from_utf8_lossy(entity))
+                });
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.extend_from_slice(rest);
+    String::from_utf8(out).expect("Dump is not valid UTF-8")
+}
+
+/// Parse a character reference like `#60` or `#x3C` (without the `&` and `;`).
+fn parse_char_reference(entity: &[u8]) -> Option<char> {
+    let entity = std::str::from_utf8(entity.strip_prefix(b"#")?).ok()?;
+    let code = match entity.strip_prefix('x') {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => entity.parse().ok()?,
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"<?xml version="1.0"?>
+<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/" xml:lang="en">
+  <siteinfo>
+    <sitename>Wikipedia</sitename>
+  </siteinfo>
+  <page>
+    <title>Fish &amp; Chips</title>
+    <ns>0</ns>
+    <id>12</id>
+    <revision>
+      <id>100</id>
+      <timestamp>2020-01-01T00:00:00Z</timestamp>
+      <contributor>
+        <username>Example</username>
+        <id>7</id>
+      </contributor>
+      <minor />
+      <comment>Created page</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="22" xml:space="preserve">&lt;b&gt;Fish&lt;/b&gt; &#38; &#x263A;</text>
+      <sha1>abc</sha1>
+    </revision>
+    <revision>
+      <id>101</id>
+      <parentid>100</parentid>
+      <timestamp>2020-01-02T00:00:00Z</timestamp>
+      <contributor>
+        <ip>127.0.0.1</ip>
+      </contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="4">Fish</text>
+    </revision>
+    <revision>
+      <id>103</id>
+      <timestamp>2020-01-04T00:00:00Z</timestamp>
+      <contributor deleted="deleted" />
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="0" deleted="deleted" />
+    </revision>
+  </page>
+  <page>
+    <title>Talk:Chips</title>
+    <ns>1</ns>
+    <id>13</id>
+    <revision>
+      <id>102</id>
+      <timestamp>2020-01-03T00:00:00Z</timestamp>
+      <contributor><username>Other</username><id>8</id></contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="5">Chips</text>
+    </revision>
+  </page>
+</mediawiki>
+"#;
+
+    fn read_all(scanner: &mut Scanner<&[u8]>) -> Vec<(PageHeader, Vec<Revision>)> {
+        let mut pages = Vec::new();
+        loop {
+            let mut revisions = Vec::new();
+            let header = scanner.read_page(|rev| {
+                revisions.push(rev);
+                std::ops::ControlFlow::Continue(())
+            });
+            if header.id.is_none() {
+                return pages;
+            }
+            pages.push((header, revisions));
+        }
+    }
+
+    #[test]
+    fn unescape_replaces_entities() {
+        assert_eq!(unescape(b"plain text"), "plain text");
+        assert_eq!(
+            unescape(b"&lt;a href=&quot;x&quot;&gt; &amp;&apos;"),
+            "<a href=\"x\"> &'"
+        );
+        assert_eq!(unescape(b"&#60;&#x3C;&#x263a;"), "<<\u{263A}");
+        assert_eq!(unescape("naïve &amp; café".as_bytes()), "naïve & café");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown entity")]
+    fn unescape_rejects_unknown_entities() {
+        unescape(b"&nbsp;");
+    }
+
+    #[test]
+    fn parse_char_reference_accepts_decimal_and_hex() {
+        assert_eq!(parse_char_reference(b"#60"), Some('<'));
+        assert_eq!(parse_char_reference(b"#x3C"), Some('<'));
+        assert_eq!(parse_char_reference(b"#x1F600"), Some('\u{1F600}'));
+        assert_eq!(parse_char_reference(b"60"), None);
+        assert_eq!(parse_char_reference(b"#xZZ"), None);
+        // Surrogates are not characters
+        assert_eq!(parse_char_reference(b"#xD800"), None);
+    }
+
+    #[test]
+    fn scanner_reads_pages_and_revisions() {
+        let mut scanner = Scanner::new(DUMP.as_bytes());
+        let pages = read_all(&mut scanner);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(scanner.position(), DUMP.len() as u64);
+
+        let (header, revisions) = &pages[0];
+        assert_eq!(header.id, Some(12));
+        assert_eq!(header.namespace, Some(0));
+        assert_eq!(header.title.as_deref(), Some("Fish & Chips"));
+        // The revision whose text was deleted is kept with an empty body
+        assert_eq!(revisions.len(), 3);
+        assert_eq!((revisions[2].id(), revisions[2].body().as_str()), (103, ""));
+
+        let first = &revisions[0];
+        assert_eq!(first.id(), 100);
+        assert_eq!(first.parent_id(), None);
+        assert_eq!(first.contributor_id(), Some(7));
+        assert_eq!(
+            first.contributor_username().map(String::as_str),
+            Some("Example")
+        );
+        assert_eq!(first.comment().map(String::as_str), Some("Created page"));
+        assert_eq!(first.body(), "<b>Fish</b> & \u{263A}");
+
+        let second = &revisions[1];
+        assert_eq!(second.parent_id(), Some(100));
+        assert_eq!(
+            second.contributor_ip().map(String::as_str),
+            Some("127.0.0.1")
+        );
+        assert_eq!(second.contributor_username(), None);
+        assert_eq!(second.body(), "Fish");
+
+        let (header, revisions) = &pages[1];
+        assert_eq!((header.id, header.namespace), (Some(13), Some(1)));
+        assert_eq!(revisions[0].body(), "Chips");
+    }
+
+    #[test]
+    fn scanner_matches_xml_parser() {
+        use crate::dump::{PageIterator, Parser, ScanOptions};
+        let read = |parser| {
+            PageIterator::from_reader_with(DUMP.as_bytes(), ScanOptions::new().with_parser(parser))
+                .map(|page| {
+                    let revisions: Vec<_> = page
+                        .revisions()
+                        .iter()
+                        .map(|rev| (rev.id(), rev.contributor_id(), rev.body().clone()))
+                        .collect();
+                    (page.id(), page.namespace(), page.title().clone(), revisions)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(read(Parser::Fast), read(Parser::Xml));
+    }
+
+    #[test]
+    fn scanner_stops_reading_when_asked() {
+        let mut scanner = Scanner::new(DUMP.as_bytes());
+        let mut count = 0;
+        let header = scanner.read_page(|_| {
+            count += 1;
+            std::ops::ControlFlow::Break(())
+        });
+        assert_eq!(count, 1);
+        assert_eq!(header.id, Some(12));
+    }
+}
@@ -0,0 +1,52 @@
+/// Reads the decompressed contents of a file from a `7z` process.
+///
+/// The process is killed when the reader is dropped, so that abandoning a dump part way through
+/// does not leave it running. Reaching the end of the output fails if `7z` did not exit
+/// successfully (for example because the archive is truncated).
+pub struct SevenZipReader {
+    child: std::process::Child,
+    stdout: std::process::ChildStdout,
+    /// Whether the process has exited successfully.
+    finished: bool,
+}
+
+impl SevenZipReader {
+    /// Start decompressing the file at `path`. This requires that the `7z` command be installed
+    /// and on the `$PATH`.
+    pub fn new(path: &std::path::Path) -> std::io::Result<SevenZipReader> {
+        let mut child = std::process::Command::new("7z")
+            .arg("x")
+            .arg("-so")
+            .arg(path)
+            .stdout(std::process::Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("7z stdout is piped");
+        Ok(SevenZipReader {
+            child,
+            stdout,
+            finished: false,
+        })
+    }
+}
+
+impl std::io::Read for SevenZipReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.finished {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(std::io::Error::other(format!("7z failed ({})", status)));
+            }
+            self.finished = true;
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for SevenZipReader {
+    fn drop(&mut self) {
+        // The process may already have exited, in which case this fails harmlessly
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,377 @@
+use super::page::PageSource;
+use super::{Dump, Page, ScanOptions};
+
+/// Shared counters describing the progress of reading pages from dumps.
+///
+/// A [`Progress`] is given to a [`ScanOptions`](super::ScanOptions), and clones of it share the
+/// same counters, so it can be inspected with [`Progress::report`] while a scan is running (or
+/// report itself periodically with [`Progress::with_callback`]).
+///
+/// The `7z` process reading a dump does not report how much of the compressed file it has
+/// consumed, so this is estimated from the ID of the last page read and the range of page IDs
+/// covered by the dump.
+#[derive(Clone)]
+pub struct Progress {
+    state: std::sync::Arc<ProgressState>,
+}
+
+type ProgressCallback = dyn Fn(&ProgressReport) + Send + Sync;
+
+struct ProgressState {
+    start: std::time::Instant,
+    pages: std::sync::atomic::AtomicU64,
+    revisions: std::sync::atomic::AtomicU64,
+    dumps: std::sync::Mutex<std::collections::HashMap<std::path::PathBuf, DumpState>>,
+    callback: Option<(std::time::Duration, Box<ProgressCallback>)>,
+    last_callback: std::sync::Mutex<std::time::Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct DumpState {
+    page_id_range: Option<(i64, i64)>,
+    file_size: u64,
+    bytes_done: u64,
+    /// Number of iterators currently reading the dump.
+    readers: usize,
+    completed: bool,
+}
+
+/// Counts of the pages and revisions read with a [`ScanOptions`] and its clones, which are kept
+/// whether or not there is a [`Progress`] (for [`DumpStore::manifest`](super::DumpStore::manifest)).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReadCounts {
+    pages: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    revisions: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl ReadCounts {
+    fn page_read(self: &ReadCounts, page: &Page) {
+        self.pages
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.revisions.fetch_add(
+            page.revisions().len() as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+    }
+
+    /// Pages read so far.
+    pub(crate) fn pages(self: &ReadCounts) -> u64 {
+        self.pages.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Revisions read so far.
+    pub(crate) fn revisions(self: &ReadCounts) -> u64 {
+        self.revisions.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// A snapshot of a [`Progress`].
+#[derive(Debug, Clone)]
+pub struct ProgressReport {
+    /// Time since the [`Progress`] was created.
+    pub elapsed: std::time::Duration,
+    /// Estimated number of compressed bytes consumed so far. This is not measured, but
+    /// extrapolated from the page IDs read (see [`Progress`]).
+    pub estimated_bytes_done: u64,
+    /// Total size of the compressed dump files.
+    pub bytes_total: u64,
+    /// Number of pages read.
+    pub pages: u64,
+    /// Number of revisions read.
+    pub revisions: u64,
+    /// Number of dumps which have been read completely.
+    pub dumps_completed: usize,
+    /// Number of dumps to be read.
+    pub dumps_total: usize,
+    /// Estimated fraction of each dump currently being read. Dumps which were abandoned before
+    /// they were read completely are not included.
+    pub in_progress: Vec<(std::path::PathBuf, f64)>,
+}
+
+impl ProgressReport {
+    /// Estimated fraction of the scan which has been completed.
+    pub fn fraction(self: &ProgressReport) -> f64 {
+        if self.bytes_total == 0 {
+            0.0
+        } else {
+            self.estimated_bytes_done as f64 / self.bytes_total as f64
+        }
+    }
+
+    /// Estimated time until the scan is completed, assuming the current rate continues.
+    pub fn eta(self: &ProgressReport) -> Option<std::time::Duration> {
+        if self.estimated_bytes_done == 0 {
+            return None;
+        }
+        let remaining = self.bytes_total.saturating_sub(self.estimated_bytes_done) as f64;
+        Some(
+            self.elapsed
+                .mul_f64(remaining / self.estimated_bytes_done as f64),
+        )
+    }
+}
+
+impl std::fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:5.1}% | {}/{} dumps | {} pages | {} revisions | ~{:.0} MB/s (estimated) | ETA {}",
+            100.0 * self.fraction(),
+            self.dumps_completed,
+            self.dumps_total,
+            self.pages,
+            self.revisions,
+            self.estimated_bytes_done as f64 / self.elapsed.as_secs_f64().max(1e-3) / 1e6,
+            self.eta()
+                .map(format_duration)
+                .unwrap_or_else(|| "unknown".to_string())
+        )
+    }
+}
+
+/// Format a duration as hours, minutes and seconds.
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl Default for Progress {
+    fn default() -> Progress {
+        Progress::new()
+    }
+}
+
+impl Progress {
+    /// Create a [`Progress`] with all counters at zero.
+    pub fn new() -> Progress {
+        Progress::from_callback(None)
+    }
+
+    /// Create a [`Progress`] which calls `callback` with a report at most once every `interval`
+    /// (and whenever a dump is completed).
+    pub fn with_callback(
+        interval: std::time::Duration,
+        callback: impl Fn(&ProgressReport) + Send + Sync + 'static,
+    ) -> Progress {
+        Progress::from_callback(Some((interval, Box::new(callback))))
+    }
+
+    fn from_callback(callback: Option<(std::time::Duration, Box<ProgressCallback>)>) -> Progress {
+        let now = std::time::Instant::now();
+        Progress {
+            state: std::sync::Arc::new(ProgressState {
+                start: now,
+                pages: Default::default(),
+                revisions: Default::default(),
+                dumps: Default::default(),
+                callback,
+                last_callback: std::sync::Mutex::new(now),
+            }),
+        }
+    }
+
+    /// Return a snapshot of the progress so far.
+    pub fn report(self: &Progress) -> ProgressReport {
+        let dumps = self.state.dumps.lock().unwrap();
+        let mut in_progress: Vec<_> = dumps
+            .iter()
+            .filter(|(_, d)| d.readers > 0 && !d.completed)
+            .map(|(path, d)| {
+                (
+                    path.clone(),
+                    d.bytes_done as f64 / d.file_size.max(1) as f64,
+                )
+            })
+            .collect();
+        in_progress.sort_by(|a, b| a.0.cmp(&b.0));
+
+        ProgressReport {
+            elapsed: self.state.start.elapsed(),
+            estimated_bytes_done: dumps.values().map(|d| d.bytes_done).sum(),
+            bytes_total: dumps.values().map(|d| d.file_size).sum(),
+            pages: self.state.pages.load(std::sync::atomic::Ordering::Relaxed),
+            revisions: self
+                .state
+                .revisions
+                .load(std::sync::atomic::Ordering::Relaxed),
+            dumps_completed: dumps.values().filter(|d| d.completed).count(),
+            dumps_total: dumps.len(),
+            in_progress,
+        }
+    }
+
+    /// Include `dumps` in the totals, before any of them are started.
+    pub(crate) fn expect_dumps(self: &Progress, dumps: &[Dump]) {
+        let mut states = self.state.dumps.lock().unwrap();
+        for dump in dumps {
+            states
+                .entry(dump.path().clone())
+                .or_insert_with(|| DumpState {
+                    page_id_range: dump.page_id_range(),
+                    file_size: dump.file_size().unwrap_or(0),
+                    bytes_done: 0,
+                    readers: 0,
+                    completed: false,
+                });
+        }
+    }
+
+    fn page_read(self: &Progress, path: &std::path::Path, page: &Page) {
+        self.state
+            .pages
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.state.revisions.fetch_add(
+            page.revisions().len() as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+
+        if let Some(state) = self.state.dumps.lock().unwrap().get_mut(path) {
+            // Without a range of page IDs, progress is only recorded once the dump is completed
+            if let Some((min_id, max_id)) = state.page_id_range {
+                let fraction =
+                    (page.id() - min_id + 1) as f64 / (max_id - min_id + 1).max(1) as f64;
+                let bytes_done = (fraction.clamp(0.0, 1.0) * state.file_size as f64) as u64;
+                state.bytes_done = state.bytes_done.max(bytes_done);
+            }
+        }
+
+        self.maybe_callback(false);
+    }
+
+    fn dump_completed(self: &Progress, path: &std::path::Path) {
+        if let Some(state) = self.state.dumps.lock().unwrap().get_mut(path) {
+            state.bytes_done = state.file_size;
+            state.completed = true;
+        }
+        self.maybe_callback(true);
+    }
+
+    fn maybe_callback(self: &Progress, force: bool) {
+        if let Some((interval, callback)) = &self.state.callback {
+            // Skip this report if another thread is already reporting
+            let mut last_callback = match self.state.last_callback.try_lock() {
+                Ok(last_callback) => last_callback,
+                Err(_) => return,
+            };
+            if force || last_callback.elapsed() >= *interval {
+                *last_callback = std::time::Instant::now();
+                callback(&self.report());
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Progress {{ report: {:?} }}", self.report())
+    }
+}
+
+/// An iterator over the pages of a dump which updates the [`ReadCounts`] and [`Progress`] (if any)
+/// of its [`ScanOptions`].
+pub(crate) struct TrackedPages<I: PageSource> {
+    pages: I,
+    options: ScanOptions,
+    path: std::path::PathBuf,
+    /// The error which stopped reading the dump (if any).
+    error: Option<std::io::Error>,
+}
+
+impl<I: PageSource> TrackedPages<I> {
+    pub(crate) fn new(pages: I, dump: &Dump, options: &ScanOptions) -> TrackedPages<I> {
+        if let Some(progress) = options.progress() {
+            progress.expect_dumps(std::slice::from_ref(dump));
+            if let Some(state) = progress.state.dumps.lock().unwrap().get_mut(dump.path()) {
+                state.readers += 1;
+            }
+        }
+        TrackedPages {
+            pages,
+            options: options.clone(),
+            path: dump.path().clone(),
+            error: None,
+        }
+    }
+}
+
+impl<I: PageSource> Iterator for TrackedPages<I> {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        let page = self.pages.next();
+        if page.is_none() && self.error.is_none() {
+            self.error = self.pages.take_error();
+        }
+        if let Some(page) = &page {
+            self.options.read_counts().page_read(page);
+        }
+        if let Some(progress) = self.options.progress() {
+            match &page {
+                Some(page) => progress.page_read(&self.path, page),
+                // A cancelled or failed dump has not been read completely
+                None if self.options.is_cancelled() || self.error.is_some() => {}
+                None => progress.dump_completed(&self.path),
+            }
+        }
+        page
+    }
+}
+
+impl<I: PageSource> Drop for TrackedPages<I> {
+    fn drop(&mut self) {
+        // The dump is no longer being read, whether or not it was read completely
+        if let Some(progress) = self.options.progress() {
+            if let Some(state) = progress.state.dumps.lock().unwrap().get_mut(&self.path) {
+                state.readers = state.readers.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl<I: PageSource> PageSource for TrackedPages<I> {
+    fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::PageIterator;
+
+    const DUMP: &str = "<mediawiki>
+  <page><title>A</title><ns>0</ns><id>1</id></page>
+  <page><title>B</title><ns>0</ns><id>6</id></page>
+</mediawiki>";
+
+    fn tracked(progress: &Progress) -> TrackedPages<PageIterator<&'static [u8]>> {
+        let dump = Dump::new("enwiki-20230101-pages-meta-history1.xml-p1p10.7z".into()).unwrap();
+        let options = ScanOptions::new().with_progress(progress.clone());
+        TrackedPages::new(PageIterator::from_reader(DUMP.as_bytes()), &dump, &options)
+    }
+
+    #[test]
+    fn abandoned_dumps_are_not_in_progress() {
+        let progress = Progress::new();
+        let mut pages = tracked(&progress);
+        assert_eq!(pages.next().map(|p| p.id()), Some(1));
+        let report = progress.report();
+        assert_eq!(report.in_progress.len(), 1);
+        assert_eq!(report.pages, 1);
+
+        drop(pages);
+        let report = progress.report();
+        assert!(report.in_progress.is_empty());
+        assert_eq!((report.dumps_completed, report.dumps_total), (0, 1));
+    }
+
+    #[test]
+    fn finished_dumps_are_completed() {
+        let progress = Progress::new();
+        assert_eq!(tracked(&progress).count(), 2);
+        let report = progress.report();
+        assert!(report.in_progress.is_empty());
+        assert_eq!((report.dumps_completed, report.dumps_total), (1, 1));
+        assert_eq!(report.pages, 2);
+    }
+}
@@ -0,0 +1,154 @@
+use super::{Dump, Page, ScanOptions};
+
+/// Controls how dumps are read concurrently by [`DumpStore::scheduled_pages`](super::DumpStore::scheduled_pages).
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    max_decompressors: usize,
+    max_in_flight_pages: usize,
+    page_id_order: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Schedule {
+            max_decompressors: threads,
+            max_in_flight_pages: 4 * threads,
+            page_id_order: false,
+        }
+    }
+}
+
+impl Schedule {
+    /// Create a [`Schedule`] which runs one decompressor per available thread.
+    pub fn new() -> Schedule {
+        Schedule::default()
+    }
+
+    /// Limit the number of dumps (and so `7z` processes) read at the same time.
+    pub fn with_max_decompressors(mut self: Schedule, max_decompressors: usize) -> Schedule {
+        self.max_decompressors = max_decompressors.max(1);
+        self
+    }
+
+    /// Limit the number of pages which have been parsed but not yet consumed.
+    pub fn with_max_in_flight_pages(mut self: Schedule, max_in_flight_pages: usize) -> Schedule {
+        self.max_in_flight_pages = max_in_flight_pages.max(1);
+        self
+    }
+
+    /// Yield pages in order of their page IDs. Otherwise, the largest dumps are started first and
+    /// pages are yielded as soon as they are parsed.
+    pub fn with_page_id_order(mut self: Schedule, page_id_order: bool) -> Schedule {
+        self.page_id_order = page_id_order;
+        self
+    }
+
+    /// The maximum number of dumps read at the same time.
+    pub fn max_decompressors(self: &Schedule) -> usize {
+        self.max_decompressors
+    }
+
+    /// The maximum number of pages which have been parsed but not yet consumed.
+    pub fn max_in_flight_pages(self: &Schedule) -> usize {
+        self.max_in_flight_pages
+    }
+
+    /// Whether pages are yielded in order of their page IDs.
+    pub fn page_id_order(self: &Schedule) -> bool {
+        self.page_id_order
+    }
+}
+
+/// An iterator over the pages of several dumps, which are read on background threads according
+/// to a [`Schedule`].
+///
+/// Dropping the iterator stops the background threads once they finish parsing their current page.
+pub struct ScheduledPages {
+    /// Receivers which are read in turn until each is exhausted.
+    receivers: std::collections::VecDeque<std::sync::mpsc::Receiver<Page>>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl ScheduledPages {
+    pub(crate) fn new(
+        dumps: &[Dump],
+        options: &ScanOptions,
+        schedule: &Schedule,
+    ) -> ScheduledPages {
+        let mut dumps = dumps.to_vec();
+        let worker_count = schedule.max_decompressors.min(dumps.len());
+
+        // Each dump is sent to the receiver at the same position (or the only receiver)
+        let (senders, receivers): (Vec<_>, std::collections::VecDeque<_>) = if schedule
+            .page_id_order
+        {
+            dumps.sort_by_key(Dump::page_id_range);
+            let capacity = (schedule.max_in_flight_pages / worker_count.max(1)).max(1);
+            (0..dumps.len())
+                .map(|_| std::sync::mpsc::sync_channel(capacity))
+                .unzip()
+        } else {
+            dumps.sort_by_cached_key(|d| std::cmp::Reverse(d.file_size().unwrap_or(0)));
+            let (sender, receiver) = std::sync::mpsc::sync_channel(schedule.max_in_flight_pages);
+            (vec![sender], [receiver].into())
+        };
+
+        let queue = std::sync::Arc::new(std::sync::Mutex::new(
+            dumps
+                .into_iter()
+                .zip(senders.into_iter().cycle())
+                .collect::<std::collections::VecDeque<_>>(),
+        ));
+
+        let workers = (0..worker_count)
+            .map(|_| {
+                let queue = queue.clone();
+                let options = options.clone();
+                std::thread::spawn(move || loop {
+                    if options.is_cancelled() {
+                        return;
+                    }
+                    let next = queue.lock().unwrap().pop_front();
+                    let (dump, sender) = match next {
+                        Some(next) => next,
+                        None => return,
+                    };
+                    for page in dump.pages_with(&options) {
+                        // Stop if the pages are no longer wanted
+                        if sender.send(page).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        ScheduledPages { receivers, workers }
+    }
+}
+
+impl Iterator for ScheduledPages {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        while let Some(receiver) = self.receivers.front() {
+            match receiver.recv() {
+                Ok(page) => return Some(page),
+                Err(_) => {
+                    self.receivers.pop_front();
+                }
+            }
+        }
+
+        // Propagate any panic from parsing a dump
+        for worker in self.workers.drain(..) {
+            if let Err(e) = worker.join() {
+                std::panic::resume_unwind(e);
+            }
+        }
+        None
+    }
+}
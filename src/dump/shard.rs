@@ -0,0 +1,239 @@
+use super::{Dump, DumpStore};
+
+/// One of a fixed number of deterministic partitions of a [`DumpStore`], written `k/N` for the
+/// `k`-th of `N` shards (counting from zero).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shard {
+    index: usize,
+    count: usize,
+}
+
+/// How a [`DumpStore`] is divided into [`Shard`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShardMode {
+    /// Each dump belongs to a single shard, and dumps are assigned so that the total compressed
+    /// size of each shard is roughly equal.
+    #[default]
+    Dumps,
+    /// Every shard reads every dump, but only keeps the pages whose hashed page ID belongs to it.
+    /// This balances the work more evenly, at the cost of each dump being decompressed by every
+    /// shard.
+    PageIds,
+}
+
+impl Shard {
+    /// Create the `index`-th of `count` shards, or [`None`] if `index` is not less than `count`.
+    pub fn new(index: usize, count: usize) -> Option<Shard> {
+        if index < count {
+            Some(Shard { index, count })
+        } else {
+            None
+        }
+    }
+
+    /// The position of this shard (counting from zero).
+    pub fn index(self: &Shard) -> usize {
+        self.index
+    }
+
+    /// The total number of shards.
+    pub fn count(self: &Shard) -> usize {
+        self.count
+    }
+
+    /// Returns [`true`] when the page with the specified ID belongs to this shard under
+    /// [`ShardMode::PageIds`].
+    pub fn contains_page_id(self: &Shard, page_id: i64) -> bool {
+        splitmix64(page_id as u64) % self.count as u64 == self.index as u64
+    }
+
+    /// Return the dumps which belong to this shard under [`ShardMode::Dumps`].
+    ///
+    /// Dumps are assigned largest first to the shard with the smallest total size so far. This only
+    /// depends on the names and sizes of the dump files, so every machine sharing the same files
+    /// makes the same assignment.
+    pub fn select_dumps(self: &Shard, dumps: &[Dump]) -> Vec<Dump> {
+        let mut dumps: Vec<(u64, &Dump)> = dumps
+            .iter()
+            .map(|d| (d.file_size().unwrap_or(0), d))
+            .collect();
+        dumps.sort_by(|(a_size, a), (b_size, b)| {
+            b_size
+                .cmp(a_size)
+                .then_with(|| a.path().file_name().cmp(&b.path().file_name()))
+        });
+
+        let mut loads = vec![0; self.count];
+        let mut selected = Vec::new();
+        for (size, dump) in dumps {
+            let (lightest, _) = loads
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, load)| (**load, *i))
+                .unwrap();
+            loads[lightest] += size;
+            if lightest == self.index {
+                selected.push(dump.clone());
+            }
+        }
+        selected.sort_by_key(Dump::page_id_range);
+        selected
+    }
+}
+
+/// A fixed, well-mixed hash, so that shards do not depend on the platform or Rust version.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// The error returned when a [`Shard`] cannot be parsed from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseShardError(String);
+
+impl std::fmt::Display for ParseShardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid shard {:?} (expected k/N with 0 <= k < N)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseShardError {}
+
+impl std::str::FromStr for Shard {
+    type Err = ParseShardError;
+
+    fn from_str(s: &str) -> Result<Shard, ParseShardError> {
+        let err = || ParseShardError(s.to_string());
+        let (index, count) = s.split_once('/').ok_or_else(err)?;
+        Shard::new(
+            index.trim().parse().map_err(|_| err())?,
+            count.trim().parse().map_err(|_| err())?,
+        )
+        .ok_or_else(err)
+    }
+}
+
+impl DumpStore {
+    /// Return a [`DumpStore`] containing only the part of this one which belongs to `shard`.
+    /// Running the same analysis on every shard (for example, on separate machines) covers every
+    /// page exactly once.
+    pub fn shard(self: &DumpStore, shard: Shard, mode: ShardMode) -> DumpStore {
+        match mode {
+            ShardMode::Dumps => DumpStore {
+                dumps: shard.select_dumps(self.dumps()),
+                ..self.clone()
+            },
+            ShardMode::PageIds => DumpStore {
+                options: self.options().clone().with_page_shard(shard),
+                ..self.clone()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create dump files of the given sizes in a new temporary directory.
+    fn dumps_of_sizes(name: &str, sizes: &[usize]) -> Vec<Dump> {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| {
+                let path = dir.join(format!(
+                    "enwiki-20230101-pages-meta-history{}.xml-p{}p{}.7z",
+                    i + 1,
+                    i * 100 + 1,
+                    (i + 1) * 100
+                ));
+                std::fs::write(&path, vec![0; size]).unwrap();
+                Dump::new(path).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shards_parse_and_display() {
+        let shard: Shard = "2/5".parse().unwrap();
+        assert_eq!((shard.index(), shard.count()), (2, 5));
+        assert_eq!(shard.to_string(), "2/5");
+        assert_eq!(" 0 / 1 ".parse(), Ok(Shard::new(0, 1).unwrap()));
+        for invalid in ["5/5", "1", "a/2", "1/b", "-1/2", "0/0", ""] {
+            assert_eq!(
+                invalid.parse::<Shard>(),
+                Err(ParseShardError(invalid.to_string())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn page_ids_belong_to_exactly_one_shard() {
+        let shards: Vec<Shard> = (0..3).map(|i| Shard::new(i, 3).unwrap()).collect();
+        for page_id in 0..1000 {
+            let owners = shards
+                .iter()
+                .filter(|s| s.contains_page_id(page_id))
+                .count();
+            assert_eq!(owners, 1);
+        }
+        // Shards are roughly even
+        let first = (0..3000)
+            .filter(|&id| shards[0].contains_page_id(id))
+            .count();
+        assert!((900..1100).contains(&first), "{}", first);
+    }
+
+    #[test]
+    fn select_dumps_balances_sizes() {
+        let dumps = dumps_of_sizes("select-dumps", &[50, 40, 30, 20, 10, 10]);
+        let selected: Vec<Vec<String>> = (0..2)
+            .map(|i| {
+                Shard::new(i, 2)
+                    .unwrap()
+                    .select_dumps(&dumps)
+                    .iter()
+                    .map(|d| format!("{:?}", d.page_id_range().unwrap()))
+                    .collect()
+            })
+            .collect();
+        // Largest first to the lightest shard: 50 -> 0, 40 -> 1, 30 -> 1, 20 -> 0, 10 -> 0, 10 -> 1
+        assert_eq!(selected[0], ["(1, 100)", "(301, 400)", "(401, 500)"]);
+        assert_eq!(selected[1], ["(101, 200)", "(201, 300)", "(501, 600)"]);
+        std::fs::remove_dir_all(dumps[0].path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn select_dumps_covers_every_dump_once() {
+        let dumps = dumps_of_sizes("select-every-dump", &[7, 7, 7, 3, 12, 1, 0]);
+        let mut seen: Vec<_> = (0..4)
+            .flat_map(|i| Shard::new(i, 4).unwrap().select_dumps(&dumps))
+            .map(|d| d.path().clone())
+            .collect();
+        seen.sort();
+        let mut all: Vec<_> = dumps.iter().map(|d| d.path().clone()).collect();
+        all.sort();
+        assert_eq!(seen, all);
+        std::fs::remove_dir_all(dumps[0].path().parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use super::page::sevenzip::SevenZipReader;
+use super::Dump;
+use quick_xml::events::Event;
+
+/// Information about the wiki from the `<siteinfo>` element at the start of a dump.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SiteInfo {
+    /// Name of the site, like `Wikipedia`.
+    pub sitename: String,
+    /// Name of the wiki's database, like `enwiki`.
+    pub dbname: String,
+    /// URL of the main page.
+    pub base: String,
+    /// Version of MediaWiki which produced the dump.
+    pub generator: String,
+    /// Whether the first letter of titles is capitalized (`first-letter`) or not (`case-sensitive`).
+    pub case: String,
+    /// Names of the namespaces by key. The name of the main namespace (0) is empty.
+    pub namespaces: std::collections::BTreeMap<i64, String>,
+}
+
+impl SiteInfo {
+    /// Read the `<siteinfo>` element from the start of a (decompressed) dump, returning [`None`]
+    /// if a `<page>` or the end of the dump is reached first.
+    pub fn from_reader<B: std::io::BufRead>(bufreader: B) -> Option<SiteInfo> {
+        let mut xml_reader = quick_xml::Reader::from_reader(bufreader);
+        let mut buf = Vec::new();
+        let mut info = SiteInfo::default();
+        let mut in_siteinfo = false;
+        loop {
+            match xml_reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name() {
+                    b"siteinfo" => in_siteinfo = true,
+                    b"page" => return None,
+                    b"namespace" if in_siteinfo => {
+                        let key = namespace_key(e);
+                        let name = xml_reader
+                            .read_text(b"namespace", &mut Vec::new())
+                            .expect("No namespace name");
+                        info.namespaces.insert(key?, name);
+                    }
+                    name if in_siteinfo => {
+                        let field = match name {
+                            b"sitename" => Some(&mut info.sitename),
+                            b"dbname" => Some(&mut info.dbname),
+                            b"base" => Some(&mut info.base),
+                            b"generator" => Some(&mut info.generator),
+                            b"case" => Some(&mut info.case),
+                            _ => None,
+                        };
+                        if let Some(field) = field {
+                            *field = xml_reader
+                                .read_text(name, &mut Vec::new())
+                                .expect("No siteinfo text");
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Empty(ref e)) if in_siteinfo && e.name() == b"namespace" => {
+                    info.namespaces.insert(namespace_key(e)?, String::new());
+                }
+                Ok(Event::End(ref e)) if e.name() == b"siteinfo" => return Some(info),
+                Ok(Event::Eof) => return None,
+                Ok(_) => {}
+                Err(e) => panic!("Error {:?}", e),
+            }
+            buf.clear();
+        }
+    }
+
+    /// Return the name of a namespace (if it is known).
+    pub fn namespace_name(self: &SiteInfo, key: i64) -> Option<&str> {
+        self.namespaces.get(&key).map(String::as_str)
+    }
+}
+
+/// Return the value of the `key` attribute of a `<namespace>` element.
+fn namespace_key(element: &quick_xml::events::BytesStart) -> Option<i64> {
+    element
+        .attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key == b"key")
+        .and_then(|a| std::str::from_utf8(&a.value).ok()?.parse().ok())
+}
+
+impl Dump {
+    /// Read the [`SiteInfo`] from the start of the dump. Only the beginning of the dump is
+    /// decompressed.
+    pub fn site_info(self: &Dump) -> std::io::Result<SiteInfo> {
+        let reader = std::io::BufReader::new(SevenZipReader::new(self.path())?);
+        SiteInfo::from_reader(reader).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("No siteinfo in {}", self.path().display()),
+            )
+        })
+    }
+}
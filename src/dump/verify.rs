@@ -0,0 +1,464 @@
+use super::{Dump, DumpStore};
+use rayon::prelude::*;
+use sha1::Digest;
+
+/// The name of the file in which Wikimedia records the status of each job in a dump run.
+const DUMP_STATUS_FILE: &str = "dumpstatus.json";
+
+/// What to do when the dumps in a [`DumpStore`] do not match the published dump metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerificationPolicy {
+    /// Do not verify the dumps.
+    #[default]
+    Off,
+    /// Allow the dumps to be read, leaving the caller to report the problems.
+    Warn,
+    /// Return an error instead of the [`DumpStore`].
+    Refuse,
+}
+
+/// How the dumps in a [`DumpStore`] are verified by [`DumpStore::verified`].
+#[derive(Debug, Clone, Default)]
+pub struct Verification {
+    policy: VerificationPolicy,
+    checksums: bool,
+}
+
+impl Verification {
+    /// Create a [`Verification`] which applies `policy`, checking file names and sizes only.
+    pub fn new(policy: VerificationPolicy) -> Verification {
+        Verification {
+            policy,
+            checksums: false,
+        }
+    }
+
+    /// Also compare the MD5 and SHA-1 checksums of the dumps with the published checksums. This
+    /// requires every dump to be read in full.
+    pub fn with_checksums(mut self: Verification, checksums: bool) -> Verification {
+        self.checksums = checksums;
+        self
+    }
+
+    /// The [`VerificationPolicy`] applied.
+    pub fn policy(self: &Verification) -> VerificationPolicy {
+        self.policy
+    }
+
+    /// Whether checksums are compared.
+    pub fn checksums(self: &Verification) -> bool {
+        self.checksums
+    }
+}
+
+/// A difference between a dump file and the published metadata for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Name of the dump file.
+    pub file_name: String,
+    /// The property which differs: `"size"`, `"md5"` or `"sha1"`.
+    pub check: &'static str,
+    /// The published value.
+    pub expected: String,
+    /// The value for the file on disk.
+    pub actual: String,
+}
+
+/// A job in `dumpstatus.json` which had not finished, and which produced files of a kind in the
+/// [`DumpStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteJob {
+    /// Name of the job, like `metahistory7zdump`.
+    pub name: String,
+    /// Status of the job, like `in-progress` or `failed`.
+    pub status: String,
+}
+
+/// The result of comparing the dumps in a [`DumpStore`] with the published dump metadata.
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    /// The metadata files which were read.
+    pub sources: Vec<std::path::PathBuf>,
+    /// Names of published files (of the kinds in the [`DumpStore`]) which are not in the store.
+    pub missing: Vec<String>,
+    /// Dumps in the store which are not listed in any metadata file.
+    pub extra: Vec<std::path::PathBuf>,
+    /// Differences between dumps and their published sizes or checksums.
+    pub mismatched: Vec<Mismatch>,
+    /// Jobs which had not finished when the metadata was written.
+    pub incomplete_jobs: Vec<IncompleteJob>,
+}
+
+impl VerificationReport {
+    /// Returns [`true`] if no problems were found.
+    pub fn is_ok(self: &VerificationReport) -> bool {
+        self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.mismatched.is_empty()
+            && self.incomplete_jobs.is_empty()
+    }
+}
+
+impl std::fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_ok() {
+            return write!(f, "All dumps verified");
+        }
+        for job in &self.incomplete_jobs {
+            writeln!(f, "Incomplete job: {} ({})", job.name, job.status)?;
+        }
+        for file_name in &self.missing {
+            writeln!(f, "Missing: {}", file_name)?;
+        }
+        for path in &self.extra {
+            writeln!(f, "Not in dump metadata: {}", path.display())?;
+        }
+        for mismatch in &self.mismatched {
+            writeln!(
+                f,
+                "Wrong {}: {} (expected {}, found {})",
+                mismatch.check, mismatch.file_name, mismatch.expected, mismatch.actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The contents of `dumpstatus.json`.
+#[derive(serde::Deserialize)]
+struct DumpStatus {
+    #[serde(default)]
+    jobs: std::collections::BTreeMap<String, JobStatus>,
+}
+
+#[derive(serde::Deserialize)]
+struct JobStatus {
+    status: String,
+    #[serde(default)]
+    files: std::collections::BTreeMap<String, FileStatus>,
+}
+
+#[derive(serde::Deserialize, Default)]
+struct FileStatus {
+    size: Option<u64>,
+    md5: Option<String>,
+    sha1: Option<String>,
+}
+
+/// The published metadata of every file, merged from all the metadata files found.
+#[derive(Default)]
+struct Metadata {
+    sources: Vec<std::path::PathBuf>,
+    files: std::collections::BTreeMap<String, FileStatus>,
+    jobs: Vec<(String, JobStatus)>,
+}
+
+impl Metadata {
+    /// Read the metadata files in `dir`: `dumpstatus.json`, `*-md5sums.txt` and `*-sha1sums.txt`.
+    fn read_dir(self: &mut Metadata, dir: &std::path::Path) -> std::io::Result<()> {
+        let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|res| res.ok())
+            .map(|e| e.path())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name == DUMP_STATUS_FILE {
+                let status: DumpStatus =
+                    serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(&path)?))?;
+                for (job_name, job) in status.jobs {
+                    for (file_name, file) in &job.files {
+                        let entry = self.files.entry(file_name.clone()).or_default();
+                        entry.size = entry.size.or(file.size);
+                        entry.md5 = entry.md5.take().or(file.md5.clone());
+                        entry.sha1 = entry.sha1.take().or(file.sha1.clone());
+                    }
+                    self.jobs.push((job_name, job));
+                }
+            } else if name.ends_with("-md5sums.txt") || name.ends_with("-sha1sums.txt") {
+                let md5 = name.ends_with("-md5sums.txt");
+                for line in std::fs::read_to_string(&path)?.lines() {
+                    // Each line is `{checksum}  {file name}`
+                    let Some((checksum, file_name)) = line.split_once(char::is_whitespace) else {
+                        continue;
+                    };
+                    let entry = self
+                        .files
+                        .entry(file_name.trim_start().to_string())
+                        .or_default();
+                    let checksum = Some(checksum.to_lowercase());
+                    if md5 {
+                        entry.md5 = entry.md5.take().or(checksum);
+                    } else {
+                        entry.sha1 = entry.sha1.take().or(checksum);
+                    }
+                }
+            } else {
+                continue;
+            }
+            self.sources.push(path);
+        }
+        Ok(())
+    }
+}
+
+/// Return the MD5 and SHA-1 checksums of the file at `path`, as lowercase hexadecimal.
+pub(crate) fn checksums(path: &std::path::Path) -> std::io::Result<(String, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut md5 = md5::Md5::new();
+    let mut sha1 = sha1::Sha1::new();
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let n = std::io::Read::read(&mut file, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        md5.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+    }
+    Ok((hex(&md5.finalize()), hex(&sha1.finalize())))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare a dump with its published metadata.
+fn check_dump(
+    dump: &Dump,
+    expected: &FileStatus,
+    file_name: &str,
+    with_checksums: bool,
+) -> std::io::Result<Vec<Mismatch>> {
+    let mut mismatched = Vec::new();
+    let mut check = |check: &'static str, expected: &Option<String>, actual: String| {
+        if let Some(expected) = expected {
+            if !expected.eq_ignore_ascii_case(&actual) {
+                mismatched.push(Mismatch {
+                    file_name: file_name.to_string(),
+                    check,
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+    };
+    check(
+        "size",
+        &expected.size.map(|size| size.to_string()),
+        dump.file_size()?.to_string(),
+    );
+    if with_checksums && (expected.md5.is_some() || expected.sha1.is_some()) {
+        let (md5, sha1) = checksums(dump.path())?;
+        check("md5", &expected.md5, md5);
+        check("sha1", &expected.sha1, sha1);
+    }
+    Ok(mismatched)
+}
+
+impl DumpStore {
+    /// Compare the dumps in the [`DumpStore`] with the `dumpstatus.json`, `*-md5sums.txt` and
+    /// `*-sha1sums.txt` files published alongside them, which are looked for in the dump directory
+    /// and the directories containing the dumps. Only published files of the kinds present in the
+    /// store are expected to be in it. If `with_checksums` is [`true`], every dump is read in full
+    /// to compare its checksums.
+    pub fn verify(self: &DumpStore, with_checksums: bool) -> std::io::Result<VerificationReport> {
+        let mut dirs: std::collections::BTreeSet<&std::path::Path> = self
+            .dumps()
+            .iter()
+            .filter_map(|d| d.path().parent())
+            .collect();
+        dirs.insert(self.dump_dir());
+        let mut metadata = Metadata::default();
+        for dir in dirs {
+            metadata.read_dir(dir)?;
+        }
+
+        // Published dump files of the same wikis, dates and kinds as those in the store
+        let groups: std::collections::BTreeSet<_> = self
+            .dumps()
+            .iter()
+            .map(|d| (d.dbname(), d.date(), d.kind()))
+            .collect();
+        let is_expected = |file_name: &str| {
+            Dump::new(file_name.into())
+                .is_some_and(|d| groups.contains(&(d.dbname(), d.date(), d.kind())))
+        };
+
+        let mut report = VerificationReport {
+            sources: metadata.sources.clone(),
+            ..VerificationReport::default()
+        };
+        for (name, job) in &metadata.jobs {
+            if job.status != "done" && job.files.keys().any(|f| is_expected(f)) {
+                report.incomplete_jobs.push(IncompleteJob {
+                    name: name.clone(),
+                    status: job.status.clone(),
+                });
+            }
+        }
+
+        let present: std::collections::BTreeSet<&str> = self
+            .dumps()
+            .iter()
+            .filter_map(|d| d.path().file_name()?.to_str())
+            .collect();
+        report.missing = metadata
+            .files
+            .keys()
+            .filter(|f| is_expected(f) && !present.contains(f.as_str()))
+            .cloned()
+            .collect();
+
+        let checked: Vec<std::io::Result<Vec<Mismatch>>> = self
+            .dumps()
+            .par_iter()
+            .filter_map(|dump| {
+                let file_name = dump.path().file_name()?.to_str()?;
+                let expected = metadata.files.get(file_name)?;
+                Some(check_dump(dump, expected, file_name, with_checksums))
+            })
+            .collect();
+        for mismatched in checked {
+            report.mismatched.extend(mismatched?);
+        }
+        report.extra = self
+            .dumps()
+            .iter()
+            .map(Dump::path)
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_none_or(|n| !metadata.files.contains_key(n))
+            })
+            .cloned()
+            .collect();
+        Ok(report)
+    }
+
+    /// Verify the dumps in the [`DumpStore`] (see [`DumpStore::verify`]) before any are read,
+    /// returning the store along with the [`VerificationReport`] (or [`None`] under
+    /// [`VerificationPolicy::Off`]). Under [`VerificationPolicy::Refuse`], problems are instead
+    /// returned as an error of kind [`std::io::ErrorKind::InvalidData`].
+    pub fn verified(
+        self: DumpStore,
+        verification: &Verification,
+    ) -> std::io::Result<(DumpStore, Option<VerificationReport>)> {
+        if verification.policy() == VerificationPolicy::Off {
+            return Ok((self, None));
+        }
+        let report = self.verify(verification.checksums())?;
+        if !report.is_ok() && verification.policy() == VerificationPolicy::Refuse {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Dumps failed verification:\n{}", report),
+            ));
+        }
+        Ok((self, Some(report)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems_are_reported_or_refused() {
+        let dir =
+            std::env::temp_dir().join(format!("wiki-dump-analyzer-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("enwiki-20230101-pages-articles1.xml.bz2"), b"abc").unwrap();
+        std::fs::write(dir.join("enwiki-20230101-pages-articles3.xml.bz2"), b"").unwrap();
+        std::fs::write(
+            dir.join(DUMP_STATUS_FILE),
+            r#"{"jobs": {
+                "articlesdump": {"status": "done", "files": {
+                    "enwiki-20230101-pages-articles1.xml.bz2": {"size": 4},
+                    "enwiki-20230101-pages-articles2.xml.bz2": {"size": 1}
+                }},
+                "logsdump": {"status": "failed", "files": {
+                    "enwiki-20230101-pages-logging.xml.gz": {}
+                }}
+            }}"#,
+        )
+        .unwrap();
+        let store = DumpStore::new(dir.clone()).unwrap();
+
+        let (_, report) = store.clone().verified(&Verification::default()).unwrap();
+        assert!(report.is_none());
+
+        let (_, report) = store
+            .clone()
+            .verified(&Verification::new(VerificationPolicy::Warn))
+            .unwrap();
+        let report = report.unwrap();
+        assert_eq!(report.sources, [dir.join(DUMP_STATUS_FILE)]);
+        assert_eq!(report.missing, ["enwiki-20230101-pages-articles2.xml.bz2"]);
+        assert_eq!(
+            report.extra,
+            [dir.join("enwiki-20230101-pages-articles3.xml.bz2")]
+        );
+        assert_eq!(
+            report.mismatched,
+            [Mismatch {
+                file_name: "enwiki-20230101-pages-articles1.xml.bz2".to_string(),
+                check: "size",
+                expected: "4".to_string(),
+                actual: "3".to_string(),
+            }]
+        );
+        // The failed job only produced files of another kind
+        assert!(report.incomplete_jobs.is_empty());
+
+        let err = store
+            .verified(&Verification::new(VerificationPolicy::Refuse))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksums_are_only_compared_when_requested() {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-verify-checksums-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_name = "enwiki-20230101-pages-articles1.xml.bz2";
+        std::fs::write(dir.join(file_name), b"abc").unwrap();
+        // The published MD5 is wrong, and the SHA-1 is right
+        std::fs::write(
+            dir.join("enwiki-20230101-md5sums.txt"),
+            format!("0123456789abcdef0123456789abcdef  {}\n", file_name),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("enwiki-20230101-sha1sums.txt"),
+            format!("A9993E364706816ABA3E25717850C26C9CD0D89D  {}\n", file_name),
+        )
+        .unwrap();
+        let store = DumpStore::new(dir.clone()).unwrap();
+
+        assert!(store.verify(false).unwrap().is_ok());
+        let report = store.verify(true).unwrap();
+        assert_eq!(
+            report.mismatched,
+            [Mismatch {
+                file_name: file_name.to_string(),
+                check: "md5",
+                expected: "0123456789abcdef0123456789abcdef".to_string(),
+                actual: "900150983cd24fb0d6963f7d28e17f72".to_string(),
+            }]
+        );
+
+        let verification = Verification::new(VerificationPolicy::Refuse);
+        assert!(store.clone().verified(&verification).is_ok());
+        let err = store
+            .verified(&verification.with_checksums(true))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
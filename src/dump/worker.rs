@@ -0,0 +1,515 @@
+//! Running analyses in child processes.
+//!
+//! A [`WorkerPool`] starts several copies of the current executable, sends each of them dumps to
+//! read, and collects the results they report. Each child must call [`run_worker_if_requested`]
+//! at the start of `main` with the analysis to perform, and must not otherwise write to stdout.
+//!
+//! This isolates analyses which use non-thread-safe libraries, or which leak memory, from each
+//! other. If a child crashes it is restarted, and resumes the dump it was reading after the last
+//! page it analyzed. A page which crashes the child every time can be skipped (see
+//! [`WorkerPool::with_skip_crashing_pages`]).
+
+use super::manifest::ManifestOptions;
+use super::{Dump, DumpStore};
+use std::io::{BufRead, Write};
+
+/// The environment variable which marks a process as a worker.
+const WORKER_ENV_VAR: &str = "WIKI_DUMP_ANALYZER_WORKER";
+
+/// A dump for a worker to read, sent as a line of JSON on its stdin.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct WorkerTask {
+    path: std::path::PathBuf,
+    /// The options of the [`DumpStore`] which affect which pages and revisions are read.
+    options: ManifestOptions,
+    /// Skip pages with IDs up to and including this one (which were analyzed before a crash).
+    after_page_id: Option<i64>,
+}
+
+/// A message from a worker, sent as a line of JSON on its stdout.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum WorkerMessage<R> {
+    /// A page is about to be analyzed.
+    Reading { page_id: i64 },
+    /// The result of analyzing a page.
+    Result { page_id: i64, value: Option<R> },
+    /// Reading the dump failed.
+    Failed { error: String },
+    /// The dump has been read completely.
+    Done,
+}
+
+/// A page skipped by a [`WorkerPool`] because analyzing it crashed the worker (see
+/// [`WorkerPool::with_skip_crashing_pages`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPage {
+    /// Path of the dump containing the page.
+    pub dump: std::path::PathBuf,
+    pub page_id: i64,
+}
+
+/// What happened while running a [`WorkerPool`], apart from the results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerReport {
+    /// Pages which were skipped because analyzing them crashed the worker.
+    pub skipped_pages: Vec<SkippedPage>,
+    /// Lines written to stdout by the workers which were not results, such as output printed by
+    /// the analysis itself.
+    pub stray_output: Vec<String>,
+}
+
+/// If this process was started by a [`WorkerPool`], read the dumps it is sent (with the options of
+/// the pool's [`DumpStore`]), report the result of `analyze` for each page (when it is not
+/// [`None`]), and exit. Otherwise, return immediately.
+pub fn run_worker_if_requested<R, F>(analyze: F)
+where
+    R: serde::Serialize,
+    F: Fn(crate::Page) -> Option<R>,
+{
+    if std::env::var_os(WORKER_ENV_VAR).is_none() {
+        return;
+    }
+
+    // Stdout is line buffered, so each message is sent as soon as it is written
+    let send = |message: &WorkerMessage<R>| {
+        let mut out = std::io::stdout().lock();
+        serde_json::to_writer(&mut out, message).expect("Failed to write worker message");
+        writeln!(out).expect("Failed to write worker message");
+    };
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("Failed to read worker task");
+        let task: WorkerTask = serde_json::from_str(&line).expect("Invalid worker task");
+        let dump = Dump::new(task.path).expect("Worker task is not a dump");
+        let options = task.options.scan_options().expect("Invalid worker options");
+
+        for page in dump.try_pages_with(&options) {
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    send(&WorkerMessage::Failed {
+                        error: e.to_string(),
+                    });
+                    break;
+                }
+            };
+            let page_id = page.id();
+            if task.after_page_id.is_some_and(|id| page_id <= id) {
+                continue;
+            }
+            send(&WorkerMessage::Reading { page_id });
+            let value = analyze(page);
+            send(&WorkerMessage::Result { page_id, value });
+        }
+        send(&WorkerMessage::Done);
+        std::io::stdout()
+            .flush()
+            .expect("Failed to write worker message");
+    }
+    std::process::exit(0);
+}
+
+/// Runs an analysis over the dumps of a [`DumpStore`] in child processes (see the
+/// [module documentation](self)).
+#[derive(Debug, Clone)]
+pub struct WorkerPool {
+    workers: usize,
+    max_restarts: usize,
+    skip_crashing_pages: bool,
+    program: std::path::PathBuf,
+    args: Vec<std::ffi::OsString>,
+}
+
+/// What a worker thread of a [`WorkerPool`] passes on to [`WorkerPool::run`].
+enum Event<R> {
+    Result(i64, R),
+    Skipped(SkippedPage),
+    Output(String),
+}
+
+impl WorkerPool {
+    /// Create a [`WorkerPool`] of `workers` copies of the current executable, started with the
+    /// same arguments as the current process.
+    pub fn new(workers: usize) -> std::io::Result<WorkerPool> {
+        Ok(WorkerPool {
+            workers: workers.max(1),
+            max_restarts: 3,
+            skip_crashing_pages: false,
+            program: std::env::current_exe()?,
+            args: std::env::args_os().skip(1).collect(),
+        })
+    }
+
+    /// Give up on a dump after a worker has crashed while reading it this many times.
+    pub fn with_max_restarts(mut self: WorkerPool, max_restarts: usize) -> WorkerPool {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Rather than giving up on a dump after [`WorkerPool::with_max_restarts`] crashes, skip the
+    /// page whose analysis crashed the worker the last time and carry on. Skipped pages are listed
+    /// in the [`WorkerReport`]. A crash while reading the dump rather than analyzing a page still
+    /// stops the run.
+    pub fn with_skip_crashing_pages(mut self: WorkerPool, skip: bool) -> WorkerPool {
+        self.skip_crashing_pages = skip;
+        self
+    }
+
+    /// Read every dump in `store` using the workers, calling `on_result` with the ID of each page
+    /// and the result reported for it. Results are passed on as they arrive, so pages are not in
+    /// any particular order.
+    ///
+    /// The workers read with the parser, memory budget and page shard of the store's options
+    /// (spilling the bodies of oversized pages to the system's temporary directory). Incremental
+    /// dumps (see [`DumpStore::with_increments`]) are not supported, and an error of kind
+    /// [`std::io::ErrorKind::InvalidInput`] is returned if the store has any.
+    pub fn run<R, F>(
+        self: &WorkerPool,
+        store: &DumpStore,
+        mut on_result: F,
+    ) -> std::io::Result<WorkerReport>
+    where
+        R: serde::de::DeserializeOwned + Send,
+        F: FnMut(i64, R),
+    {
+        if !store.increments().is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Worker processes cannot read incremental dumps",
+            ));
+        }
+        let options = ManifestOptions::new(store.options());
+        let mut dumps = store.page_dumps();
+        dumps.sort_by_cached_key(|d| std::cmp::Reverse(d.file_size().unwrap_or(0)));
+        let queue: std::sync::Mutex<std::collections::VecDeque<WorkerTask>> = std::sync::Mutex::new(
+            dumps
+                .into_iter()
+                .map(|d| WorkerTask {
+                    path: d.path().clone(),
+                    options: options.clone(),
+                    after_page_id: None,
+                })
+                .collect(),
+        );
+        let failed = std::sync::atomic::AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            for _ in 0..self.workers {
+                let sender = sender.clone();
+                let (queue, failed) = (&queue, &failed);
+                scope.spawn(move || {
+                    if let Err(e) = self.serve(queue, failed, &sender) {
+                        failed.store(true, std::sync::atomic::Ordering::Relaxed);
+                        let _ = sender.send(Err(e));
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut report = WorkerReport::default();
+            for message in receiver {
+                match message {
+                    Ok(Event::Result(page_id, value)) => on_result(page_id, value),
+                    Ok(Event::Skipped(page)) => report.skipped_pages.push(page),
+                    Ok(Event::Output(line)) => report.stray_output.push(line),
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(report)
+        })
+    }
+
+    /// Run one worker at a time, sending it tasks from `queue` until the queue is empty.
+    fn serve<R: serde::de::DeserializeOwned>(
+        self: &WorkerPool,
+        queue: &std::sync::Mutex<std::collections::VecDeque<WorkerTask>>,
+        failed: &std::sync::atomic::AtomicBool,
+        sender: &std::sync::mpsc::Sender<std::io::Result<Event<R>>>,
+    ) -> std::io::Result<()> {
+        let mut worker: Option<Worker> = None;
+        loop {
+            if failed.load(std::sync::atomic::Ordering::Relaxed) {
+                return Ok(());
+            }
+            let mut task = match queue.lock().unwrap().pop_front() {
+                Some(task) => task,
+                None => return Ok(()),
+            };
+
+            let mut restarts = 0;
+            loop {
+                let current = match &mut worker {
+                    Some(worker) => worker,
+                    None => worker.insert(Worker::spawn(self)?),
+                };
+                match current.run_task(&task, sender)? {
+                    TaskOutcome::Done => break,
+                    TaskOutcome::Crashed {
+                        last_page_id,
+                        page_id,
+                    } => {
+                        worker = None;
+                        task.after_page_id = last_page_id.or(task.after_page_id);
+                        restarts += 1;
+                        if restarts <= self.max_restarts {
+                            continue;
+                        }
+                        match page_id {
+                            Some(page_id) if self.skip_crashing_pages => {
+                                let page = SkippedPage {
+                                    dump: task.path.clone(),
+                                    page_id,
+                                };
+                                if sender.send(Ok(Event::Skipped(page))).is_err() {
+                                    return Ok(());
+                                }
+                                task.after_page_id = Some(page_id);
+                                restarts = 0;
+                            }
+                            _ => {
+                                return Err(std::io::Error::other(format!(
+                                    "Worker crashed {} times while reading {:?}",
+                                    restarts, task.path
+                                )))
+                            }
+                        }
+                    }
+                    TaskOutcome::Abandoned => return Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// What happened when a worker was sent a task.
+enum TaskOutcome {
+    Done,
+    /// The worker exited before finishing, after analyzing pages up to `last_page_id`, while
+    /// analyzing the page `page_id` (if it was analyzing a page).
+    Crashed {
+        last_page_id: Option<i64>,
+        page_id: Option<i64>,
+    },
+    /// The results are no longer wanted.
+    Abandoned,
+}
+
+/// A running worker process.
+struct Worker {
+    child: std::process::Child,
+    stdin: std::process::ChildStdin,
+    stdout: std::io::BufReader<std::process::ChildStdout>,
+}
+
+impl Worker {
+    fn spawn(pool: &WorkerPool) -> std::io::Result<Worker> {
+        let mut child = std::process::Command::new(&pool.program)
+            .args(&pool.args)
+            .env(WORKER_ENV_VAR, "1")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("Worker stdin is piped");
+        let stdout = std::io::BufReader::new(child.stdout.take().expect("Worker stdout is piped"));
+        Ok(Worker {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn run_task<R: serde::de::DeserializeOwned>(
+        self: &mut Worker,
+        task: &WorkerTask,
+        sender: &std::sync::mpsc::Sender<std::io::Result<Event<R>>>,
+    ) -> std::io::Result<TaskOutcome> {
+        let mut last_page_id = None;
+        let mut page_id = None;
+
+        let sent = serde_json::to_writer(&mut self.stdin, task)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writeln!(self.stdin))
+            .and_then(|_| self.stdin.flush());
+        if sent.is_err() {
+            return Ok(TaskOutcome::Crashed {
+                last_page_id,
+                page_id,
+            });
+        }
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            // A worker which closes its stdout (or whose stdout cannot be read) has crashed
+            match self.stdout.read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    return Ok(TaskOutcome::Crashed {
+                        last_page_id,
+                        page_id,
+                    })
+                }
+                Ok(_) => {}
+            }
+
+            let event = match serde_json::from_str::<WorkerMessage<R>>(&line) {
+                Ok(WorkerMessage::Reading { page_id: id }) => {
+                    page_id = Some(id);
+                    continue;
+                }
+                Ok(WorkerMessage::Result { page_id: id, value }) => {
+                    (last_page_id, page_id) = (Some(id), None);
+                    match value {
+                        Some(value) => Event::Result(id, value),
+                        None => continue,
+                    }
+                }
+                Ok(WorkerMessage::Failed { error }) => {
+                    return Err(std::io::Error::other(format!(
+                        "Failed to read {}: {}",
+                        task.path.display(),
+                        error
+                    )))
+                }
+                Ok(WorkerMessage::Done) => return Ok(TaskOutcome::Done),
+                // Anything else was printed by the analysis itself
+                Err(_) => Event::Output(line.trim_end().to_string()),
+            };
+            if sender.send(Ok(event)).is_err() {
+                return Ok(TaskOutcome::Abandoned);
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::page::test_page;
+    use crate::dump::{ScanOptions, Shard};
+
+    /// The entry point of the workers started by these tests, which runs this test alone in a
+    /// copy of the test executable. Analyzing the page titled `Crash` aborts the worker.
+    #[test]
+    fn worker_main() {
+        run_worker_if_requested(|page| {
+            match page.title().as_str() {
+                "Crash" => std::process::abort(),
+                "Print" => println!("Analyzing {}", page.id()),
+                _ => {}
+            }
+            Some(page.revisions().len())
+        });
+    }
+
+    fn pool() -> WorkerPool {
+        WorkerPool {
+            workers: 2,
+            max_restarts: 1,
+            skip_crashing_pages: false,
+            program: std::env::current_exe().unwrap(),
+            // Quietly, so that the test harness does not print the name of the test before the
+            // first message, and without capturing what the analysis prints
+            args: [
+                "dump::worker::tests::worker_main",
+                "--exact",
+                "--quiet",
+                "--nocapture",
+            ]
+            .map(std::ffi::OsString::from)
+            .to_vec(),
+        }
+    }
+
+    fn store(name: &str, titles: &[&str]) -> DumpStore {
+        let dir = std::env::temp_dir().join(format!(
+            "wiki-dump-analyzer-worker-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let pages: Vec<crate::Page> = titles
+            .iter()
+            .zip(1..)
+            .map(|(title, id)| test_page(id, title, &[(id, "2020-01-01T00:00:00Z", "Body")]))
+            .collect();
+        let (first, second) = pages.split_at(pages.len() / 2);
+        crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history1.xml-p1p3.7z",
+            first,
+        );
+        crate::dump::test_dump(
+            &dir,
+            "enwiki-20230101-pages-meta-history2.xml-p4p9.7z",
+            second,
+        );
+        DumpStore::new(dir).unwrap()
+    }
+
+    fn run(pool: &WorkerPool, store: &DumpStore) -> std::io::Result<(Vec<i64>, WorkerReport)> {
+        let mut ids = Vec::new();
+        let report = pool.run(store, |id, count: usize| {
+            assert_eq!(count, 1);
+            ids.push(id);
+        })?;
+        ids.sort();
+        Ok((ids, report))
+    }
+
+    #[test]
+    fn pages_are_read_with_the_store_options() {
+        let store = store("options", &["A", "B", "C", "D", "E", "F"]);
+        let shard = Shard::new(0, 2).unwrap();
+        let sharded = store
+            .clone()
+            .with_options(ScanOptions::new().with_page_shard(shard));
+        let (ids, report) = run(&pool(), &sharded).unwrap();
+        let expected: Vec<i64> = (1..=6).filter(|&id| shard.contains_page_id(id)).collect();
+        assert_eq!(ids, expected);
+        assert!(report.skipped_pages.is_empty());
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+
+    #[test]
+    fn crashing_pages_are_skipped_and_reported() {
+        let store = store("crash", &["A", "B", "C", "D", "Crash", "F"]);
+        let error = run(&pool(), &store).unwrap_err();
+        assert!(
+            error.to_string().contains("Worker crashed 2 times"),
+            "{}",
+            error
+        );
+
+        let (ids, report) = run(&pool().with_skip_crashing_pages(true), &store).unwrap();
+        assert_eq!(ids, [1, 2, 3, 4, 6]);
+        assert_eq!(
+            report.skipped_pages,
+            [SkippedPage {
+                dump: store
+                    .dumps()
+                    .iter()
+                    .find(|d| d.part() == Some(2))
+                    .unwrap()
+                    .path()
+                    .clone(),
+                page_id: 5,
+            }]
+        );
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+
+    #[test]
+    fn stray_output_is_reported() {
+        let store = store("output", &["A", "Print"]);
+        let (ids, report) = run(&pool(), &store).unwrap();
+        assert_eq!(ids, [1, 2]);
+        assert!(report.stray_output.contains(&"Analyzing 2".to_string()));
+        std::fs::remove_dir_all(store.dump_dir()).unwrap();
+    }
+}
@@ -9,7 +9,15 @@
 //! called with [`std::process::Command::new`].
 
 pub mod dump;
-pub use dump::{Dump, DumpStore, Page, PageIterator, Revision};
+pub use dump::{
+    run_worker_if_requested, CancellationToken, Checkpoint, CompactPage, ComparisonSummary,
+    CoverageIssue, CoverageReport, Dump, DumpCatalog, DumpComparison, DumpKind, DumpStore, Grep,
+    GrepRevision, GrepSpan, Manifest, ManifestCounts, ManifestDump, ManifestOptions, MemoryBudget,
+    OversizePolicy, OversizedPage, Page, PageChange, PageIndex, PageIterator, PageLookup,
+    PageMatches, PageMove, Parser, Progress, ProgressReport, Revision, ScanOptions, Schedule,
+    ScheduledPages, Shard, ShardMode, SiteInfo, SkippedPage, TitleHistories, TitleHistory,
+    TitleRecord, Verification, VerificationPolicy, VerificationReport, WorkerPool, WorkerReport,
+};
 
 // #[cfg(test)]
 // mod tests {